@@ -0,0 +1,118 @@
+//! 以压缩包（.tar.gz / .tgz / .zip）分发的 phar：下载后从包内取出 .phar 放入缓存。
+
+use crate::error::{Error, Result};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    TarGz,
+    Zip,
+}
+
+impl ArchiveKind {
+    /// 按文件名或 URL（忽略查询串）判断压缩包类型
+    pub fn detect(name: &str) -> Option<Self> {
+        let name = name
+            .split(['?', '#'])
+            .next()
+            .unwrap_or(name)
+            .to_ascii_lowercase();
+        if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Self::TarGz)
+        } else if name.ends_with(".zip") {
+            Some(Self::Zip)
+        } else {
+            None
+        }
+    }
+}
+
+/// 从压缩包中取出第一个 .phar 写到 dest；包内没有 phar 时返回错误
+pub fn extract_phar(archive: &Path, kind: ArchiveKind, dest: &Path) -> Result<()> {
+    let found = match kind {
+        ArchiveKind::TarGz => extract_from_tar_gz(archive, dest)?,
+        ArchiveKind::Zip => extract_from_zip(archive, dest)?,
+    };
+    if !found {
+        return Err(Error::Archive(format!(
+            "No .phar file found in {}",
+            archive.display()
+        )));
+    }
+    Ok(())
+}
+
+fn extract_from_tar_gz(archive: &Path, dest: &Path) -> Result<bool> {
+    let decoder = flate2::read::GzDecoder::new(File::open(archive)?);
+    let mut tar = tar::Archive::new(decoder);
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        let is_phar = entry.header().entry_type().is_file()
+            && entry.path()?.to_string_lossy().ends_with(".phar");
+        if is_phar {
+            write_entry(&mut entry, dest)?;
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn extract_from_zip(archive: &Path, dest: &Path) -> Result<bool> {
+    let mut zip = zip::ZipArchive::new(File::open(archive)?)
+        .map_err(|e| Error::Archive(format!("Invalid zip {}: {}", archive.display(), e)))?;
+    for i in 0..zip.len() {
+        let mut entry = zip
+            .by_index(i)
+            .map_err(|e| Error::Archive(format!("Invalid zip {}: {}", archive.display(), e)))?;
+        if entry.is_file() && entry.name().ends_with(".phar") {
+            write_entry(&mut entry, dest)?;
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// 只按文件名取内容写入 dest，不使用包内路径，避免 ../ 之类的路径穿越
+fn write_entry(entry: &mut impl Read, dest: &Path) -> Result<()> {
+    // dest 可能是去重 blob 的硬链接，先删除以免改写共享内容
+    let _ = std::fs::remove_file(dest);
+    let mut out = File::create(dest)?;
+    std::io::copy(entry, &mut out)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_phar_from_tar_gz() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("tool.tar.gz");
+
+        let encoder = flate2::write::GzEncoder::new(
+            File::create(&archive).unwrap(),
+            flate2::Compression::default(),
+        );
+        let mut builder = tar::Builder::new(encoder);
+        let content = b"<?php echo 'hi';";
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "tool-1.0/bin/tool.phar", &content[..])
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let dest = dir.path().join("tool.phar");
+        assert_eq!(
+            ArchiveKind::detect("https://x/tool.tar.gz?dl=1"),
+            Some(ArchiveKind::TarGz)
+        );
+        extract_phar(&archive, ArchiveKind::TarGz, &dest).unwrap();
+        assert_eq!(std::fs::read(&dest).unwrap(), content);
+    }
+}
@@ -1,6 +1,7 @@
-use crate::error::Result;
+use crate::error::{Error, Result};
+use crate::security::{HashCheck, SecurityManager};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
 
@@ -18,6 +19,28 @@ mod path_serde {
     }
 }
 
+/// 将工具名、版本等外部输入编码为安全的单个路径段，防止写出 cache_dir：
+/// 路径分隔符及其它非常规字符替换为 '-'（vendor/package -> vendor-package），
+/// 开头的 '.' 替换为 '_'（使 ".."、".hidden" 无法成为特殊路径），空串编码为 "_"
+pub fn sanitize_component(raw: &str) -> String {
+    let mut out: String = raw
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-' | '+' | '@') {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    let leading_dots = out.len() - out.trim_start_matches('.').len();
+    out.replace_range(..leading_dots, &"_".repeat(leading_dots));
+    if out.is_empty() {
+        out.push('_');
+    }
+    out
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CacheEntry {
     pub tool_name: String,
@@ -25,6 +48,7 @@ pub struct CacheEntry {
     #[serde(with = "path_serde")]
     pub file_path: PathBuf,
     pub download_url: String,
+    /// 记录时文件的哈希，带算法前缀（sha256:…）；旧条目为不带前缀的 md5
     pub file_hash: Option<String>,
     pub created_at: u64,
     pub last_accessed: u64,
@@ -32,14 +56,172 @@ pub struct CacheEntry {
     /// Composer 安装目录时对应的 bin 名（如 rector）；phar 条目为 None
     #[serde(default)]
     pub bin_name: Option<String>,
+    /// Composer 包声明的全部 bin，供 `vendor/package:bin` 选择；旧条目为空
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bin_names: Vec<String>,
     /// 是否为 Composer 安装目录（删除时需 remove_dir_all）
     #[serde(default)]
     pub is_composer: bool,
+    /// Composer 安装时使用的 PHP 版本；换用其它 PHP 后据此判断安装是否仍然可用
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub php_version: Option<String>,
+    /// 安装该版本时用户给出的版本约束（如 ^3）；按同一约束运行时据此复用缓存
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub constraint: Option<String>,
+    /// 启用 dedupe_phars 时 phar 内容的 sha256；file_path 是 blobs/<sha256> 的硬链接
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blob: Option<String>,
+    /// 固定的条目（phpx cache pin）不会被 TTL 清理或 prune 删除，只能用 cache clean 显式删除
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
+}
+
+/// 递归复制目录；符号链接（如 vendor/bin 中的链接）在 Unix 上原样重建，其它平台复制其指向的内容
+fn copy_tree(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(to)?;
+    for child in std::fs::read_dir(from)? {
+        let child = child?;
+        let target = to.join(child.file_name());
+        let file_type = child.file_type()?;
+        if file_type.is_dir() {
+            copy_tree(&child.path(), &target)?;
+        } else if file_type.is_symlink() {
+            #[cfg(unix)]
+            std::os::unix::fs::symlink(std::fs::read_link(child.path())?, &target)?;
+            #[cfg(not(unix))]
+            std::fs::copy(child.path(), &target).map(|_| ())?;
+        } else {
+            std::fs::copy(child.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+/// 递归统计目录下普通文件的总大小（不跟随符号链接）
+fn dir_size(path: &Path) -> u64 {
+    let Ok(read_dir) = std::fs::read_dir(path) else {
+        return 0;
+    };
+    read_dir
+        .flatten()
+        .map(|child| match child.metadata() {
+            Ok(meta) if meta.is_dir() => dir_size(&child.path()),
+            Ok(meta) if meta.is_file() => meta.len(),
+            _ => 0,
+        })
+        .sum()
+}
+
+/// 目录能否创建并写入文件（只读的 home、受限的 CI 中 cache_dir 可能不可写）
+pub fn is_writable_dir(dir: &Path) -> bool {
+    if std::fs::create_dir_all(dir).is_err() {
+        return false;
+    }
+    let probe = dir.join(format!(".write-test-{}", std::process::id()));
+    let writable = std::fs::write(&probe, b"").is_ok();
+    let _ = std::fs::remove_file(&probe);
+    writable
+}
+
+/// phpx 在磁盘上各部分占用的字节数（phpx cache size）
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DiskUsage {
+    /// cache_dir 顶层的 phar 与索引文件；dedupe 的 blobs 与这些 phar 是硬链接，不重复计算
+    pub phars: u64,
+    /// Composer 隔离安装（install_base/composer）
+    pub composer: u64,
+    /// override 安装（install_base/override）
+    pub override_installs: u64,
+    pub composer_home: u64,
+    /// Composer 下载缓存
+    pub composer_cache: u64,
+}
+
+impl DiskUsage {
+    /// 统计 cache_dir 与安装根目录（两者可以相同）下各部分的大小
+    pub fn measure(cache_dir: &Path, install_base: &Path) -> Self {
+        let phars = std::fs::read_dir(cache_dir)
+            .map(|read_dir| {
+                read_dir
+                    .flatten()
+                    .filter_map(|child| child.metadata().ok())
+                    .filter(|meta| meta.is_file())
+                    .map(|meta| meta.len())
+                    .sum()
+            })
+            .unwrap_or(0);
+        Self {
+            phars,
+            composer: dir_size(&install_base.join("composer")),
+            override_installs: dir_size(&install_base.join("override")),
+            composer_home: dir_size(&cache_dir.join("composer_home")),
+            composer_cache: dir_size(&cache_dir.join("composer_cache")),
+        }
+    }
+
+    /// 各部分的显示名与大小
+    pub fn categories(&self) -> [(&'static str, u64); 5] {
+        [
+            ("phars", self.phars),
+            ("composer", self.composer),
+            ("override", self.override_installs),
+            ("composer_home", self.composer_home),
+            ("composer_cache", self.composer_cache),
+        ]
+    }
+
+    pub fn total(&self) -> u64 {
+        self.categories().iter().map(|(_, size)| size).sum()
+    }
+}
+
+/// cache.json 的格式版本；CacheEntry 出现无法靠 serde(default) 兼容的改动时递增，
+/// 并在 parse_cache_file 中补充从旧版本的迁移
+const CACHE_SCHEMA_VERSION: u64 = 1;
+
+/// cache.json 的磁盘格式
+#[derive(Serialize)]
+struct CacheFile<'a> {
+    schema_version: u64,
+    entries: &'a HashMap<String, CacheEntry>,
+}
+
+/// 解析 cache.json：当前版本直接读取；没有 schema_version 的旧格式（顶层即条目表）就地迁移；
+/// 更新版本的 phpx 写出的格式或无法解析的内容返回 Err（原因），由调用方丢弃
+fn parse_cache_file(content: &str) -> std::result::Result<HashMap<String, CacheEntry>, String> {
+    let mut value: serde_json::Value = serde_json::from_str(content).map_err(|e| e.to_string())?;
+    let entries = match value.get("schema_version").and_then(|v| v.as_u64()) {
+        Some(CACHE_SCHEMA_VERSION) => value["entries"].take(),
+        Some(version) => {
+            return Err(format!(
+                "unsupported schema_version {} (expected {})",
+                version, CACHE_SCHEMA_VERSION
+            ))
+        }
+        // 版本 0：条目键为 "tool:version"，不会与 schema_version 冲突
+        None => value,
+    };
+    serde_json::from_value(entries).map_err(|e| e.to_string())
+}
+
+/// cache import 的结果
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ImportSummary {
+    /// 新加入缓存的条目数
+    pub imported: usize,
+    /// 因已存在或哈希不符而跳过的条目数
+    pub skipped: usize,
+    /// file_path 为绝对路径或含 .. 而拒绝的条目数（归档可能被篡改）
+    pub rejected: usize,
 }
 
 pub struct CacheManager {
     cache_dir: PathBuf,
     entries: HashMap<String, CacheEntry>,
+    /// 内存中有未写回 cache.json 的改动（如 last_accessed 更新），在 flush 或 drop 时写回
+    dirty: bool,
+    /// 按内容去重：相同字节的 phar 共享 blobs/<sha256> 中的一份文件
+    dedupe: bool,
 }
 
 impl CacheManager {
@@ -47,12 +229,20 @@ impl CacheManager {
         let mut manager = Self {
             cache_dir,
             entries: HashMap::new(),
+            dirty: false,
+            dedupe: false,
         };
 
         manager.load_cache()?;
         Ok(manager)
     }
 
+    /// 开启后新加入的 phar 条目以硬链接共享 blobs/<sha256>，已有条目不受影响
+    pub fn with_dedupe(mut self, dedupe: bool) -> Self {
+        self.dedupe = dedupe;
+        self
+    }
+
     pub fn get_entry(&mut self, tool_name: &str, version: &str) -> Option<&CacheEntry> {
         let key = Self::build_key(tool_name, version);
         if let Some(entry) = self.entries.get_mut(&key) {
@@ -60,12 +250,49 @@ impl CacheManager {
                 .duration_since(UNIX_EPOCH)
                 .unwrap()
                 .as_secs();
+            // 访问时间批量写回，避免每次访问都重写 cache.json
+            self.dirty = true;
             Some(entry)
         } else {
             None
         }
     }
 
+    /// 记录某个已缓存版本是按哪个版本约束解析得到的
+    pub fn set_constraint(
+        &mut self,
+        tool_name: &str,
+        version: &str,
+        constraint: &str,
+    ) -> Result<()> {
+        let key = Self::build_key(tool_name, version);
+        if let Some(entry) = self.entries.get_mut(&key) {
+            entry.constraint = Some(constraint.to_string());
+            self.save_cache()?;
+        }
+        Ok(())
+    }
+
+    /// 固定或取消固定工具的缓存条目；version 为 None 时作用于该工具的全部版本。返回改动的条目数
+    pub fn set_pinned(
+        &mut self,
+        tool_name: &str,
+        version: Option<&str>,
+        pinned: bool,
+    ) -> Result<usize> {
+        let mut changed = 0;
+        for entry in self.entries.values_mut() {
+            if entry.tool_name == tool_name && version.is_none_or(|v| entry.version == v) {
+                entry.pinned = pinned;
+                changed += 1;
+            }
+        }
+        if changed > 0 {
+            self.save_cache()?;
+        }
+        Ok(changed)
+    }
+
     pub fn add_entry(
         &mut self,
         tool_name: String,
@@ -94,6 +321,8 @@ impl CacheManager {
         version: String,
         dir_path: PathBuf,
         bin_name: String,
+        bin_names: Vec<String>,
+        php_version: Option<String>,
     ) -> Result<()> {
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
@@ -109,14 +338,21 @@ impl CacheManager {
             last_accessed: now,
             size: 0,
             bin_name: Some(bin_name),
+            bin_names,
             is_composer: true,
+            php_version,
+            constraint: None,
+            blob: None,
+            pinned: false,
         };
         let key = Self::build_key(&entry.tool_name, &entry.version);
-        self.entries.insert(key, entry);
+        let pinned = self.entries.get(&key).is_some_and(|e| e.pinned);
+        self.entries.insert(key, CacheEntry { pinned, ..entry });
         self.save_cache()?;
         Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn add_entry_inner(
         &mut self,
         tool_name: String,
@@ -133,6 +369,12 @@ impl CacheManager {
             .unwrap()
             .as_secs();
 
+        let blob = if self.dedupe && !is_composer {
+            self.store_blob(&file_path)?
+        } else {
+            None
+        };
+
         let entry = CacheEntry {
             tool_name,
             version,
@@ -143,28 +385,87 @@ impl CacheManager {
             last_accessed: now,
             size,
             bin_name,
+            bin_names: Vec::new(),
             is_composer,
+            php_version: None,
+            constraint: None,
+            blob,
+            pinned: false,
         };
 
+        // 重新下载同一版本时保留固定状态
         let key = Self::build_key(&entry.tool_name, &entry.version);
-        self.entries.insert(key, entry);
+        let pinned = self.entries.get(&key).is_some_and(|e| e.pinned);
+        if let Some(replaced) = self.entries.insert(key, CacheEntry { pinned, ..entry }) {
+            self.release_blob(&replaced);
+        }
         self.save_cache()?;
 
         Ok(())
     }
 
+    /// 将 file_path 的内容登记到 blobs/<sha256> 并让 file_path 成为该 blob 的硬链接，返回 sha256。
+    /// 文件系统不支持硬链接时保留原文件、不去重（返回 None）
+    fn store_blob(&self, file_path: &Path) -> Result<Option<String>> {
+        let hash = SecurityManager::sha256_file(file_path)?;
+
+        let blobs_dir = self.cache_dir.join("blobs");
+        std::fs::create_dir_all(&blobs_dir)?;
+        let blob_path = blobs_dir.join(&hash);
+
+        if !blob_path.exists() {
+            if let Err(e) = std::fs::hard_link(file_path, &blob_path) {
+                tracing::debug!("Content-addressed storage unavailable: {}", e);
+                return Ok(None);
+            }
+            return Ok(Some(hash));
+        }
+
+        // 已有相同内容：先链接到临时名再 rename 覆盖，任一步失败都不影响原文件
+        let staged = file_path.with_extension("blob-link");
+        let linked = std::fs::hard_link(&blob_path, &staged)
+            .and_then(|_| std::fs::rename(&staged, file_path));
+        if let Err(e) = linked {
+            let _ = std::fs::remove_file(&staged);
+            tracing::debug!("Failed to link {:?} to blob {}: {}", file_path, hash, e);
+            return Ok(None);
+        }
+        Ok(Some(hash))
+    }
+
+    /// 删除条目对应的文件；条目共享的 blob 在不再被任何条目引用时一并删除
+    fn remove_entry_files(&self, entry: &CacheEntry) -> std::io::Result<()> {
+        if entry.file_path.exists() {
+            if entry.is_composer {
+                std::fs::remove_dir_all(&entry.file_path)?;
+            } else {
+                std::fs::remove_file(&entry.file_path)?;
+            }
+        }
+        self.release_blob(entry);
+        Ok(())
+    }
+
+    /// entry 已从 entries 中移除后调用：没有其它条目引用其 blob 时删除 blob
+    fn release_blob(&self, entry: &CacheEntry) {
+        let Some(hash) = &entry.blob else {
+            return;
+        };
+        let referenced = self
+            .entries
+            .values()
+            .any(|other| other.blob.as_ref() == Some(hash));
+        if !referenced {
+            let _ = std::fs::remove_file(self.cache_dir.join("blobs").join(hash));
+        }
+    }
+
     pub fn remove_entry(&mut self, tool_name: &str, version: Option<&str>) -> Result<()> {
         match version {
             Some(ver) => {
                 let key = Self::build_key(tool_name, ver);
                 if let Some(entry) = self.entries.remove(&key) {
-                    if entry.file_path.exists() {
-                        if entry.is_composer {
-                            std::fs::remove_dir_all(&entry.file_path)?;
-                        } else {
-                            std::fs::remove_file(&entry.file_path)?;
-                        }
-                    }
+                    self.remove_entry_files(&entry)?;
                 }
             }
             None => {
@@ -177,13 +478,7 @@ impl CacheManager {
 
                 for key in keys_to_remove {
                     if let Some(entry) = self.entries.remove(&key) {
-                        if entry.file_path.exists() {
-                            if entry.is_composer {
-                                std::fs::remove_dir_all(&entry.file_path)?;
-                            } else {
-                                std::fs::remove_file(&entry.file_path)?;
-                            }
-                        }
+                        self.remove_entry_files(&entry)?;
                     }
                 }
             }
@@ -193,39 +488,322 @@ impl CacheManager {
         Ok(())
     }
 
+    /// 把整个缓存目录（phar、Composer 安装、blobs 等）移动到 new_dir 并改写各条目的 file_path，
+    /// 返回改写的条目数。new_dir 须不存在或为空目录；跨文件系统时先复制再删除原目录，
+    /// 复制后去重的 phar 重新硬链接到 blobs
+    pub fn relocate(&mut self, new_dir: &Path) -> Result<usize> {
+        let old_dir = self.cache_dir.clone();
+        if new_dir.starts_with(&old_dir) || old_dir.starts_with(new_dir) {
+            return Err(Error::Cache(format!(
+                "Cannot move the cache between {} and {}: one contains the other",
+                old_dir.display(),
+                new_dir.display()
+            )));
+        }
+        if new_dir.exists() {
+            if std::fs::read_dir(new_dir)?.next().is_some() {
+                return Err(Error::Cache(format!(
+                    "Target directory {} is not empty",
+                    new_dir.display()
+                )));
+            }
+            std::fs::remove_dir(new_dir)?;
+        }
+        if let Some(parent) = new_dir.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        if old_dir.exists() {
+            if let Err(e) = std::fs::rename(&old_dir, new_dir) {
+                // 多为跨文件系统（EXDEV），退回复制后删除
+                tracing::debug!("rename failed ({}), copying the cache instead", e);
+                if let Err(e) = copy_tree(&old_dir, new_dir) {
+                    let _ = std::fs::remove_dir_all(new_dir);
+                    return Err(e.into());
+                }
+                std::fs::remove_dir_all(&old_dir)?;
+            }
+        } else {
+            std::fs::create_dir_all(new_dir)?;
+        }
+
+        let mut moved = 0;
+        for entry in self.entries.values_mut() {
+            if let Ok(relative) = entry.file_path.strip_prefix(&old_dir) {
+                entry.file_path = new_dir.join(relative);
+                moved += 1;
+            }
+        }
+        self.cache_dir = new_dir.to_path_buf();
+
+        for entry in self.entries.values() {
+            let Some(hash) = &entry.blob else {
+                continue;
+            };
+            let blob_path = self.cache_dir.join("blobs").join(hash);
+            let staged = entry.file_path.with_extension("blob-link");
+            let linked = std::fs::hard_link(&blob_path, &staged)
+                .and_then(|_| std::fs::rename(&staged, &entry.file_path));
+            if let Err(e) = linked {
+                let _ = std::fs::remove_file(&staged);
+                tracing::debug!(
+                    "Failed to relink {:?} to blob {}: {}",
+                    entry.file_path,
+                    hash,
+                    e
+                );
+            }
+        }
+
+        self.save_cache()?;
+        Ok(moved)
+    }
+
     pub fn list_entries(&self) -> Vec<&CacheEntry> {
         self.entries.values().collect()
     }
 
+    /// 将 cache.json 与其引用的 phar、Composer 安装目录打包为 tar.gz，返回导出的条目数。
+    /// 包内路径相对于 cache_dir，cache.json 中的 file_path 也改写为相对路径
+    pub fn export(&self, archive: &Path) -> Result<usize> {
+        let file = std::fs::File::create(archive)?;
+        let encoder = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+
+        let mut exported = HashMap::new();
+        for (key, entry) in &self.entries {
+            if !entry.file_path.exists() {
+                tracing::warn!("Skipping {}: {} is missing", key, entry.file_path.display());
+                continue;
+            }
+            let relative = match entry.file_path.strip_prefix(&self.cache_dir) {
+                Ok(relative) => relative.to_path_buf(),
+                Err(_) => PathBuf::from(entry.file_path.file_name().unwrap_or_default()),
+            };
+            if entry.is_composer {
+                builder.append_dir_all(&relative, &entry.file_path)?;
+            } else {
+                builder.append_path_with_name(&entry.file_path, &relative)?;
+            }
+            let mut entry = entry.clone();
+            entry.file_path = relative;
+            entry.blob = None;
+            exported.insert(key.clone(), entry);
+        }
+
+        let manifest = serde_json::to_vec_pretty(&CacheFile {
+            schema_version: CACHE_SCHEMA_VERSION,
+            entries: &exported,
+        })?;
+        let mut header = tar::Header::new_gnu();
+        header.set_size(manifest.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, "cache.json", manifest.as_slice())?;
+        builder.into_inner()?.finish()?;
+        Ok(exported.len())
+    }
+
+    /// 解包 export 生成的归档并合并条目：file_path 改写到当前 cache_dir；
+    /// 已有同名条目或目标路径已存在的跳过，phar 的哈希与记录不符的也跳过；
+    /// file_path 不是归档内相对路径（绝对路径、含 ..）的条目拒绝导入并单独计数
+    pub fn import(&mut self, archive: &Path) -> Result<ImportSummary> {
+        std::fs::create_dir_all(&self.cache_dir)?;
+        let staging = self
+            .cache_dir
+            .join(format!(".import-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&staging);
+        let result = self.import_from(archive, &staging);
+        let _ = std::fs::remove_dir_all(&staging);
+        result
+    }
+
+    fn import_from(&mut self, archive: &Path, staging: &Path) -> Result<ImportSummary> {
+        let file = std::fs::File::open(archive)?;
+        tar::Archive::new(flate2::read::GzDecoder::new(file))
+            .unpack(staging)
+            .map_err(|e| Error::Cache(format!("Cannot unpack {}: {}", archive.display(), e)))?;
+        let manifest = std::fs::read_to_string(staging.join("cache.json")).map_err(|_| {
+            Error::Cache(format!("{} is not a phpx cache export", archive.display()))
+        })?;
+        let imported = parse_cache_file(&manifest)
+            .map_err(|e| Error::Cache(format!("Cannot read {}: {}", archive.display(), e)))?;
+
+        let mut summary = ImportSummary::default();
+        for (key, mut entry) in imported {
+            let relative = entry.file_path.clone();
+            let safe = relative
+                .components()
+                .all(|c| matches!(c, std::path::Component::Normal(_)));
+            if !safe {
+                tracing::warn!(
+                    "Rejecting {}: unsafe file path {} in archive",
+                    key,
+                    relative.display()
+                );
+                summary.rejected += 1;
+                continue;
+            }
+            let dest = self.cache_dir.join(&relative);
+            if self.entries.contains_key(&key) || dest.exists() {
+                tracing::info!("Skipping {}: already cached", key);
+                summary.skipped += 1;
+                continue;
+            }
+            let unpacked = staging.join(&relative);
+            if !entry.is_composer {
+                let expected = entry.file_hash.as_deref().filter(|h| !h.is_empty());
+                if let Some(expected) = expected {
+                    let matches =
+                        HashCheck::compute(&unpacked, expected).is_ok_and(|check| check.matches());
+                    if !matches {
+                        tracing::warn!("Skipping {}: hash mismatch", key);
+                        summary.skipped += 1;
+                        continue;
+                    }
+                }
+            }
+            if let Some(parent) = dest.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::rename(&unpacked, &dest)?;
+            entry.file_path = dest;
+            entry.blob = None;
+            self.entries.insert(key, entry);
+            summary.imported += 1;
+        }
+
+        self.save_cache()?;
+        Ok(summary)
+    }
+
     pub fn cleanup_old_entries(&mut self, ttl: u64) -> Result<()> {
         let now = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
             .as_secs();
+        self.remove_where(|entry| !entry.pinned && now.saturating_sub(entry.last_accessed) > ttl)?;
+        Ok(())
+    }
+
+    /// 删除超过 older_than 秒未访问、或占用超过 larger_than 字节的条目（满足任一条件即删除，固定的条目除外），
+    /// 返回被删除的条目及其占用字节数；dry_run 时只返回将被删除的条目，不做改动
+    pub fn prune(
+        &mut self,
+        older_than: Option<u64>,
+        larger_than: Option<u64>,
+        dry_run: bool,
+    ) -> Result<Vec<(CacheEntry, u64)>> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let mut matched: Vec<(CacheEntry, u64)> = self
+            .entries
+            .values()
+            .filter(|entry| !entry.pinned)
+            .map(|entry| (entry.clone(), Self::disk_size(entry)))
+            .filter(|(entry, size)| {
+                older_than.is_some_and(|age| now.saturating_sub(entry.last_accessed) > age)
+                    || larger_than.is_some_and(|limit| *size > limit)
+            })
+            .collect();
+        matched.sort_by(|a, b| (&a.0.tool_name, &a.0.version).cmp(&(&b.0.tool_name, &b.0.version)));
+
+        if !dry_run {
+            let keys: HashSet<String> = matched
+                .iter()
+                .map(|(entry, _)| Self::build_key(&entry.tool_name, &entry.version))
+                .collect();
+            self.remove_where(|entry| {
+                keys.contains(&Self::build_key(&entry.tool_name, &entry.version))
+            })?;
+        }
+        Ok(matched)
+    }
 
+    /// 删除满足 pred 的条目及其文件并保存索引，返回删除的条目数
+    fn remove_where(&mut self, pred: impl Fn(&CacheEntry) -> bool) -> Result<usize> {
         let keys_to_remove: Vec<String> = self
             .entries
             .iter()
-            .filter(|(_, entry)| now - entry.last_accessed > ttl)
+            .filter(|(_, entry)| pred(entry))
             .map(|(key, _)| key.clone())
             .collect();
 
-        for key in keys_to_remove {
-            if let Some(entry) = self.entries.remove(&key) {
-                if entry.file_path.exists() {
-                    if entry.is_composer {
-                        let _ = std::fs::remove_dir_all(&entry.file_path);
-                    } else {
-                        let _ = std::fs::remove_file(&entry.file_path);
-                    }
-                }
+        for key in &keys_to_remove {
+            if let Some(entry) = self.entries.remove(key) {
+                let _ = self.remove_entry_files(&entry);
             }
         }
 
         self.save_cache()?;
+        Ok(keys_to_remove.len())
+    }
+
+    /// 条目在磁盘上占用的字节数；Composer 安装目录的 size 记为 0，需要遍历目录统计
+    pub fn disk_size(entry: &CacheEntry) -> u64 {
+        if entry.is_composer {
+            dir_size(&entry.file_path)
+        } else {
+            entry.size
+        }
+    }
+
+    /// 距上次清理超过 interval 秒时才执行 TTL 清理，时间戳记录在 cache_dir/.last_gc。
+    /// 返回本次是否实际执行了清理。
+    pub fn cleanup_if_due(&mut self, ttl: u64, interval: u64) -> Result<bool> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let marker = self.cache_dir.join(".last_gc");
+        let last_gc = std::fs::read_to_string(&marker)
+            .ok()
+            .and_then(|s| s.trim().parse::<u64>().ok())
+            .unwrap_or(0);
+        if now.saturating_sub(last_gc) < interval {
+            return Ok(false);
+        }
+
+        self.cleanup_old_entries(ttl)?;
+        std::fs::write(&marker, now.to_string())?;
+        Ok(true)
+    }
+
+    /// 该标识符在 ttl 秒内是否解析失败过（负缓存，记录在 cache_dir/negative.json）
+    pub fn is_known_missing(&self, identifier: &str, ttl: u64) -> bool {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        self.load_negative()
+            .get(identifier)
+            .is_some_and(|&failed_at| now.saturating_sub(failed_at) < ttl)
+    }
+
+    /// 记录一次解析失败，同时丢弃超过 ttl 的旧记录
+    pub fn record_missing(&self, identifier: &str, ttl: u64) -> Result<()> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let mut negative = self.load_negative();
+        negative.retain(|_, failed_at| now.saturating_sub(*failed_at) < ttl);
+        negative.insert(identifier.to_string(), now);
+        std::fs::create_dir_all(&self.cache_dir)?;
+        let content = serde_json::to_string_pretty(&negative)?;
+        std::fs::write(self.cache_dir.join("negative.json"), content)?;
         Ok(())
     }
 
+    fn load_negative(&self) -> HashMap<String, u64> {
+        std::fs::read_to_string(self.cache_dir.join("negative.json"))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
     fn build_key(tool_name: &str, version: &str) -> String {
         format!("{}:{}", tool_name, version)
     }
@@ -233,21 +811,375 @@ impl CacheManager {
     fn load_cache(&mut self) -> Result<()> {
         let cache_file = self.cache_dir.join("cache.json");
         if cache_file.exists() {
-            let content = std::fs::read_to_string(cache_file)?;
-            self.entries = serde_json::from_str(&content)?;
+            let content = std::fs::read_to_string(&cache_file)?;
+            match parse_cache_file(&content) {
+                Ok(entries) => self.entries = entries,
+                Err(reason) => {
+                    // 不因格式不兼容而失败：保留原文件备查，以空缓存继续，工具会重新下载
+                    let backup = cache_file.with_extension("json.bak");
+                    tracing::warn!(
+                        "Discarding incompatible cache index {} ({}), moved to {}",
+                        cache_file.display(),
+                        reason,
+                        backup.display()
+                    );
+                    std::fs::rename(&cache_file, &backup)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 将未保存的改动（如 last_accessed）写回 cache.json
+    pub fn flush(&mut self) -> Result<()> {
+        if self.dirty {
+            self.save_cache()?;
         }
         Ok(())
     }
 
-    fn save_cache(&self) -> Result<()> {
+    fn save_cache(&mut self) -> Result<()> {
         if !self.cache_dir.exists() {
             std::fs::create_dir_all(&self.cache_dir)?;
         }
 
         let cache_file = self.cache_dir.join("cache.json");
-        let content = serde_json::to_string_pretty(&self.entries)?;
+        let content = serde_json::to_string_pretty(&CacheFile {
+            schema_version: CACHE_SCHEMA_VERSION,
+            entries: &self.entries,
+        })?;
         std::fs::write(cache_file, content)?;
+        self.dirty = false;
 
         Ok(())
     }
 }
+
+impl Drop for CacheManager {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            tracing::warn!("Failed to persist cache access times: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitize_component_blocks_path_traversal() {
+        assert_eq!(sanitize_component(".."), "__");
+        assert_eq!(sanitize_component("../../etc/passwd"), "__-..-etc-passwd");
+        assert_eq!(sanitize_component("/usr/bin/php"), "-usr-bin-php");
+        assert_eq!(sanitize_component(r"C:\tools\x"), "C--tools-x");
+        assert_eq!(sanitize_component("laravel/pint"), "laravel-pint");
+        assert_eq!(sanitize_component("1.2.0-RC1"), "1.2.0-RC1");
+        assert_eq!(sanitize_component(""), "_");
+    }
+
+    #[test]
+    fn identical_phars_share_one_blob_until_last_entry_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut manager = CacheManager::new(dir.path().to_path_buf())
+            .unwrap()
+            .with_dedupe(true);
+        for (name, version) in [("tool", "1.0.0"), ("alias", "1.0.0")] {
+            let file = dir.path().join(format!("{}-{}.phar", name, version));
+            std::fs::write(&file, b"same bytes").unwrap();
+            manager
+                .add_entry(
+                    name.to_string(),
+                    version.to_string(),
+                    file,
+                    String::new(),
+                    None,
+                    10,
+                )
+                .unwrap();
+        }
+
+        let blobs: Vec<_> = std::fs::read_dir(dir.path().join("blobs"))
+            .unwrap()
+            .collect();
+        assert_eq!(blobs.len(), 1);
+        let blob = blobs[0].as_ref().unwrap().path();
+
+        manager.remove_entry("tool", None).unwrap();
+        assert!(blob.exists());
+        assert_eq!(
+            std::fs::read(dir.path().join("alias-1.0.0.phar")).unwrap(),
+            b"same bytes"
+        );
+
+        manager.remove_entry("alias", None).unwrap();
+        assert!(!blob.exists());
+    }
+
+    #[test]
+    fn export_then_import_rewrites_paths_and_checks_hashes() {
+        let source = tempfile::tempdir().unwrap();
+        let mut manager = CacheManager::new(source.path().to_path_buf()).unwrap();
+        for (name, content, hash) in [
+            (
+                "good",
+                &b"good phar"[..],
+                format!("{:x}", md5::compute(b"good phar")),
+            ),
+            ("bad", &b"bad phar"[..], "0".repeat(32)),
+        ] {
+            let file = source.path().join(format!("{}-1.0.0.phar", name));
+            std::fs::write(&file, content).unwrap();
+            manager
+                .add_entry(
+                    name.to_string(),
+                    "1.0.0".to_string(),
+                    file,
+                    String::new(),
+                    Some(hash),
+                    content.len() as u64,
+                )
+                .unwrap();
+        }
+        let archive = source.path().join("export.tar.gz");
+        assert_eq!(manager.export(&archive).unwrap(), 2);
+
+        let target = tempfile::tempdir().unwrap();
+        let mut imported = CacheManager::new(target.path().to_path_buf()).unwrap();
+        let summary = imported.import(&archive).unwrap();
+        assert_eq!(
+            summary,
+            ImportSummary {
+                imported: 1,
+                skipped: 1,
+                rejected: 0
+            }
+        );
+        let entry = imported.get_entry("good", "1.0.0").unwrap();
+        assert_eq!(entry.file_path, target.path().join("good-1.0.0.phar"));
+        assert_eq!(std::fs::read(&entry.file_path).unwrap(), b"good phar");
+        assert!(imported.get_entry("bad", "1.0.0").is_none());
+
+        // 再次导入时已存在的条目全部跳过
+        assert_eq!(imported.import(&archive).unwrap().imported, 0);
+    }
+
+    #[test]
+    fn import_rejects_entries_with_unsafe_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("evil.tar.gz");
+        let manifest = br#"{"../evil:1.0.0":{"tool_name":"../evil","version":"1.0.0","file_path":"../evil.phar",
+            "download_url":"","file_hash":null,"created_at":0,"last_accessed":0,"size":4},
+            "abs:1.0.0":{"tool_name":"abs","version":"1.0.0","file_path":"/tmp/abs.phar",
+            "download_url":"","file_hash":null,"created_at":0,"last_accessed":0,"size":4}}"#;
+        let encoder = flate2::write::GzEncoder::new(
+            std::fs::File::create(&archive).unwrap(),
+            flate2::Compression::default(),
+        );
+        let mut builder = tar::Builder::new(encoder);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(manifest.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "cache.json", &manifest[..])
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let mut manager = CacheManager::new(dir.path().join("cache")).unwrap();
+        assert_eq!(
+            manager.import(&archive).unwrap(),
+            ImportSummary {
+                imported: 0,
+                skipped: 0,
+                rejected: 2
+            }
+        );
+    }
+
+    #[test]
+    fn legacy_cache_index_is_migrated_and_unknown_versions_discarded() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache_file = dir.path().join("cache.json");
+        std::fs::write(
+            &cache_file,
+            r#"{"tool:1.0.0":{"tool_name":"tool","version":"1.0.0","file_path":"/tmp/tool.phar",
+                "download_url":"","file_hash":null,"created_at":0,"last_accessed":0,"size":4}}"#,
+        )
+        .unwrap();
+        let mut manager = CacheManager::new(dir.path().to_path_buf()).unwrap();
+        assert!(manager.get_entry("tool", "1.0.0").is_some());
+        manager.flush().unwrap();
+        let saved = std::fs::read_to_string(&cache_file).unwrap();
+        assert!(saved.contains("\"schema_version\": 1"));
+
+        std::fs::write(&cache_file, r#"{"schema_version":99,"entries":{"x":1}}"#).unwrap();
+        let manager = CacheManager::new(dir.path().to_path_buf()).unwrap();
+        assert!(manager.list_entries().is_empty());
+        assert!(dir.path().join("cache.json.bak").exists());
+    }
+
+    #[test]
+    fn prune_removes_entries_matching_either_threshold() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut manager = CacheManager::new(dir.path().to_path_buf()).unwrap();
+        for (name, size) in [("big", 100), ("old", 1), ("fresh", 1)] {
+            let file = dir.path().join(format!("{}.phar", name));
+            std::fs::write(&file, b"x").unwrap();
+            manager
+                .add_entry(
+                    name.to_string(),
+                    "1.0.0".to_string(),
+                    file,
+                    String::new(),
+                    None,
+                    size,
+                )
+                .unwrap();
+        }
+        manager.entries.get_mut("old:1.0.0").unwrap().last_accessed -= 3600;
+
+        let planned = manager.prune(Some(60), Some(50), true).unwrap();
+        let names: Vec<_> = planned.iter().map(|(e, _)| e.tool_name.as_str()).collect();
+        assert_eq!(names, vec!["big", "old"]);
+        assert_eq!(manager.list_entries().len(), 3);
+
+        let removed = manager.prune(Some(60), Some(50), false).unwrap();
+        assert_eq!(removed.iter().map(|(_, size)| size).sum::<u64>(), 101);
+        assert_eq!(manager.list_entries().len(), 1);
+        assert!(!dir.path().join("big.phar").exists());
+        assert!(dir.path().join("fresh.phar").exists());
+    }
+
+    #[test]
+    fn relocate_moves_files_and_rewrites_entry_paths() {
+        let root = tempfile::tempdir().unwrap();
+        let old_dir = root.path().join("old");
+        let new_dir = root.path().join("disk").join("phpx");
+        std::fs::create_dir_all(&old_dir).unwrap();
+        let mut manager = CacheManager::new(old_dir.clone()).unwrap();
+        let phar = old_dir.join("tool-1.0.0.phar");
+        std::fs::write(&phar, b"phar").unwrap();
+        manager
+            .add_entry(
+                "tool".to_string(),
+                "1.0.0".to_string(),
+                phar,
+                String::new(),
+                None,
+                4,
+            )
+            .unwrap();
+
+        assert!(manager.relocate(&old_dir.join("nested")).is_err());
+        assert_eq!(manager.relocate(&new_dir).unwrap(), 1);
+
+        assert!(!old_dir.exists());
+        let entry = manager.get_entry("tool", "1.0.0").unwrap().clone();
+        assert_eq!(entry.file_path, new_dir.join("tool-1.0.0.phar"));
+        assert_eq!(std::fs::read(&entry.file_path).unwrap(), b"phar");
+        let reloaded = CacheManager::new(new_dir).unwrap();
+        assert_eq!(reloaded.list_entries().len(), 1);
+
+        let copy = root.path().join("copy");
+        copy_tree(&root.path().join("disk"), &copy).unwrap();
+        assert!(copy.join("phpx").join("cache.json").exists());
+    }
+
+    #[test]
+    fn accessing_entry_resets_eviction_clock() {
+        let dir = tempfile::tempdir().unwrap();
+        let ttl = 60;
+        {
+            let mut manager = CacheManager::new(dir.path().to_path_buf()).unwrap();
+            let file = dir.path().join("tool-1.0.0.phar");
+            std::fs::write(&file, b"phar").unwrap();
+            manager
+                .add_entry(
+                    "tool".to_string(),
+                    "1.0.0".to_string(),
+                    file,
+                    String::new(),
+                    None,
+                    4,
+                )
+                .unwrap();
+            // 模拟很久以前最后一次访问
+            manager.entries.get_mut("tool:1.0.0").unwrap().last_accessed = 0;
+            manager.save_cache().unwrap();
+        }
+
+        {
+            let mut manager = CacheManager::new(dir.path().to_path_buf()).unwrap();
+            assert!(manager.get_entry("tool", "1.0.0").is_some());
+        }
+
+        let mut manager = CacheManager::new(dir.path().to_path_buf()).unwrap();
+        manager.cleanup_old_entries(ttl).unwrap();
+        assert!(manager.get_entry("tool", "1.0.0").is_some());
+    }
+
+    #[test]
+    fn pinned_entries_survive_gc_and_prune_but_not_clean() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut manager = CacheManager::new(dir.path().to_path_buf()).unwrap();
+        for version in ["1.0.0", "2.0.0"] {
+            let file = dir.path().join(format!("tool-{}.phar", version));
+            std::fs::write(&file, b"phar").unwrap();
+            manager
+                .add_entry(
+                    "tool".to_string(),
+                    version.to_string(),
+                    file,
+                    String::new(),
+                    None,
+                    4,
+                )
+                .unwrap();
+            manager
+                .entries
+                .get_mut(&format!("tool:{}", version))
+                .unwrap()
+                .last_accessed = 0;
+        }
+        assert_eq!(manager.set_pinned("tool", Some("1.0.0"), true).unwrap(), 1);
+
+        manager.cleanup_old_entries(60).unwrap();
+        assert_eq!(manager.prune(None, Some(0), false).unwrap().len(), 0);
+        let versions: Vec<&str> = manager
+            .list_entries()
+            .iter()
+            .map(|e| e.version.as_str())
+            .collect();
+        assert_eq!(versions, ["1.0.0"]);
+
+        manager.remove_entry("tool", None).unwrap();
+        assert!(manager.list_entries().is_empty());
+    }
+
+    #[test]
+    fn disk_usage_splits_cache_and_install_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache_dir = dir.path().join("cache");
+        let install_base = dir.path().join("tools");
+        std::fs::create_dir_all(cache_dir.join("composer_cache")).unwrap();
+        std::fs::create_dir_all(install_base.join("composer").join("acme-tool-1.0.0")).unwrap();
+        std::fs::write(cache_dir.join("tool-1.0.0.phar"), [0u8; 100]).unwrap();
+        std::fs::write(cache_dir.join("composer_cache").join("a.zip"), [0u8; 30]).unwrap();
+        std::fs::write(
+            install_base
+                .join("composer")
+                .join("acme-tool-1.0.0")
+                .join("composer.json"),
+            [0u8; 20],
+        )
+        .unwrap();
+
+        let usage = DiskUsage::measure(&cache_dir, &install_base);
+        assert_eq!(usage.phars, 100);
+        assert_eq!(usage.composer, 20);
+        assert_eq!(usage.composer_cache, 30);
+        assert_eq!(usage.override_installs, 0);
+        assert_eq!(usage.total(), 150);
+    }
+}
@@ -1,16 +1,39 @@
-use crate::error::Result;
+use crate::cache::{self, CacheManager, DiskUsage};
+use crate::composer;
+use crate::config::{expand_tilde, Config};
+use crate::dotenv;
+use crate::error::{Error, Result};
+use crate::executor::Executor;
+use crate::manifest::{
+    split_command_line, ProjectManifest, JSON_MANIFEST_FILE_NAME, MANIFEST_FILE_NAME,
+};
+use crate::progress::TerminalProgress;
+use crate::resolver::{ReleaseNote, ResolverConfig, SourcePreference, Stability, ToolResolver};
 use crate::runner::Runner;
-use crate::ToolOptions;
-use clap::{Parser, Subcommand};
-use std::path::PathBuf;
+use crate::shim;
+use crate::units;
+use crate::{RunBuilder, ToolOptions};
+use clap::{ArgAction, ArgGroup, Parser, Subcommand, ValueEnum};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// `phpx --version` 输出的完整版本：版本号、提交号与构建日期（由 build.rs 写入）
+pub const LONG_VERSION: &str = concat!(
+    env!("CARGO_PKG_VERSION"),
+    " (",
+    env!("PHPX_GIT_SHA"),
+    " ",
+    env!("PHPX_BUILD_DATE"),
+    ")"
+);
 
 #[derive(Parser)]
 #[command(name = "phpx")]
 #[command(about = "A npx-like tool for PHP - run PHP tools without installation")]
-#[command(version, long_about = None)]
-#[command(arg_required_else_help = true)]
+#[command(version, long_version = LONG_VERSION, long_about = None)]
+#[command(arg_required_else_help = true, disable_version_flag = true)]
 pub struct Cli {
-    /// Tool identifier (e.g., phpstan, php-cs-fixer@^3.0)
+    /// Tool identifier (e.g., phpstan, php-cs-fixer@^3.0, vendor/package:bin@^1.0)
     #[arg(required = false)]
     pub tool: Option<String>,
 
@@ -21,13 +44,38 @@ pub struct Cli {
     #[command(subcommand)]
     pub command: Option<Commands>,
 
-    #[arg(long, short, global = true)]
-    pub verbose: bool,
+    /// Print version. After a tool identifier it is passed to the tool instead (phpx phpstan --version)
+    #[arg(short = 'V', long)]
+    pub version: bool,
+
+    /// Increase log verbosity (-v for debug, -vv for trace)
+    #[arg(long, short, global = true, action = ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Decrease log verbosity (-q for warnings only, -qq for errors only)
+    #[arg(long, short, global = true, action = ArgAction::Count, conflicts_with = "verbose")]
+    pub quiet: u8,
+
+    /// Log output format
+    #[arg(long, global = true, value_enum, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
 
     /// Use the given config file instead of ~/.config/phpx/config.toml
     #[arg(long, short = 'c', global = true)]
     pub config: Option<PathBuf>,
 
+    /// Use this cache directory for this invocation (overrides config cache_dir)
+    #[arg(long, global = true)]
+    pub cache_dir: Option<String>,
+
+    /// Install Composer-based tools and overrides under this directory (overrides config install_dir)
+    #[arg(long, global = true, value_name = "DIR")]
+    pub install_dir: Option<String>,
+
+    /// Refuse to download files larger than this many bytes (overrides config max_download_size)
+    #[arg(long, global = true, value_name = "BYTES")]
+    pub max_download_size: Option<u64>,
+
     /// Clear this tool's cache (or all cache if no tool) before running
     #[arg(long, global = true)]
     pub clear_cache: bool,
@@ -36,11 +84,20 @@ pub struct Cli {
     #[arg(long, global = true)]
     pub no_cache: bool,
 
-    /// Skip signature/hash verification for this run
+    /// Re-resolve and re-download even if a valid cached copy exists, replacing the cache entry
+    #[arg(long, global = true, visible_alias = "reinstall")]
+    pub force: bool,
+
+    /// Skip signature/hash verification for this run (PHPX_SKIP_VERIFY=1 disables it globally)
     #[arg(long, global = true)]
     pub skip_verify: bool,
 
-    /// PHP binary path to run the .phar (overrides config default_php_path)
+    /// INSECURE: accept invalid HTTPS certificates, e.g. behind a TLS-intercepting corporate proxy
+    /// (PHPX_NO_VERIFY_TLS=1 does the same). Every request prints a warning
+    #[arg(long, global = true)]
+    pub no_verify_tls: bool,
+
+    /// PHP binary path, or a version such as 8.2 / php8.2 to pick a discovered PHP (overrides config default_php_path)
     #[arg(long, global = true)]
     pub php: Option<PathBuf>,
 
@@ -48,9 +105,128 @@ pub struct Cli {
     #[arg(long, short = 'n', global = true)]
     pub no_local: bool,
 
+    /// Use phpx's cached copy of the tool when there is one, even if vendor/bin or composer global has it
+    #[arg(long, global = true, conflicts_with_all = ["prefer_local", "no_local"])]
+    pub prefer_cache: bool,
+
+    /// Use the tool from vendor/bin or composer global when present, even when config prefer_cache
+    /// is set (this is the default otherwise)
+    #[arg(long, global = true)]
+    pub prefer_local: bool,
+
+    /// Do not prepend the tool's default_args from config for this run
+    #[arg(long, global = true)]
+    pub no_default_args: bool,
+
+    /// Do not read the project's composer.json PHP requirement (no version check or warning)
+    #[arg(long, global = true)]
+    pub ignore_project_php: bool,
+
+    /// Fail instead of warning when PHP is older than min_php_version (default 7.4)
+    #[arg(long, global = true)]
+    pub strict_php: bool,
+
+    /// Don't search Packagist for similar names when a tool can't be found
+    #[arg(long, global = true)]
+    pub no_suggest: bool,
+
+    /// Load variables from a .env-style file into the tool's environment (overrides inherited variables)
+    #[arg(long, value_name = "PATH", global = true)]
+    pub env_file: Option<PathBuf>,
+
+    /// Set a variable in the tool's environment (repeatable), e.g. --env APP_ENV=test; overrides --env-file
+    #[arg(long, value_name = "KEY=VALUE", global = true, value_parser = dotenv::parse_assignment, action = ArgAction::Append)]
+    pub env: Vec<(String, String)>,
+
+    /// Allow pre-release versions, including GitHub releases marked as pre-release
+    /// (same as min_stability = "dev" for this invocation)
+    #[arg(long, global = true)]
+    pub pre: bool,
+
+    /// Where to resolve tools from. auto tries Packagist, then GitHub releases, then GitHub tags,
+    /// and uses the first source that has a matching version (overrides config source)
+    #[arg(long, global = true, value_enum)]
+    pub source: Option<SourcePreference>,
+
+    /// Install Composer-based tools together with their require-dev dependencies (cached separately)
+    #[arg(long, visible_alias = "dev", global = true)]
+    pub with_dev: bool,
+
+    /// With a version constraint, reuse the newest cached version that satisfies it instead of re-resolving
+    #[arg(long, global = true, conflicts_with = "prefer_newer")]
+    pub reuse_cached: bool,
+
+    /// With a version constraint, always re-resolve and install a newer matching version,
+    /// even when config reuse_cached is set (this is the default otherwise)
+    #[arg(long, global = true)]
+    pub prefer_newer: bool,
+
+    /// Print how long resolve, download, verify, install and execute took (implied by --verbose)
+    #[arg(long, global = true)]
+    pub timings: bool,
+
+    /// Extra argument for the isolated `composer install` (repeatable, e.g. --composer-arg=--prefer-source)
+    #[arg(
+        long = "composer-arg",
+        global = true,
+        value_name = "ARG",
+        allow_hyphen_values = true
+    )]
+    pub composer_args: Vec<String>,
+
+    /// Install Composer-based tools even if the current PHP doesn't satisfy their PHP requirement
+    /// (shorthand for --composer-arg=--ignore-platform-req=php)
+    #[arg(long, global = true)]
+    pub ignore_platform_php: bool,
+
+    /// Composer plugin the isolated install may run (repeatable, e.g. --allow-plugin=php-http/discovery).
+    /// Plugins not listed here or in config allow_plugins are disabled instead of prompting
+    #[arg(long = "allow-plugin", global = true, value_name = "PLUGIN")]
+    pub allow_plugins: Vec<String>,
+
+    /// If `composer install` fails on platform requirements (PHP version, extensions),
+    /// retry ignoring exactly those requirements instead of failing or prompting
+    #[arg(long, global = true)]
+    pub ignore_platform_reqs: bool,
+
+    /// Do not apply default PHP ini settings (built-in or default_php_ini), e.g. memory_limit=-1 for phpstan
+    #[arg(long, global = true)]
+    pub no_default_php_args: bool,
+
+    /// Maximum number of concurrent resolutions/downloads for batch operations (default: CPU count)
+    #[arg(long, global = true)]
+    pub concurrency: Option<usize>,
+
     /// Pass --no-interaction to the tool (e.g. rector, composer) to avoid interactive prompts
     #[arg(long, global = true)]
     pub no_interaction: bool,
+
+    /// Keep interactive mode even when stdin is not a TTY (disables adding --no-interaction automatically
+    /// to composer, rector, php-cs-fixer and phpstan)
+    #[arg(long, global = true, conflicts_with = "no_interaction")]
+    pub interaction: bool,
+
+    /// Pass a flag to the PHP interpreter itself, e.g. --php-arg=-dmemory_limit=-1 (repeatable)
+    #[arg(long = "php-arg", value_name = "FLAG", global = true, allow_hyphen_values = true, action = ArgAction::Append)]
+    pub php_args: Vec<String>,
+
+    /// Save a copy of the downloaded phar to PATH, e.g. --keep-download=build/ (default: current directory)
+    #[arg(
+        long,
+        visible_alias = "output",
+        value_name = "PATH",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "."
+    )]
+    pub keep_download: Option<PathBuf>,
+}
+
+/// 日志输出格式：默认人类可读文本，json 便于接入日志采集管道
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    Text,
+    Json,
 }
 
 #[derive(Subcommand, Debug)]
@@ -61,6 +237,12 @@ pub enum Commands {
         command: CacheCommands,
     },
 
+    /// Manage shims in bin_dir that run a pinned tool version through phpx
+    Global {
+        #[command(subcommand)]
+        command: GlobalCommands,
+    },
+
     /// Manage configuration
     Config {
         #[command(subcommand)]
@@ -70,6 +252,68 @@ pub enum Commands {
     /// Update phpx to the latest version
     SelfUpdate,
 
+    /// Print version and build info; with --verbose also the detected PHP and Composer
+    #[command(visible_alias = "self-version")]
+    Version,
+
+    /// Resolve a tool, run `<tool> --version` and print only the version it reports (for scripts)
+    VersionOf {
+        /// Tool identifier (e.g. phpstan@^1.10)
+        tool: String,
+    },
+
+    /// Resolve, download and cache tools without running them (e.g. to pre-warm CI caches).
+    /// Without arguments, installs every tool in the [tools] section of .phpx.toml / phpx.json.
+    Install {
+        /// Tool identifiers (e.g. phpstan@^1.10 php-cs-fixer)
+        tools: Vec<String>,
+    },
+
+    /// Download a tool and check its signature and hash without running or caching it
+    Verify {
+        /// Tool identifier (e.g. phpstan@1.10.0)
+        tool: String,
+
+        /// Print the result as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Resolve tools and pin their exact versions in the project's phpx.lock.
+    /// Without arguments, refreshes every tool already in the lock file.
+    Lock {
+        /// Tool identifiers (e.g. phpstan@^1.10 php-cs-fixer)
+        tools: Vec<String>,
+    },
+
+    /// Run a named script from the project's .phpx.toml [scripts] section
+    RunScript {
+        /// Script name (e.g. stan for `stan = "phpstan analyse src"`)
+        script: String,
+
+        /// Extra arguments appended to the script's command
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+
+    /// Re-resolve all cached tools and cache newer versions concurrently
+    Update {
+        /// Print the GitHub release notes of the versions each tool was updated across
+        #[arg(long)]
+        changelog: bool,
+    },
+
+    /// Show cached tools that have newer versions available (read-only)
+    Outdated {
+        /// Print the result as JSON
+        #[arg(long)]
+        json: bool,
+
+        /// Also fetch the GitHub release notes between the cached and the latest version
+        #[arg(long)]
+        changelog: bool,
+    },
+
     /// Install a library package in override dir for "seamless version switch" (no bin required).
     /// Prints the install path; use it as vendor/autoload.php prefix or run with --bootstrap.
     Add {
@@ -79,6 +323,10 @@ pub enum Commands {
         /// Generate override_autoload.php in current dir and print run command
         #[arg(long)]
         bootstrap: bool,
+
+        /// Install into the shared override project (one vendor tree for all --shared packages)
+        #[arg(long)]
+        shared: bool,
     },
 
     /// Remove override install(s) for a package. Omit version to remove all versions.
@@ -88,10 +336,18 @@ pub enum Commands {
 
         /// Version to remove (e.g. 7.10.0); omit to remove all versions of the package
         version: Option<String>,
+
+        /// Remove the package from the shared override project and re-resolve the rest
+        #[arg(long, conflicts_with = "version")]
+        shared: bool,
     },
 
     /// List override-installed packages (from phpx add).
-    List,
+    List {
+        /// Print the result as a JSON array of {package, version, path}
+        #[arg(long)]
+        json: bool,
+    },
 }
 
 #[derive(Subcommand, Debug)]
@@ -104,6 +360,84 @@ pub enum CacheCommands {
 
     /// Show cache information for a tool
     Info { tool: String },
+
+    /// Remove cache entries not accessed within cache_ttl
+    Gc,
+
+    /// Keep a tool's cache entries (all versions, or tool@version) from being removed by gc and prune
+    Pin { tool: String },
+
+    /// Undo `cache pin`
+    Unpin { tool: String },
+
+    /// Remove entries older than and/or larger than the given thresholds
+    #[command(group(
+        ArgGroup::new("threshold")
+            .required(true)
+            .multiple(true)
+            .args(["older_than", "larger_than"])
+    ))]
+    Prune {
+        /// Remove entries not accessed within this duration (e.g. 30d, 12h)
+        #[arg(long, value_name = "DURATION", value_parser = units::parse_duration)]
+        older_than: Option<u64>,
+
+        /// Remove entries taking more than this much disk space (e.g. 50MB)
+        #[arg(long, value_name = "SIZE", value_parser = units::parse_size)]
+        larger_than: Option<u64>,
+
+        /// Only print what would be removed
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Print the resolved cache directory
+    Path,
+
+    /// Print how much disk space phpx uses, by category, against max_cache_size
+    Size {
+        /// Print the result as JSON (sizes in bytes)
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Bundle cache.json and all cached phars / Composer installs into a tar.gz
+    Export { archive: PathBuf },
+
+    /// Merge a tarball created by `cache export` into the current cache directory
+    Import { archive: PathBuf },
+
+    /// Move the whole cache to another directory and rewrite cached paths
+    Move {
+        new_dir: PathBuf,
+
+        /// Also set cache_dir in the user config to the new directory
+        #[arg(long)]
+        update_config: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum GlobalCommands {
+    /// Install the tool and write a shim to bin_dir that always runs this exact version
+    Add {
+        /// Tool identifier (e.g. phpstan, phpstan@^1.10, vendor/package:bin)
+        tool: String,
+
+        /// Command name for the shim (defaults to the bin or the package name)
+        #[arg(long)]
+        name: Option<String>,
+    },
+
+    /// Remove a shim written by `phpx global add`
+    Remove { name: String },
+
+    /// List shims in bin_dir
+    List {
+        /// Print the result as JSON
+        #[arg(long)]
+        json: bool,
+    },
 }
 
 #[derive(Subcommand, Debug)]
@@ -113,12 +447,30 @@ pub enum ConfigCommands {
 
     /// Set a configuration value
     Set { key: String, value: String },
+
+    /// Open the config file in $VISUAL / $EDITOR
+    Edit,
+
+    /// Print the resolved user config file path
+    Path,
 }
 
 impl Cli {
-    pub async fn execute(self) -> Result<()> {
+    /// 根据 --verbose / --quiet 计数得到日志级别（默认 INFO）
+    pub fn log_level(&self) -> tracing::Level {
+        match (self.verbose, self.quiet) {
+            (0, 0) => tracing::Level::INFO,
+            (1, _) => tracing::Level::DEBUG,
+            (v, _) if v >= 2 => tracing::Level::TRACE,
+            (_, 1) => tracing::Level::WARN,
+            _ => tracing::Level::ERROR,
+        }
+    }
+
+    /// 执行命令，返回进程退出码（运行工具时为工具自身的退出码）
+    pub async fn execute(self) -> Result<i32> {
         if let Some(ref command) = self.command {
-            match command {
+            let result = match command {
                 Commands::Cache { command } => match command {
                     CacheCommands::Clean { tool } => {
                         tracing::info!("Cleaning cache for tool: {:?}", tool);
@@ -132,6 +484,50 @@ impl Cli {
                         tracing::info!("Getting cache info for tool: {}", tool);
                         self.cache_info(tool)
                     }
+                    CacheCommands::Gc => {
+                        tracing::info!("Cleaning expired cache entries");
+                        self.gc_cache()
+                    }
+                    CacheCommands::Pin { tool } => self.pin_cache(tool, true),
+                    CacheCommands::Unpin { tool } => self.pin_cache(tool, false),
+                    CacheCommands::Prune {
+                        older_than,
+                        larger_than,
+                        dry_run,
+                    } => {
+                        tracing::info!("Pruning cache entries");
+                        self.prune_cache(*older_than, *larger_than, *dry_run)
+                    }
+                    CacheCommands::Path => self.config_with_overrides().map(|config| {
+                        println!("{}", config.cache_dir.display());
+                    }),
+                    CacheCommands::Size { json } => self.cache_size(*json),
+                    CacheCommands::Export { archive } => {
+                        tracing::info!("Exporting cache to {}", archive.display());
+                        self.export_cache(archive)
+                    }
+                    CacheCommands::Import { archive } => {
+                        tracing::info!("Importing cache from {}", archive.display());
+                        self.import_cache(archive)
+                    }
+                    CacheCommands::Move {
+                        new_dir,
+                        update_config,
+                    } => {
+                        tracing::info!("Moving cache to {}", new_dir.display());
+                        self.move_cache(new_dir, *update_config)
+                    }
+                },
+                Commands::Global { command } => match command {
+                    GlobalCommands::Add { tool, name } => {
+                        tracing::info!("Adding global shim for {}", tool);
+                        self.global_add(tool, name.as_deref()).await
+                    }
+                    GlobalCommands::Remove { name } => {
+                        tracing::info!("Removing global shim {}", name);
+                        self.global_remove(name)
+                    }
+                    GlobalCommands::List { json } => self.global_list(*json),
                 },
                 Commands::Config { command } => match command {
                     ConfigCommands::Get { key } => {
@@ -142,30 +538,87 @@ impl Cli {
                         tracing::info!("Setting config: {} = {}", key, value);
                         self.set_config(key, value)
                     }
+                    ConfigCommands::Edit => {
+                        tracing::info!("Editing config");
+                        self.edit_config()
+                    }
+                    ConfigCommands::Path => {
+                        Config::user_config_path(self.config.clone()).map(|path| {
+                            println!("{}", path.display());
+                        })
+                    }
                 },
                 Commands::SelfUpdate => {
                     tracing::info!("Updating phpx");
                     self.self_update()
                 }
-                Commands::Add { package, bootstrap } => {
-                    self.add_override_package(&package, *bootstrap).await
+                Commands::Install { tools } => {
+                    tracing::info!("Installing tools: {:?}", tools);
+                    self.install_tools(tools).await
                 }
-                Commands::Remove { package, version } => {
-                    self.remove_override_package(&package, version.as_deref())
+                Commands::Verify { tool, json } => {
+                    tracing::info!("Verifying tool: {}", tool);
+                    self.verify_tool(tool, *json).await
                 }
-                Commands::List => self.list_override_packages(),
-            }
+                Commands::Lock { tools } => {
+                    tracing::info!("Locking tools: {:?}", tools);
+                    self.lock_tools(tools).await
+                }
+                Commands::RunScript { script, args } => {
+                    tracing::info!("Running script: {} with args: {:?}", script, args);
+                    return self.run_script(script, args).await;
+                }
+                Commands::Update { changelog } => {
+                    tracing::info!("Updating cached tools");
+                    self.update_tools(*changelog).await
+                }
+                Commands::Version => self.print_version(),
+                Commands::VersionOf { tool } => self.print_tool_version(tool).await,
+                Commands::Outdated { json, changelog } => {
+                    tracing::info!("Checking cached tools for newer versions");
+                    self.outdated_tools(*json, *changelog).await
+                }
+                Commands::Add {
+                    package,
+                    bootstrap,
+                    shared,
+                } => {
+                    self.add_override_package(package, *bootstrap, *shared)
+                        .await
+                }
+                Commands::Remove {
+                    package,
+                    version,
+                    shared,
+                } => {
+                    if *shared {
+                        self.remove_shared_override_package(package)
+                    } else {
+                        self.remove_override_package(package, version.as_deref())
+                    }
+                }
+                Commands::List { json } => self.list_override_packages(*json),
+            };
+            result.map(|()| 0)
+        } else if self.version && self.tool.is_none() {
+            self.print_version()?;
+            Ok(0)
         } else if self.clear_cache && self.tool.is_none() {
             // 仅传入 --clear-cache 时，清理全部缓存（等同 phpx cache clean）
             tracing::info!("Clearing all cache (--clear-cache without tool)");
             self.clean_cache(None)?;
             println!("Cache cleared.");
-            Ok(())
+            Ok(0)
         } else if let Some(ref tool) = self.tool {
-            tracing::info!("Running tool: {} with args: {:?}", tool, self.args);
+            // 工具标识符之后的 --version / -V 属于工具而不是 phpx；它出现在其余参数之前
+            let mut args = self.args.clone();
+            if self.version {
+                args.insert(0, "--version".to_string());
+            }
+            tracing::info!("Running tool: {} with args: {:?}", tool, args);
             self.run_tool(
                 tool,
-                &self.args,
+                &args,
                 self.clear_cache,
                 self.no_cache,
                 self.skip_verify,
@@ -175,7 +628,7 @@ impl Cli {
             .await
         } else {
             println!("No command specified. Use --help for usage information.");
-            Ok(())
+            Ok(0)
         }
     }
 
@@ -189,14 +642,32 @@ impl Cli {
         skip_verify: bool,
         php: Option<&PathBuf>,
         no_local: bool,
-    ) -> Result<()> {
+    ) -> Result<i32> {
         let options = ToolOptions {
             clear_cache,
             no_cache,
             skip_verify,
             php: php.cloned(),
             no_local,
+            prefer_cache: self.prefer_cache,
+            prefer_local: self.prefer_local,
             no_interaction: self.no_interaction,
+            interaction: self.interaction,
+            no_default_args: self.no_default_args,
+            keep_download: self.keep_download.clone(),
+            php_args: self.php_args.clone(),
+            no_default_php_args: self.no_default_php_args,
+            force: self.force,
+            ignore_project_php: self.ignore_project_php,
+            strict_php: self.strict_php,
+            with_dev: self.with_dev,
+            reuse_cached: self.reuse_cached,
+            prefer_newer: self.prefer_newer,
+            no_suggest: self.no_suggest,
+            pre: self.pre,
+            source: self.source,
+            env_file: self.env_file.clone(),
+            env: self.env.clone(),
         };
 
         tracing::info!(
@@ -207,35 +678,560 @@ impl Cli {
             options.skip_verify
         );
 
-        // 创建并运行工具（传入可选配置文件路径以覆盖默认 ~/.config/phpx/config.toml）
-        let mut runner = Runner::new(self.config.clone())?;
-        runner.run_tool_with_options(tool, args, &options).await
+        // 通过库 API 运行工具（传入可选配置文件路径以覆盖默认 ~/.config/phpx/config.toml）
+        let mut builder = RunBuilder::new()
+            .tool(tool)
+            .args(args)
+            .options(options)
+            .progress(Arc::new(TerminalProgress::new()));
+        if let Some(ref config) = self.config {
+            builder = builder.config_path(config);
+        }
+        if let Some(ref dir) = self.cache_dir {
+            builder = builder.cache_dir(expand_tilde(dir));
+        }
+        if let Some(ref dir) = self.install_dir {
+            builder = builder.install_dir(expand_tilde(dir));
+        }
+        if let Some(bytes) = self.max_download_size {
+            builder = builder.max_download_size(bytes);
+        }
+        for plugin in &self.allow_plugins {
+            builder = builder.allow_plugin(plugin);
+        }
+        builder = builder
+            .composer_args(self.extra_composer_args())
+            .ignore_platform_reqs(self.ignore_platform_reqs)
+            .no_verify_tls(self.no_verify_tls);
+        let outcome = builder.run().await?;
+        if self.timings || self.verbose > 0 {
+            eprintln!("Timings: {}", outcome.timings);
+        }
+        Ok(outcome.exit_code)
+    }
+
+    async fn run_script(&self, script: &str, extra_args: &[String]) -> Result<i32> {
+        let path = ProjectManifest::find().ok_or_else(|| {
+            Error::Config(format!(
+                "No {} found in current directory or its parents",
+                MANIFEST_FILE_NAME
+            ))
+        })?;
+        let manifest = ProjectManifest::load(&path)?;
+        let (tool, mut args) = manifest.script(script)?;
+        args.extend_from_slice(extra_args);
+        self.run_tool(
+            &tool,
+            &args,
+            self.clear_cache,
+            self.no_cache,
+            self.skip_verify,
+            self.php.as_ref(),
+            self.no_local,
+        )
+        .await
     }
 
     fn clean_cache(&self, tool: Option<String>) -> Result<()> {
-        let mut runner = Runner::new(self.config.clone())?;
+        let mut runner = self.cache_runner()?;
         runner.clean_cache(tool)
     }
 
+    fn gc_cache(&self) -> Result<()> {
+        let mut runner = self.cache_runner()?;
+        runner.gc_cache()
+    }
+
+    fn pin_cache(&self, tool: &str, pinned: bool) -> Result<()> {
+        let mut runner = self.cache_runner()?;
+        let changed = runner.pin_cache(tool, pinned)?;
+        println!(
+            "{} {} cache entr{} for {}",
+            if pinned { "Pinned" } else { "Unpinned" },
+            changed,
+            if changed == 1 { "y" } else { "ies" },
+            tool
+        );
+        Ok(())
+    }
+
+    fn prune_cache(
+        &self,
+        older_than: Option<u64>,
+        larger_than: Option<u64>,
+        dry_run: bool,
+    ) -> Result<()> {
+        let mut runner = self.cache_runner()?;
+        runner.prune_cache(older_than, larger_than, dry_run)
+    }
+
+    fn cache_size(&self, json: bool) -> Result<()> {
+        let config = self.config_with_overrides()?;
+        let usage = DiskUsage::measure(&config.cache_dir, &config.install_base());
+        let total = usage.total();
+        if json {
+            let mut value = serde_json::to_value(&usage)?;
+            value["total"] = total.into();
+            value["max_cache_size"] = config.max_cache_size.into();
+            println!("{}", serde_json::to_string_pretty(&value)?);
+            return Ok(());
+        }
+        for (name, size) in usage.categories() {
+            println!("{:<15} {:>10}", name, units::format_size(size));
+        }
+        println!(
+            "{:<15} {:>10}  ({:.0}% of max_cache_size {})",
+            "total",
+            units::format_size(total),
+            total as f64 * 100.0 / config.max_cache_size.max(1) as f64,
+            units::format_size(config.max_cache_size)
+        );
+        if total >= config.max_cache_size {
+            println!("Cache exceeds max_cache_size; run `phpx cache gc` or `phpx cache prune`");
+        } else if total >= config.max_cache_size / 10 * 9 {
+            println!("Cache is near max_cache_size");
+        }
+        Ok(())
+    }
+
     fn list_cache(&self) -> Result<()> {
-        let runner = Runner::new(self.config.clone())?;
+        let runner = self.cache_runner()?;
         runner.list_cache()
     }
 
     fn cache_info(&self, tool: &str) -> Result<()> {
-        let runner = Runner::new(self.config.clone())?;
+        let runner = self.cache_runner()?;
         runner.cache_info(tool)
     }
 
+    /// 打印版本与构建信息；--verbose 时附带当前 PHP 与 Composer，便于提交问题报告
+    fn print_version(&self) -> Result<()> {
+        println!("phpx {}", LONG_VERSION);
+        if self.verbose == 0 {
+            return Ok(());
+        }
+        println!(
+            "target: {}-{}",
+            std::env::consts::ARCH,
+            std::env::consts::OS
+        );
+
+        let config = self.config_with_overrides()?;
+        let php = self
+            .php
+            .clone()
+            .or_else(|| config.default_php_path.clone())
+            .map(|p| Executor::resolve_php_path(&p).unwrap_or(p))
+            .or_else(|| Executor::php_from_version_file().or_else(Executor::discover_php));
+        match &php {
+            Some(php) => println!(
+                "PHP: {} ({})",
+                Executor::get_php_version(php).unwrap_or_else(|| "unknown".to_string()),
+                php.display()
+            ),
+            None => println!("PHP: not found"),
+        }
+
+        let mut cache = CacheManager::new(config.cache_dir.clone())?;
+        match composer::detect_composer(&mut cache, &config, php.as_ref()) {
+            Some((path, version)) => println!(
+                "Composer: {} ({})",
+                version.as_deref().unwrap_or("unknown"),
+                path.display()
+            ),
+            None => println!("Composer: not found"),
+        }
+        Ok(())
+    }
+
+    fn export_cache(&self, archive: &Path) -> Result<()> {
+        let cache = CacheManager::new(self.config_with_overrides()?.cache_dir)?;
+        let count = cache.export(archive)?;
+        println!("Exported {} cache entries to {}", count, archive.display());
+        Ok(())
+    }
+
+    fn import_cache(&self, archive: &Path) -> Result<()> {
+        let mut cache = CacheManager::new(self.config_with_overrides()?.cache_dir)?;
+        let summary = cache.import(archive)?;
+        // 被拒绝的条目已逐个以警告列出路径，这里只汇总数量
+        println!(
+            "Imported {} cache entries ({} skipped, {} rejected for unsafe paths)",
+            summary.imported, summary.skipped, summary.rejected
+        );
+        Ok(())
+    }
+
+    fn move_cache(&self, new_dir: &Path, update_config: bool) -> Result<()> {
+        let new_dir = std::path::absolute(expand_tilde(&new_dir.to_string_lossy()))?;
+        let mut cache = CacheManager::new(self.config_with_overrides()?.cache_dir)?;
+        let moved = cache.relocate(&new_dir)?;
+        println!("Moved {} cache entries to {}", moved, new_dir.display());
+        if update_config {
+            let value = new_dir.to_string_lossy();
+            Config::set_value(self.config.clone(), "cache_dir", &value)?;
+            println!("Set cache_dir = {}", value);
+        } else {
+            println!(
+                "Run `phpx config set cache_dir {}` (or pass --update-config) to keep using it",
+                new_dir.display()
+            );
+        }
+        Ok(())
+    }
+
+    /// 加载配置并应用命令行覆盖（如 --cache-dir）后创建 Runner
+    fn runner(&self) -> Result<Runner> {
+        Ok(Runner::from_config(self.config_with_overrides()?)?
+            .with_progress(Arc::new(TerminalProgress::new())))
+    }
+
+    /// 缓存管理命令使用的 Runner：cache_dir 不可写时报错，而不是像运行工具那样改用空的临时缓存
+    fn cache_runner(&self) -> Result<Runner> {
+        let config = self.config_with_overrides()?;
+        if !cache::is_writable_dir(&config.cache_dir) {
+            return Err(Error::Cache(format!(
+                "Cache directory {} is not writable",
+                config.cache_dir.display()
+            )));
+        }
+        Ok(Runner::from_config(config)?.with_progress(Arc::new(TerminalProgress::new())))
+    }
+
+    /// 命令行给出的额外 composer 参数（--composer-arg 与 --ignore-platform-php）
+    fn extra_composer_args(&self) -> Vec<String> {
+        let mut args = self.composer_args.clone();
+        if self.ignore_platform_php {
+            args.push("--ignore-platform-req=php".to_string());
+        }
+        args
+    }
+
+    /// 加载配置并应用命令行覆盖（--cache-dir、--max-download-size、--composer-arg、--allow-plugin、--ignore-platform-reqs、--pre、--source）
+    fn config_with_overrides(&self) -> Result<Config> {
+        let mut config = Config::load(self.config.clone())?;
+        if let Some(ref dir) = self.cache_dir {
+            config.cache_dir = expand_tilde(dir);
+        }
+        if let Some(ref dir) = self.install_dir {
+            config.install_dir = Some(expand_tilde(dir));
+        }
+        if self.max_download_size.is_some() {
+            config.max_download_size = self.max_download_size;
+        }
+        config.composer_args.extend(self.extra_composer_args());
+        config
+            .allow_plugins
+            .extend(self.allow_plugins.iter().cloned());
+        if self.ignore_platform_reqs {
+            config.ignore_platform_reqs = true;
+        }
+        if self.no_verify_tls {
+            config.no_verify_tls = true;
+        }
+        if self.pre {
+            config.min_stability = Stability::Dev;
+        }
+        if let Some(source) = self.source {
+            config.source = source;
+        }
+        Ok(config)
+    }
+
+    /// 批量操作的并发数：--concurrency 优先，否则取 CPU 数
+    fn concurrency(&self) -> usize {
+        self.concurrency.unwrap_or_else(|| {
+            std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(4)
+        })
+    }
+
+    async fn install_tools(&self, tools: &[String]) -> Result<()> {
+        let manifest_tools;
+        let tools = if tools.is_empty() {
+            let path = ProjectManifest::find().ok_or_else(|| {
+                Error::Config(format!(
+                    "No tools given and no {} or {} found",
+                    MANIFEST_FILE_NAME, JSON_MANIFEST_FILE_NAME
+                ))
+            })?;
+            manifest_tools = ProjectManifest::load(&path)?.tool_specs();
+            if manifest_tools.is_empty() {
+                return Err(Error::Config(format!(
+                    "No tools given and {} has no [tools] section",
+                    path.display()
+                )));
+            }
+            &manifest_tools
+        } else {
+            tools
+        };
+        let mut runner = self.runner()?;
+        for tool in tools {
+            let (version, path) = runner
+                .install_tool(tool, self.skip_verify, self.php.as_ref())
+                .await?;
+            let name = tool.split('@').next().unwrap_or(tool);
+            println!("{}@{}  {}", name, version, path.display());
+        }
+        Ok(())
+    }
+
+    /// 安装工具并写入固定到所装版本的 shim；未固定版本的 URL 与 latest 按原样写入
+    async fn global_add(&self, tool: &str, name: Option<&str>) -> Result<()> {
+        let config = self.config_with_overrides()?;
+        let identifier = ToolResolver::new(ResolverConfig::from(&config)).parse_identifier(tool)?;
+        let mut runner = self.runner()?;
+        let (version, _) = runner
+            .install_tool(tool, self.skip_verify, self.php.as_ref())
+            .await?;
+
+        let spec = if identifier.url.is_some() || version == "latest" {
+            tool.to_string()
+        } else {
+            let name_part = tool.split('@').next().unwrap_or(tool);
+            match semver::Version::parse(&version) {
+                Ok(_) => format!("{}@={}", name_part, version),
+                Err(_) => format!("{}@{}", name_part, version),
+            }
+        };
+        let shim_name = name
+            .map(str::to_string)
+            .or(identifier.bin.clone())
+            .unwrap_or_else(|| {
+                identifier
+                    .name
+                    .rsplit('/')
+                    .next()
+                    .unwrap_or(&identifier.name)
+                    .to_string()
+            });
+        let bin_dir = config.shim_dir();
+        let path = shim::install(&bin_dir, &shim_name, &spec, &std::env::current_exe()?)?;
+        println!("{} -> phpx {}  ({})", shim_name, spec, path.display());
+        if !shim::on_path(&bin_dir) {
+            eprintln!(
+                "Note: {} is not on PATH; add it to run `{}` directly",
+                bin_dir.display(),
+                shim_name
+            );
+        }
+        Ok(())
+    }
+
+    fn global_remove(&self, name: &str) -> Result<()> {
+        let bin_dir = self.config_with_overrides()?.shim_dir();
+        if shim::remove(&bin_dir, name)? {
+            println!("Removed {}", shim::shim_path(&bin_dir, name).display());
+        } else {
+            println!("No shim named {} in {}", name, bin_dir.display());
+        }
+        Ok(())
+    }
+
+    fn global_list(&self, json: bool) -> Result<()> {
+        let shims = shim::list(&self.config_with_overrides()?.shim_dir())?;
+        if json {
+            println!("{}", serde_json::to_string_pretty(&shims)?);
+            return Ok(());
+        }
+        if shims.is_empty() {
+            println!("No shims installed.");
+            return Ok(());
+        }
+        let width = shims.iter().map(|s| s.name.len()).max().unwrap_or(4).max(4);
+        for s in &shims {
+            println!("{:<width$}  {}", s.name, s.spec);
+        }
+        Ok(())
+    }
+
+    async fn verify_tool(&self, tool: &str, json: bool) -> Result<()> {
+        let mut runner = self.runner()?;
+        let report = runner.verify_tool(tool).await?;
+        if json {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else {
+            println!("{}@{}", report.tool, report.version);
+            println!("  url:       {}", report.url);
+            println!("  sha256:    {}", report.sha256);
+            match &report.hash {
+                Some(check) => println!(
+                    "  {}:       expected {}, actual {} ({})",
+                    check.algorithm,
+                    check.expected,
+                    check.actual,
+                    if check.matches() { "ok" } else { "MISMATCH" }
+                ),
+                None => println!("  hash:      none published"),
+            }
+            match &report.signature_url {
+                Some(url) => println!(
+                    "  signature: {} (signer: {})",
+                    url,
+                    report
+                        .signer
+                        .as_deref()
+                        .unwrap_or("unknown, GPG verification not implemented")
+                ),
+                None => println!("  signature: none published"),
+            }
+        }
+        if !report.passed() {
+            return Err(Error::Security(format!(
+                "{}@{} failed verification",
+                report.tool, report.version
+            )));
+        }
+        Ok(())
+    }
+
+    async fn print_tool_version(&self, tool: &str) -> Result<()> {
+        let mut runner = self.runner()?;
+        let version = runner
+            .tool_reported_version(tool, self.skip_verify, self.php.as_ref())
+            .await?;
+        println!("{}", version);
+        Ok(())
+    }
+
+    async fn lock_tools(&self, tools: &[String]) -> Result<()> {
+        let mut runner = self.runner()?;
+        let path = runner.lock_tools(tools, self.skip_verify).await?;
+        println!("Wrote {}", path.display());
+        Ok(())
+    }
+
+    async fn outdated_tools(&self, json: bool, changelog: bool) -> Result<()> {
+        let mut runner = self.runner()?;
+        let mut tools = runner.outdated_tools(self.concurrency()).await?;
+        if changelog {
+            for t in tools.iter_mut().filter(|t| t.outdated) {
+                if let Some(latest) = &t.latest {
+                    t.changelog = runner.release_notes(&t.tool, &t.current, latest).await;
+                }
+            }
+        }
+        if json {
+            println!("{}", serde_json::to_string_pretty(&tools)?);
+            return Ok(());
+        }
+        if tools.is_empty() {
+            println!("No cached tools.");
+            return Ok(());
+        }
+
+        let width = tools.iter().map(|t| t.tool.len()).max().unwrap_or(4).max(4);
+        println!("{:<width$}  {:<12}  {:<12}", "TOOL", "CURRENT", "LATEST");
+        for t in &tools {
+            let latest = t.latest.as_deref().unwrap_or("?");
+            // 可升级的工具在行尾标出，便于一眼看到
+            let marker = if t.outdated {
+                "  <- update available"
+            } else {
+                ""
+            };
+            println!(
+                "{:<width$}  {:<12}  {:<12}{}",
+                t.tool, t.current, latest, marker
+            );
+        }
+        if changelog {
+            for t in tools.iter().filter(|t| t.outdated) {
+                let latest = t.latest.as_deref().unwrap_or("?");
+                print_changelog(&t.tool, &t.current, latest, &t.changelog);
+            }
+        }
+        Ok(())
+    }
+
+    async fn update_tools(&self, changelog: bool) -> Result<()> {
+        let mut runner = self.runner()?;
+        let previous = runner.current_cached_versions();
+        let updated = runner
+            .update_cached_tools(self.concurrency(), self.skip_verify)
+            .await?;
+        if updated.is_empty() {
+            println!("All cached tools are up to date.");
+            return Ok(());
+        }
+        for (tool, version) in &updated {
+            println!("Updated {}@{}", tool, version);
+        }
+        if changelog {
+            for (tool, version) in &updated {
+                let Some(current) = previous.get(tool) else {
+                    continue;
+                };
+                let notes = runner.release_notes(tool, current, version).await;
+                print_changelog(tool, current, version, &notes);
+            }
+        }
+        Ok(())
+    }
+
     fn get_config(&self, key: &str) -> Result<()> {
-        println!("Getting config: {}", key);
-        println!("(Configuration system not implemented yet)");
+        let config = self.config_with_overrides()?;
+        let value = toml::Value::try_from(&config).map_err(|e| Error::Config(e.to_string()))?;
+        let found = key
+            .split('.')
+            .try_fold(&value, |v, segment| v.get(segment))
+            .ok_or_else(|| Error::Config(format!("Unknown config key: {}", key)))?;
+        match found {
+            toml::Value::String(s) => println!("{}", s),
+            other => println!("{}", other),
+        }
         Ok(())
     }
 
     fn set_config(&self, key: &str, value: &str) -> Result<()> {
-        println!("Setting config: {} = {}", key, value);
-        println!("(Configuration system not implemented yet)");
+        Config::set_value(self.config.clone(), key, value)?;
+        println!("Set {} = {}", key, value);
+        Ok(())
+    }
+
+    /// 在编辑器中打开用户配置（不存在时先写出只含注释的模板），退出后重新校验；
+    /// 校验失败只报告错误，编辑内容保留在文件中
+    fn edit_config(&self) -> Result<()> {
+        let path = Config::create_user_config(self.config.clone())?;
+
+        let editor = std::env::var("VISUAL")
+            .or_else(|_| std::env::var("EDITOR"))
+            .ok()
+            .filter(|e| !e.trim().is_empty())
+            .unwrap_or_else(|| {
+                if cfg!(windows) {
+                    "notepad".to_string()
+                } else {
+                    "vi".to_string()
+                }
+            });
+        // $EDITOR 可能带参数，如 "code --wait"
+        let mut words = split_command_line(&editor)?.into_iter();
+        let program = words
+            .next()
+            .ok_or_else(|| Error::Config("Editor command is empty".to_string()))?;
+        let status = std::process::Command::new(&program)
+            .args(words)
+            .arg(&path)
+            .status()
+            .map_err(|e| Error::Config(format!("Failed to launch editor '{}': {}", program, e)))?;
+        if !status.success() {
+            return Err(Error::Config(format!(
+                "Editor '{}' exited with {}",
+                program, status
+            )));
+        }
+
+        Config::load(Some(path.clone())).map_err(|e| {
+            Error::Config(format!(
+                "{} (your edits were kept in {})",
+                e.to_string().trim_start_matches("Configuration error: "),
+                path.display()
+            ))
+        })?;
+        println!("Config saved: {}", path.display());
         Ok(())
     }
 
@@ -245,11 +1241,22 @@ impl Cli {
         Ok(())
     }
 
-    async fn add_override_package(&self, package: &str, bootstrap: bool) -> Result<()> {
-        let mut runner = Runner::new(self.config.clone())?;
-        let install_dir = runner
-            .install_override_package(package, self.php.as_ref())
-            .await?;
+    async fn add_override_package(
+        &self,
+        package: &str,
+        bootstrap: bool,
+        shared: bool,
+    ) -> Result<()> {
+        let mut runner = self.runner()?;
+        let install_dir = if shared {
+            runner
+                .install_shared_override_package(package, self.php.as_ref())
+                .await?
+        } else {
+            runner
+                .install_override_package(package, self.php.as_ref())
+                .await?
+        };
         let autoload_path = install_dir.join("vendor").join("autoload.php");
         println!("{}", autoload_path.display());
         if bootstrap {
@@ -264,12 +1271,8 @@ impl Cli {
         Ok(())
     }
 
-    fn remove_override_package(
-        &self,
-        package: &str,
-        version: Option<&str>,
-    ) -> Result<()> {
-        let runner = Runner::new(self.config.clone())?;
+    fn remove_override_package(&self, package: &str, version: Option<&str>) -> Result<()> {
+        let runner = self.runner()?;
         let removed = runner.remove_override_package(package, version)?;
         if removed.is_empty() {
             if let Some(v) = version {
@@ -285,9 +1288,29 @@ impl Cli {
         Ok(())
     }
 
-    fn list_override_packages(&self) -> Result<()> {
-        let runner = Runner::new(self.config.clone())?;
+    fn remove_shared_override_package(&self, package: &str) -> Result<()> {
+        let mut runner = self.runner()?;
+        if runner.remove_shared_override_package(package, self.php.as_ref())? {
+            println!("Removed {} from the shared override project", package);
+        } else {
+            println!("No shared override found for {}", package);
+        }
+        Ok(())
+    }
+
+    fn list_override_packages(&self, json: bool) -> Result<()> {
+        let runner = self.runner()?;
         let items = runner.list_override_packages()?;
+        if json {
+            let items: Vec<serde_json::Value> = items
+                .iter()
+                .map(|(package, version, path)| {
+                    serde_json::json!({ "package": package, "version": version, "path": path })
+                })
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&items)?);
+            return Ok(());
+        }
         if items.is_empty() {
             println!("No override packages installed. Use 'phpx add <package>' to add one.");
             return Ok(());
@@ -298,3 +1321,22 @@ impl Cli {
         Ok(())
     }
 }
+
+/// 打印一个工具从 current 到 latest 的发布说明，正文缩进两格
+fn print_changelog(tool: &str, current: &str, latest: &str, notes: &[ReleaseNote]) {
+    println!();
+    println!("{} {} -> {}", tool, current, latest);
+    if notes.is_empty() {
+        println!("  (no GitHub release notes found)");
+        return;
+    }
+    for note in notes {
+        match &note.published_at {
+            Some(date) => println!("  ## {} ({})", note.version, date),
+            None => println!("  ## {}", note.version),
+        }
+        for line in note.body.lines() {
+            println!("  {}", line);
+        }
+    }
+}
@@ -1,48 +1,118 @@
 //! 无 phar 时在隔离目录用 Composer 安装并返回 vendor/bin 路径。
 //! 优先使用 phpx 缓存的 composer.phar，不污染本机 Composer。
 //! 另支持「override」安装：仅安装库包（无 bin）到 override 目录，用于前置 autoload 切版本。
-
-use crate::cache::CacheManager;
+//!
+//! 隔离安装约定（库调用方可依赖）：
+//! - 每个 包@版本 独占安装根目录（Config::install_base，默认即 cache_dir）下的一个目录，
+//!   只含 phpx 生成的 composer.json 与 composer 的产物；
+//! - COMPOSER_HOME 与 Composer 下载缓存指向 cache_dir/composer_home、cache_dir/composer_cache，
+//!   不读写用户的全局 Composer 配置与项目目录；认证信息只通过 COMPOSER_AUTH 传入（见 composer_auth）；
+//! - 生成的 composer.json 带 config.allow-plugins：只启用 Config::allow_plugins 中的插件，其余禁用，
+//!   不会出现插件授权提示；
+//! - 安装失败时不会留下半成品目录被下次当作已安装使用。
+
+use crate::cache::{sanitize_component, CacheManager};
 use crate::config::Config;
 use crate::error::{Error, Result};
+use crate::executor::Executor;
+use crate::progress::ProgressObserver;
 use crate::resolver::ComposerPackage;
+use crate::security::HashAlgorithm;
+use std::collections::BTreeMap;
+use std::io::IsTerminal;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
-/// 在 cache_dir/override/<package-slug>-<version> 下安装指定版本库包（不要求 bin），
+/// 在 base_dir/override/<package-slug>-<version> 下安装指定版本库包（不要求 bin），
 /// 返回安装目录路径。用于「无缝切版本」：项目通过前置该目录的 vendor/autoload.php 加载指定版本。
+/// 目录已安装时直接返回（并校验 composer.lock 哈希未被改动），否则按模块说明的隔离约定安装。
 pub fn ensure_override_installed(
     package: &str,
     version: &str,
-    cache_dir: &Path,
+    base_dir: &Path,
     cache_manager: &mut CacheManager,
     config: &Config,
     php_path: Option<&PathBuf>,
 ) -> Result<PathBuf> {
-    let slug = package.replace('/', "-");
-    let install_dir = cache_dir
-        .join("override")
-        .join(format!("{}-{}", slug, version));
+    let install_key = sanitize_component(&format!("{}-{}", package, version));
+    let install_dir = base_dir.join("override").join(&install_key);
 
     let autoload = install_dir.join("vendor").join("autoload.php");
     if install_dir.exists() && autoload.exists() {
+        verify_override_lock(base_dir, &install_key, &install_dir)?;
         return Ok(install_dir);
     }
 
-    let composer_binary = resolve_composer_binary(cache_manager, config)?;
-    let php_binary = find_php_for_composer(php_path)?;
-
     std::fs::create_dir_all(&install_dir)?;
 
-    let composer_json = format!(r#"{{"require":{{"{}":"{}"}}}}"#, package, version);
-    std::fs::write(install_dir.join("composer.json"), &composer_json)?;
+    let composer_json = isolated_composer_json(serde_json::json!({ package: version }), config);
+    std::fs::write(install_dir.join("composer.json"), composer_json.to_string())?;
+
+    run_composer("install", &install_dir, cache_manager, config, php_path)?;
+
+    if !autoload.exists() {
+        return Err(Error::ComposerInstallFailed(
+            "vendor/autoload.php not found after install".to_string(),
+        ));
+    }
+
+    // 依赖集合与首次安装时记录的不一致时删除本次安装，避免使用被篡改或漂移的依赖
+    if let Err(e) = verify_override_lock(base_dir, &install_key, &install_dir) {
+        let _ = std::fs::remove_dir_all(&install_dir);
+        return Err(e);
+    }
+
+    Ok(install_dir)
+}
+
+/// phpx 生成的隔离项目 composer.json：require 加上 config.allow-plugins。
+/// 未配置 allow_plugins 时为 false（禁用全部插件），否则只允许列出的插件
+fn isolated_composer_json(require: serde_json::Value, config: &Config) -> serde_json::Value {
+    let allow_plugins = if config.allow_plugins.is_empty() {
+        serde_json::Value::Bool(false)
+    } else {
+        config
+            .allow_plugins
+            .iter()
+            .map(|plugin| (plugin.clone(), serde_json::Value::Bool(true)))
+            .collect::<serde_json::Map<_, _>>()
+            .into()
+    };
+    serde_json::json!({
+        "require": require,
+        "config": { "allow-plugins": allow_plugins }
+    })
+}
 
-    let composer_home = cache_dir.join("composer_home");
-    let composer_cache = cache_dir.join("composer_cache");
+/// override 安装目录 composer.json 中唯一的 require 项：(包名, 版本)；不是 phpx 写入的格式时为 None
+pub fn override_requirement(install_dir: &Path) -> Option<(String, String)> {
+    let content = std::fs::read_to_string(install_dir.join("composer.json")).ok()?;
+    let json: serde_json::Value = serde_json::from_str(&content).ok()?;
+    let require = json.get("require")?.as_object()?;
+    if require.len() != 1 {
+        return None;
+    }
+    let (package, version) = require.iter().next()?;
+    Some((package.clone(), version.as_str()?.to_string()))
+}
+
+/// 在 install_dir 中以隔离的 COMPOSER_HOME / 缓存运行 composer install 或 update（--no-dev）
+fn run_composer(
+    subcommand: &str,
+    install_dir: &Path,
+    cache_manager: &mut CacheManager,
+    config: &Config,
+    php_path: Option<&PathBuf>,
+) -> Result<()> {
+    let composer_binary = resolve_composer_binary(cache_manager, config)?;
+    let php_binary = find_php_for_composer(php_path)?;
+
+    let composer_home = config.cache_dir.join("composer_home");
+    let composer_cache = config.cache_dir.join("composer_cache");
     std::fs::create_dir_all(&composer_home).ok();
     std::fs::create_dir_all(&composer_cache).ok();
 
-    let mut cmd = if composer_binary.extension().map_or(false, |e| e == "phar") {
+    let mut cmd = if composer_binary.extension().is_some_and(|e| e == "phar") {
         let mut c = Command::new(&php_binary);
         c.arg(&composer_binary);
         c
@@ -50,13 +120,17 @@ pub fn ensure_override_installed(
         Command::new(&composer_binary)
     };
 
-    cmd.arg("install")
+    cmd.arg(subcommand)
         .arg("--no-interaction")
         .arg("--no-dev")
-        .current_dir(&install_dir)
+        .args(&config.composer_args)
+        .current_dir(install_dir)
         .env("COMPOSER_HOME", &composer_home)
         .env("COMPOSER_CACHE_DIR", &composer_cache)
         .env_remove("COMPOSER");
+    if let Some(auth) = composer_auth(config) {
+        cmd.env("COMPOSER_AUTH", auth);
+    }
 
     let output = cmd
         .output()
@@ -66,81 +140,438 @@ pub fn ensure_override_installed(
         let stderr = String::from_utf8_lossy(&output.stderr);
         let stdout = String::from_utf8_lossy(&output.stdout);
         return Err(Error::ComposerInstallFailed(format!(
-            "composer install failed. stderr: {} stdout: {}",
-            stderr, stdout
+            "composer {} failed. stderr: {} stdout: {}",
+            subcommand, stderr, stdout
         )));
     }
+    Ok(())
+}
 
-    if !autoload.exists() {
-        return Err(Error::ComposerInstallFailed(
-            "vendor/autoload.php not found after install".to_string(),
-        ));
+/// 隔离安装传给 Composer 的 COMPOSER_AUTH 内容。COMPOSER_HOME 指向缓存目录，Composer 读不到用户的 auth.json，
+/// 因此按以下顺序取认证信息：配置 composer_auth_file、环境变量 COMPOSER_AUTH、用户全局 Composer 的 auth.json、
+/// 由 COMPOSER_TOKEN（其次 GITHUB_TOKEN / GH_TOKEN）生成的 github-oauth
+fn composer_auth(config: &Config) -> Option<String> {
+    let user_auth_file = user_composer_homes()
+        .into_iter()
+        .map(|home| home.join("auth.json"))
+        .find(|path| path.is_file());
+    let token = std::env::var("COMPOSER_TOKEN")
+        .ok()
+        .filter(|token| !token.trim().is_empty())
+        .or_else(crate::resolver::github_token);
+    select_composer_auth(
+        config.composer_auth_file.as_deref(),
+        std::env::var("COMPOSER_AUTH").ok(),
+        user_auth_file.as_deref(),
+        token,
+    )
+}
+
+/// 按 composer_auth 的顺序选出认证信息；读不了的文件告警后继续尝试下一项
+fn select_composer_auth(
+    auth_file: Option<&Path>,
+    env_auth: Option<String>,
+    user_auth_file: Option<&Path>,
+    token: Option<String>,
+) -> Option<String> {
+    let read = |path: &Path| match std::fs::read_to_string(path) {
+        Ok(content) => Some(content),
+        Err(e) => {
+            tracing::warn!("Failed to read Composer auth {}: {}", path.display(), e);
+            None
+        }
+    };
+    auth_file
+        .and_then(read)
+        .or_else(|| env_auth.filter(|s| !s.is_empty()))
+        .or_else(|| user_auth_file.and_then(read))
+        .or_else(|| {
+            token.map(|token| {
+                serde_json::json!({ "github-oauth": { "github.com": token } }).to_string()
+            })
+        })
+}
+
+/// 用户全局 Composer 的 COMPOSER_HOME 候选（环境变量优先，其次各平台默认位置）
+fn user_composer_homes() -> Vec<PathBuf> {
+    let mut homes = Vec::new();
+    if let Some(home) = std::env::var_os("COMPOSER_HOME").filter(|s| !s.is_empty()) {
+        homes.push(PathBuf::from(home));
+    }
+    if let Some(config_dir) = dirs::config_dir() {
+        homes.push(config_dir.join("composer"));
+    }
+    if let Some(home_dir) = dirs::home_dir() {
+        homes.push(home_dir.join(".composer"));
     }
+    homes
+}
+
+/// 共享 override 项目：所有 `phpx add --shared` 的包装在同一个 vendor 中，依赖去重、autoload 自然合并。
+/// 成员即该项目 composer.json 的 require
+pub fn shared_override_dir(base_dir: &Path) -> PathBuf {
+    base_dir.join("override").join("shared")
+}
+
+/// 共享 override 项目的成员：包名 -> 版本约束
+pub fn shared_override_members(base_dir: &Path) -> Result<BTreeMap<String, String>> {
+    let composer_json = shared_override_dir(base_dir).join("composer.json");
+    if !composer_json.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let content = std::fs::read_to_string(composer_json)?;
+    let value: serde_json::Value = serde_json::from_str(&content)?;
+    Ok(value
+        .get("require")
+        .and_then(|r| r.as_object())
+        .map(|r| {
+            r.iter()
+                .map(|(k, v)| (k.clone(), v.as_str().unwrap_or("*").to_string()))
+                .collect()
+        })
+        .unwrap_or_default())
+}
 
+/// 按成员重写 composer.json 并 composer update；失败时恢复原 composer.json，避免共享项目处于半更新状态
+fn update_shared_override(
+    members: &BTreeMap<String, String>,
+    base_dir: &Path,
+    cache_manager: &mut CacheManager,
+    config: &Config,
+    php_path: Option<&PathBuf>,
+) -> Result<PathBuf> {
+    let install_dir = shared_override_dir(base_dir);
+    std::fs::create_dir_all(&install_dir)?;
+    let composer_json = install_dir.join("composer.json");
+    let previous = std::fs::read_to_string(&composer_json).ok();
+
+    let content =
+        serde_json::to_string_pretty(&isolated_composer_json(serde_json::json!(members), config))?;
+    std::fs::write(&composer_json, content)?;
+
+    if let Err(e) = run_composer("update", &install_dir, cache_manager, config, php_path) {
+        match previous {
+            Some(previous) => std::fs::write(&composer_json, previous)?,
+            None => std::fs::remove_dir_all(&install_dir)?,
+        }
+        return Err(e);
+    }
     Ok(install_dir)
 }
 
-/// 在缓存目录下为 Composer 包创建隔离项目、执行 composer install，返回安装目录和 vendor/bin 下的可执行路径。
+/// 将包加入共享 override 项目，返回共享项目目录
+pub fn add_shared_override(
+    package: &str,
+    version: &str,
+    base_dir: &Path,
+    cache_manager: &mut CacheManager,
+    config: &Config,
+    php_path: Option<&PathBuf>,
+) -> Result<PathBuf> {
+    let mut members = shared_override_members(base_dir)?;
+    members.insert(package.to_string(), version.to_string());
+    update_shared_override(&members, base_dir, cache_manager, config, php_path)
+}
+
+/// 从共享 override 项目移除包并重新解析其余成员；最后一个成员移除时删除整个项目。
+/// 包不在共享项目中时返回 false
+pub fn remove_shared_override(
+    package: &str,
+    base_dir: &Path,
+    cache_manager: &mut CacheManager,
+    config: &Config,
+    php_path: Option<&PathBuf>,
+) -> Result<bool> {
+    let mut members = shared_override_members(base_dir)?;
+    if members.remove(package).is_none() {
+        return Ok(false);
+    }
+    if members.is_empty() {
+        std::fs::remove_dir_all(shared_override_dir(base_dir))?;
+    } else {
+        update_shared_override(&members, base_dir, cache_manager, config, php_path)?;
+    }
+    Ok(true)
+}
+
+/// override 安装清单：base_dir/override/override.json，记录各安装目录 composer.lock 的依赖哈希
+fn override_manifest_path(base_dir: &Path) -> PathBuf {
+    base_dir.join("override").join("override.json")
+}
+
+fn load_override_pins(base_dir: &Path) -> Result<BTreeMap<String, String>> {
+    let path = override_manifest_path(base_dir);
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let content = std::fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+fn save_override_pins(base_dir: &Path, pins: &BTreeMap<String, String>) -> Result<()> {
+    let content = serde_json::to_string_pretty(pins)?;
+    std::fs::write(override_manifest_path(base_dir), content)?;
+    Ok(())
+}
+
+/// composer.lock 中已解析的依赖（packages / packages-dev），忽略与依赖无关的元数据
+fn composer_lock_deps(install_dir: &Path) -> Result<String> {
+    let content = std::fs::read_to_string(install_dir.join("composer.lock"))?;
+    let lock: serde_json::Value = serde_json::from_str(&content)?;
+    Ok(serde_json::json!([lock.get("packages"), lock.get("packages-dev")]).to_string())
+}
+
+/// 依赖的 SHA-256 哈希，带 sha256: 前缀记录；不带前缀的是旧版本写下的 md5
+fn composer_lock_hash(deps: &str) -> String {
+    use sha2::Digest;
+    let digest: String = sha2::Sha256::digest(deps.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    HashAlgorithm::Sha256.tag(&digest)
+}
+
+/// 首次安装时记录 composer.lock 哈希；之后复用或重装时比对，不一致则报安全错误。
+/// 旧的 md5 记录比对一致时就地改写为 sha256:…
+fn verify_override_lock(base_dir: &Path, install_key: &str, install_dir: &Path) -> Result<()> {
+    let deps = composer_lock_deps(install_dir)?;
+    let actual = composer_lock_hash(&deps);
+    let mut pins = load_override_pins(base_dir)?;
+    match pins.get(install_key) {
+        Some(expected) if *expected == actual => Ok(()),
+        Some(expected)
+            if !expected.contains(':') && *expected == format!("{:x}", md5::compute(&deps)) =>
+        {
+            tracing::debug!(
+                "Migrating md5 lock pin of override {} to sha256",
+                install_key
+            );
+            pins.insert(install_key.to_string(), actual);
+            save_override_pins(base_dir, &pins)
+        }
+        Some(expected) => Err(Error::Security(format!(
+            "composer.lock for override {} changed: expected {}, got {}",
+            install_key, expected, actual
+        ))),
+        None => {
+            pins.insert(install_key.to_string(), actual);
+            save_override_pins(base_dir, &pins)
+        }
+    }
+}
+
+/// 删除 override 安装时一并移除其锁定哈希
+pub fn remove_override_pin(base_dir: &Path, install_key: &str) -> Result<()> {
+    let mut pins = load_override_pins(base_dir)?;
+    if pins.remove(install_key).is_some() {
+        save_override_pins(base_dir, &pins)?;
+    }
+    Ok(())
+}
+
+/// 缓存中 Composer 安装对应的版本键：带开发依赖的安装记为 <version>+dev，与普通安装分开缓存
+pub fn install_version(version: &str, dev: bool) -> String {
+    if dev {
+        format!("{}+dev", version)
+    } else {
+        version.to_string()
+    }
+}
+
+/// 在安装根目录下为 Composer 包创建隔离项目、执行 composer install，返回安装目录和 vendor/bin 下的可执行路径。
+/// 安装目录为 base_dir/composer/<package-slug>-<version>，成功后写入 cache_manager 的 Composer 条目；
+/// 已有完整安装且缓存条目指向该目录时不再调用 Composer。
+/// dev 为 true 时把包的 require-dev 一并写入并安装（不加 --no-dev），版本键见 install_version。
 pub fn ensure_composer_installed(
     pkg: &ComposerPackage,
-    cache_dir: &Path,
+    dev: bool,
+    base_dir: &Path,
     cache_manager: &mut CacheManager,
     config: &Config,
     php_path: Option<&PathBuf>,
+    progress: &dyn ProgressObserver,
 ) -> Result<(PathBuf, PathBuf)> {
-    let slug = pkg.package.replace('/', "-");
-    let install_dir = cache_dir
-        .join("composer")
-        .join(format!("{}-{}", slug, pkg.version));
-
-    let bin_name = pkg
-        .bin_names
-        .first()
-        .cloned()
-        .unwrap_or_else(|| pkg.package.split('/').last().unwrap_or("tool").to_string());
+    let version_key = install_version(&pkg.version, dev);
+    let install_dir = base_dir.join("composer").join(sanitize_component(&format!(
+        "{}-{}",
+        pkg.package, version_key
+    )));
 
+    let bin_name = default_bin_name(pkg);
     let vendor_bin = install_dir.join("vendor").join("bin").join(&bin_name);
     if install_dir.exists() && vendor_bin.exists() {
-        if let Some(entry) = cache_manager.get_entry(&pkg.package, &pkg.version) {
+        if let Some(entry) = cache_manager.get_entry(&pkg.package, &version_key) {
             if entry.is_composer && entry.file_path == install_dir {
                 return Ok((install_dir, vendor_bin));
             }
         }
     }
 
-    // 需要安装
+    // 需要安装；残留的不完整安装目录先删除，避免 composer 沿用损坏的 vendor
+    if install_dir.exists() {
+        tracing::warn!(
+            "Removing incomplete install of {}@{} at {}",
+            pkg.package,
+            pkg.version,
+            install_dir.display()
+        );
+        std::fs::remove_dir_all(&install_dir)?;
+    }
+    std::fs::create_dir_all(&install_dir)?;
+    let php_binary = install_isolated(
+        pkg,
+        dev,
+        &install_dir,
+        &vendor_bin,
+        cache_manager,
+        config,
+        php_path,
+        progress,
+    )?;
+
+    cache_manager.add_composer_entry(
+        pkg.package.clone(),
+        version_key,
+        install_dir.clone(),
+        bin_name,
+        pkg.bin_names.clone(),
+        Executor::get_php_version(&php_binary),
+    )?;
+
+    Ok((install_dir, vendor_bin))
+}
+
+/// --no-cache 时的安装：全新安装到 temp_root 下的临时目录，不复用也不写缓存条目。
+/// 返回的 TempDir 在被丢弃时删除安装目录，安装失败时也不会残留
+pub fn install_uncached(
+    pkg: &ComposerPackage,
+    dev: bool,
+    temp_root: &Path,
+    cache_manager: &mut CacheManager,
+    config: &Config,
+    php_path: Option<&PathBuf>,
+    progress: &dyn ProgressObserver,
+) -> Result<(tempfile::TempDir, PathBuf)> {
+    let install_dir = tempfile::Builder::new()
+        .prefix("phpx-composer-")
+        .tempdir_in(temp_root)?;
+    let vendor_bin = install_dir
+        .path()
+        .join("vendor")
+        .join("bin")
+        .join(default_bin_name(pkg));
+    install_isolated(
+        pkg,
+        dev,
+        install_dir.path(),
+        &vendor_bin,
+        cache_manager,
+        config,
+        php_path,
+        progress,
+    )?;
+    Ok((install_dir, vendor_bin))
+}
+
+/// 包的默认 bin：声明的第一个 bin，没有时取包名的最后一段
+fn default_bin_name(pkg: &ComposerPackage) -> String {
+    pkg.bin_names.first().cloned().unwrap_or_else(|| {
+        pkg.package
+            .split('/')
+            .next_back()
+            .unwrap_or("tool")
+            .to_string()
+    })
+}
+
+/// 在已创建的空目录 install_dir 中写入隔离的 composer.json 并执行 composer install，
+/// 平台要求失败时按配置或用户确认重试；成功后 vendor_bin 必须存在。返回安装所用的 PHP
+#[allow(clippy::too_many_arguments)]
+fn install_isolated(
+    pkg: &ComposerPackage,
+    dev: bool,
+    install_dir: &Path,
+    vendor_bin: &Path,
+    cache_manager: &mut CacheManager,
+    config: &Config,
+    php_path: Option<&PathBuf>,
+    progress: &dyn ProgressObserver,
+) -> Result<PathBuf> {
     let composer_binary = resolve_composer_binary(cache_manager, config)?;
     let php_binary = find_php_for_composer(php_path)?;
 
-    std::fs::create_dir_all(&install_dir)?;
-
-    let composer_json = format!(r#"{{"require":{{"{}":"{}"}}}}"#, pkg.package, pkg.version);
-    std::fs::write(install_dir.join("composer.json"), &composer_json)?;
+    let mut composer_json = isolated_composer_json(
+        serde_json::json!({ pkg.package.as_str(): pkg.version }),
+        config,
+    );
+    if dev && !pkg.require_dev.is_empty() {
+        composer_json["require-dev"] = serde_json::json!(pkg.require_dev);
+    }
+    std::fs::write(install_dir.join("composer.json"), composer_json.to_string())?;
 
-    let composer_home = cache_dir.join("composer_home");
-    let composer_cache = cache_dir.join("composer_cache");
+    let composer_home = config.cache_dir.join("composer_home");
+    let composer_cache = config.cache_dir.join("composer_cache");
     std::fs::create_dir_all(&composer_home).ok();
     std::fs::create_dir_all(&composer_cache).ok();
 
-    let mut cmd = if composer_binary.extension().map_or(false, |e| e == "phar") {
-        let mut c = Command::new(&php_binary);
-        c.arg(&composer_binary);
-        c
-    } else {
-        Command::new(&composer_binary)
-    };
+    // extra_args 用于平台要求失败后的重试（--ignore-platform-req=...）
+    let install = |extra_args: &[String]| {
+        let mut cmd = if composer_binary.extension().is_some_and(|e| e == "phar") {
+            let mut c = Command::new(&php_binary);
+            c.arg(&composer_binary);
+            c
+        } else {
+            Command::new(&composer_binary)
+        };
+
+        cmd.arg("install").arg("--no-interaction");
+        if !dev {
+            cmd.arg("--no-dev");
+        }
+        cmd.args(&config.composer_args)
+            .args(extra_args)
+            .current_dir(install_dir)
+            .env("COMPOSER_HOME", &composer_home)
+            .env("COMPOSER_CACHE_DIR", &composer_cache)
+            .env_remove("COMPOSER"); // 避免使用项目根目录的 composer.json
+        if let Some(auth) = composer_auth(config) {
+            cmd.env("COMPOSER_AUTH", auth);
+        }
 
-    cmd.arg("install")
-        .arg("--no-interaction")
-        .arg("--no-dev")
-        .current_dir(&install_dir)
-        .env("COMPOSER_HOME", &composer_home)
-        .env("COMPOSER_CACHE_DIR", &composer_cache)
-        .env_remove("COMPOSER"); // 避免使用项目根目录的 composer.json
+        progress.on_install_start(&pkg.package, &pkg.version);
+        let output = cmd.output();
+        progress.on_install_finish(&pkg.package, &pkg.version);
+        output.map_err(|e| Error::ComposerInstallFailed(format!("Failed to run composer: {}", e)))
+    };
 
-    let output = cmd
-        .output()
-        .map_err(|e| Error::ComposerInstallFailed(format!("Failed to run composer: {}", e)))?;
+    let mut output = install(&[])?;
+    if !output.status.success() {
+        let requirements = platform_requirement_failures(&format!(
+            "{}\n{}",
+            String::from_utf8_lossy(&output.stderr),
+            String::from_utf8_lossy(&output.stdout)
+        ));
+        if !requirements.is_empty() {
+            let lines: Vec<String> = requirements.iter().map(|r| r.line.clone()).collect();
+            if !config.ignore_platform_reqs && !confirm_platform_retry(&pkg.package, &lines) {
+                return Err(Error::ComposerPlatformReqs {
+                    package: pkg.package.clone(),
+                    requirements: lines,
+                });
+            }
+            let mut ignore_args: Vec<String> = requirements
+                .iter()
+                .map(|r| format!("--ignore-platform-req={}", r.name))
+                .collect();
+            ignore_args.dedup();
+            tracing::warn!(
+                "Retrying composer install of {} with {}",
+                pkg.package,
+                ignore_args.join(" ")
+            );
+            output = install(&ignore_args)?;
+        }
+    }
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
@@ -154,18 +585,82 @@ pub fn ensure_composer_installed(
     if !vendor_bin.exists() {
         return Err(Error::ComposerInstallFailed(format!(
             "vendor/bin/{} not found after install",
-            bin_name
+            vendor_bin
+                .file_name()
+                .map(|name| name.to_string_lossy())
+                .unwrap_or_default()
         )));
     }
+    Ok(php_binary)
+}
 
-    cache_manager.add_composer_entry(
-        pkg.package.clone(),
-        pkg.version.clone(),
-        install_dir.clone(),
-        bin_name,
-    )?;
+/// Composer 输出中一条未满足的平台要求（php、php-64bit、ext-*、lib-*）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlatformRequirement {
+    /// 平台包名，用于 --ignore-platform-req
+    pub name: String,
+    /// Composer 输出中的原始行，如 "phpstan/phpstan 1.10.0 requires php ^8.1 -> your php version (7.4.33) does not satisfy that requirement."
+    pub line: String,
+}
 
-    Ok((install_dir, vendor_bin))
+/// 从 composer install 的输出中找出因平台要求失败的行；不是平台要求导致的失败时为空
+pub fn platform_requirement_failures(output: &str) -> Vec<PlatformRequirement> {
+    let mut found: Vec<PlatformRequirement> = Vec::new();
+    for line in output.lines() {
+        let line = line.trim().trim_start_matches("- ").trim();
+        if !(line.contains("your php") || line.contains("missing from your system")) {
+            continue;
+        }
+        let Some((_, rest)) = line.split_once(" requires ") else {
+            continue;
+        };
+        let name = rest.split_whitespace().next().unwrap_or("");
+        let platform = name == "php"
+            || name.starts_with("php-")
+            || name.starts_with("ext-")
+            || name.starts_with("lib-");
+        if platform && !found.iter().any(|r| r.line == line) {
+            found.push(PlatformRequirement {
+                name: name.to_string(),
+                line: line.to_string(),
+            });
+        }
+    }
+    found.sort_by(|a, b| a.name.cmp(&b.name));
+    found
+}
+
+/// 在终端中询问是否忽略失败的平台要求重试；非交互环境（CI、管道）直接返回 false
+fn confirm_platform_retry(package: &str, requirements: &[String]) -> bool {
+    if !std::io::stdin().is_terminal() || !std::io::stderr().is_terminal() {
+        return false;
+    }
+    eprintln!(
+        "Composer could not install {} because of platform requirements:",
+        package
+    );
+    for line in requirements {
+        eprintln!("  {}", line);
+    }
+    eprint!("Retry ignoring these requirements? [y/N] ");
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes")
+}
+
+/// 校验 `vendor/package:bin` 选择的 bin 是否在包声明的 bins 中，不在时报错并列出可选项
+pub fn check_bin(package: &str, bin: &str, available: &[String]) -> Result<()> {
+    if available.iter().any(|b| b == bin) {
+        Ok(())
+    } else {
+        Err(Error::UnknownBin {
+            package: package.to_string(),
+            bin: bin.to_string(),
+            available: available.to_vec(),
+        })
+    }
 }
 
 /// 解析 Composer 可执行路径：优先 phpx 缓存的 composer.phar，再 config.composer_path，再 PATH。
@@ -215,24 +710,221 @@ fn resolve_composer_binary(cache_manager: &mut CacheManager, config: &Config) ->
     Err(Error::ComposerNotFound)
 }
 
+/// 查找 phpx 会使用的 Composer，返回其路径与 `composer --version` 报告的版本号
+pub fn detect_composer(
+    cache_manager: &mut CacheManager,
+    config: &Config,
+    php_path: Option<&PathBuf>,
+) -> Option<(PathBuf, Option<String>)> {
+    let composer_binary = resolve_composer_binary(cache_manager, config).ok()?;
+    let mut cmd = if composer_binary.extension().is_some_and(|e| e == "phar") {
+        let mut c = Command::new(find_php_for_composer(php_path).ok()?);
+        c.arg(&composer_binary);
+        c
+    } else {
+        Command::new(&composer_binary)
+    };
+    // 输出形如 "Composer version 2.7.1 2024-02-09 15:26:28"
+    let version = cmd
+        .arg("--version")
+        .arg("--no-ansi")
+        .output()
+        .ok()
+        .filter(|out| out.status.success())
+        .and_then(|out| {
+            let stdout = String::from_utf8_lossy(&out.stdout).to_string();
+            let mut words = stdout.split_whitespace();
+            words.find(|w| *w == "version")?;
+            words.next().map(str::to_string)
+        });
+    Some((composer_binary, version))
+}
+
 fn find_php_for_composer(php_path: Option<&PathBuf>) -> Result<PathBuf> {
     if let Some(p) = php_path {
-        if p.exists() {
-            return Ok(p.clone());
-        }
-        return Err(Error::Execution(format!(
-            "PHP path does not exist: {}",
-            p.display()
-        )));
+        return Executor::resolve_php_path(p);
     }
-    let possible = ["php", "/usr/bin/php", "/usr/local/bin/php"];
-    for name in &possible {
-        let path = PathBuf::from(name);
-        if Command::new(&path).arg("--version").output().is_ok() {
-            return Ok(path);
-        }
+    if let Some(path) = Executor::php_from_version_file().or_else(Executor::discover_php) {
+        return Ok(path);
     }
     Err(Error::Execution(
         "PHP not found. Install PHP or use --php".to_string(),
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn uncached_install_lives_in_a_temp_dir_that_is_removed_even_on_failure() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let temp_root = dir.path().join("tmp");
+        std::fs::create_dir(&temp_root).unwrap();
+        // 假的 composer：FAIL 文件存在时失败，否则在当前目录生成 vendor/bin/tool
+        let composer = dir.path().join("composer");
+        let fail = dir.path().join("FAIL");
+        std::fs::write(
+            &composer,
+            format!(
+                "#!/bin/sh\n[ -e '{}' ] && exit 1\nmkdir -p vendor/bin && touch vendor/bin/tool\n",
+                fail.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&composer, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let config = Config {
+            cache_dir: dir.path().join("cache"),
+            composer_path: Some(composer.clone()),
+            ..Config::default()
+        };
+        let mut cache_manager = CacheManager::new(config.cache_dir.clone()).unwrap();
+        let pkg = ComposerPackage {
+            package: "acme/tool".to_string(),
+            version: "1.0.0".to_string(),
+            bin_names: vec!["tool".to_string()],
+            require_dev: Default::default(),
+        };
+        let install = |cache_manager: &mut CacheManager| {
+            install_uncached(
+                &pkg,
+                false,
+                &temp_root,
+                cache_manager,
+                &config,
+                Some(&composer),
+                &crate::progress::NoProgress,
+            )
+        };
+
+        let (temp, vendor_bin) = install(&mut cache_manager).unwrap();
+        assert!(vendor_bin.starts_with(temp.path()) && vendor_bin.is_file());
+        assert!(cache_manager.get_entry("acme/tool", "1.0.0").is_none());
+        drop(temp);
+        assert_eq!(std::fs::read_dir(&temp_root).unwrap().count(), 0);
+
+        std::fs::write(&fail, "").unwrap();
+        assert!(install(&mut cache_manager).is_err());
+        assert_eq!(std::fs::read_dir(&temp_root).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn composer_auth_prefers_the_configured_file_then_env_then_user_file_then_token() {
+        let dir = tempfile::tempdir().unwrap();
+        let configured = dir.path().join("configured.json");
+        let user = dir.path().join("user.json");
+        std::fs::write(&configured, "configured").unwrap();
+        std::fs::write(&user, "user").unwrap();
+        let env = || Some("env".to_string());
+        let token = || Some("tok".to_string());
+
+        let pick = |file: Option<&Path>, env: Option<String>, user: Option<&Path>, token| {
+            select_composer_auth(file, env, user, token)
+        };
+        assert_eq!(
+            pick(Some(&configured), env(), Some(&user), token()).as_deref(),
+            Some("configured")
+        );
+        // 配置的文件读不到时退回后面的来源
+        assert_eq!(
+            pick(
+                Some(&dir.path().join("missing.json")),
+                env(),
+                Some(&user),
+                token()
+            )
+            .as_deref(),
+            Some("env")
+        );
+        assert_eq!(
+            pick(None, Some(String::new()), Some(&user), token()).as_deref(),
+            Some("user")
+        );
+        assert_eq!(
+            pick(None, None, None, token()).as_deref(),
+            Some(r#"{"github-oauth":{"github.com":"tok"}}"#)
+        );
+        assert_eq!(pick(None, None, None, None), None);
+    }
+
+    #[test]
+    fn platform_requirement_failures_finds_php_and_extensions() {
+        let output = r#"Your requirements could not be resolved to an installable set of packages.
+
+  Problem 1
+    - Root composer.json requires phpstan/phpstan 1.10.0 -> satisfiable by phpstan/phpstan[1.10.0].
+    - phpstan/phpstan 1.10.0 requires php ^8.1 -> your php version (7.4.33) does not satisfy that requirement.
+  Problem 2
+    - vimeo/psalm 5.0.0 requires ext-dom * -> it is missing from your system. Install or enable PHP's dom extension.
+"#;
+        let found = platform_requirement_failures(output);
+        let names: Vec<&str> = found.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["ext-dom", "php"]);
+        assert!(found[1]
+            .line
+            .starts_with("phpstan/phpstan 1.10.0 requires php ^8.1"));
+
+        let unrelated = "  Problem 1\n    - Root composer.json requires foo/bar ^9, it could not be found in any version";
+        assert!(platform_requirement_failures(unrelated).is_empty());
+    }
+
+    #[test]
+    fn override_lock_pins_are_sha256_and_legacy_md5_pins_are_migrated() {
+        let dir = tempfile::tempdir().unwrap();
+        let install_dir = dir.path().join("override").join("shared");
+        std::fs::create_dir_all(&install_dir).unwrap();
+        let lock = r#"{"packages":[{"name":"acme/lib","version":"1.0.0"}],"packages-dev":[]}"#;
+        std::fs::write(install_dir.join("composer.lock"), lock).unwrap();
+        let deps = composer_lock_deps(&install_dir).unwrap();
+
+        let legacy = format!("{:x}", md5::compute(&deps));
+        let pins = BTreeMap::from([("shared".to_string(), legacy)]);
+        save_override_pins(dir.path(), &pins).unwrap();
+        verify_override_lock(dir.path(), "shared", &install_dir).unwrap();
+        let pinned = load_override_pins(dir.path()).unwrap()["shared"].clone();
+        assert!(pinned.starts_with("sha256:") && pinned == composer_lock_hash(&deps));
+
+        std::fs::write(
+            install_dir.join("composer.lock"),
+            lock.replace("1.0.0", "6.6.6"),
+        )
+        .unwrap();
+        assert!(matches!(
+            verify_override_lock(dir.path(), "shared", &install_dir),
+            Err(Error::Security(_))
+        ));
+    }
+
+    #[test]
+    fn override_requirement_reads_the_single_require_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("composer.json"),
+            r#"{"require":{"symfony/console":"7.1.0-beta1"}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            override_requirement(dir.path()),
+            Some(("symfony/console".to_string(), "7.1.0-beta1".to_string()))
+        );
+    }
+
+    #[test]
+    fn isolated_composer_json_only_allows_configured_plugins() {
+        let require = serde_json::json!({ "acme/tool": "1.0.0" });
+        let mut config = Config::default();
+        let json = isolated_composer_json(require.clone(), &config);
+        assert_eq!(json["config"]["allow-plugins"], serde_json::json!(false));
+
+        config.allow_plugins = vec!["php-http/discovery".to_string()];
+        let json = isolated_composer_json(require, &config);
+        assert_eq!(
+            json["config"]["allow-plugins"],
+            serde_json::json!({ "php-http/discovery": true })
+        );
+        assert_eq!(json["require"]["acme/tool"], "1.0.0");
+    }
+}
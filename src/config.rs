@@ -1,5 +1,12 @@
+use crate::download::DEFAULT_USER_AGENT;
+use crate::error::{Error, Result};
+use crate::executor::{Executor, DEFAULT_MIN_PHP_VERSION, DEFAULT_PROJECT_SEARCH_DEPTH};
+use crate::resolver::{ResolverConfig, SourcePreference, Stability};
+use crate::security::HashAlgorithm;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use toml_edit::DocumentMut;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
@@ -10,11 +17,102 @@ pub struct Config {
     pub default_php_path: Option<PathBuf>,
     /// Composer 可执行文件路径；未设置时优先使用 phpx 缓存的 composer.phar
     pub composer_path: Option<PathBuf>,
+    /// 隔离安装时使用的 Composer auth.json（私有仓库认证），优先于 COMPOSER_AUTH 环境变量；
+    /// 两者都未设置时使用用户全局 Composer 的 auth.json
+    pub composer_auth_file: Option<PathBuf>,
+    /// 隔离安装时追加到 composer install 的额外参数（如 --ignore-platform-req=php、--prefer-source）
+    pub composer_args: Vec<String>,
+    /// 隔离安装时允许启用的 Composer 插件（写入生成的 composer.json 的 config.allow-plugins，可用 * 通配）；
+    /// 为空时禁用全部插件，避免非交互运行卡在插件授权提示上
+    pub allow_plugins: Vec<String>,
+    /// composer install 因平台要求（PHP 版本、扩展）失败时，自动忽略这些要求重试一次，而不是报错或询问
+    pub ignore_platform_reqs: bool,
+    /// Composer 隔离安装与 override 安装的根目录（其下为 composer/、override/）；
+    /// 未设置时使用 cache_dir，相对路径按当前目录解析（如项目内的 .phpx/tools）
+    pub install_dir: Option<PathBuf>,
+    /// `phpx global add` 写入 shim 的目录，需在 PATH 中；未设置时为 ~/.phpx/bin
+    pub bin_dir: Option<PathBuf>,
+    /// 已弃用：解析与下载从不读取它，设置时加载配置会给出警告，save 也不再写出；
+    /// 离线或自建镜像请把 packagist_base / github_download_base 设为镜像地址（可为 file://）
     pub download_mirrors: Vec<String>,
+    /// 单个下载文件的大小上限（字节），防止误解析的地址写满磁盘；未设置为不限制
+    pub max_download_size: Option<u64>,
+    /// 单次 HTTP 请求的总超时（秒），包含下载正文
+    pub network_timeout: u64,
+    /// 建立连接的超时（秒），用于快速跳过不可达的镜像
+    pub connect_timeout: u64,
+    /// 解析时可接受的最低稳定性（stable、RC、beta、alpha、dev），默认 stable
+    pub min_stability: Stability,
+    /// 解析来源：auto（Packagist → GitHub Releases → GitHub tags → 直链，先成功者胜出）、packagist 或 github
+    pub source: SourcePreference,
+    /// 工具名 -> 默认参数，运行时置于用户参数之前（如 phpstan = ["analyse", "--memory-limit=-1"]）
+    pub default_args: HashMap<String, Vec<String>>,
+    /// 工具名 -> 默认 PHP ini 设置（以 -d 传给解释器，如 phpstan = ["memory_limit=-1"]）；
+    /// 覆盖内置默认值，设为空数组可关闭该工具的内置设置
+    pub default_php_ini: HashMap<String, Vec<String>>,
+    /// 按内容（sha256）去重缓存的 phar：相同字节只在 cache_dir/blobs 中存一份，各条目以硬链接引用
+    pub dedupe_phars: bool,
+    /// 新缓存条目记录文件哈希所用的算法（md5、sha1、sha256），默认 sha256；已有条目按其记录的算法校验
+    pub hash_algorithm: HashAlgorithm,
+    /// 按版本约束运行时（如 phpstan@^1.10）直接复用缓存中满足该约束的最新版本，不访问网络；
+    /// 默认每次都重新解析，约束内有更新的版本就安装它
+    pub reuse_cached: bool,
+    /// 工具已有 phpx 缓存的副本时优先使用缓存，而不是 vendor/bin 或全局 Composer 中的工具（同 --prefer-cache）
+    pub prefer_cache: bool,
+    /// 解析前先探测 Packagist / GitHub 地址（镜像）能否连通，跳过不可达的来源
+    pub mirror_health_check: bool,
+    /// 不校验 HTTPS 证书（--no-verify-tls）；只来自命令行，不读写配置文件，避免长期关闭校验
+    #[serde(skip)]
+    pub no_verify_tls: bool,
+    /// HTTP 请求的 User-Agent；未设置时为 phpx/<版本>
+    pub user_agent: Option<String>,
+    /// 不读取项目 composer.json 的 PHP 约束，也就不再提示当前 PHP 不满足要求
+    pub ignore_project_php: bool,
+    /// 运行工具所需的最低 PHP 版本；项目 composer.json 没有 PHP 约束时据此提前提示 PHP 过旧，空字符串关闭检查
+    pub min_php_version: String,
+    /// PHP 低于 min_php_version 时报错退出，而不只是警告
+    pub strict_php_version: bool,
+    /// 向上查找项目 composer.json 最多检查的目录层数（遇到 .git 目录也会停止）
+    pub project_search_depth: usize,
+    /// Packagist 地址，可指向自建镜像（如 https://packagist.example.com）
+    pub packagist_base: String,
+    /// GitHub API 地址，GitHub Enterprise 一般为 https://<host>/api/v3
+    pub github_api_base: String,
+    /// GitHub 下载地址（releases/latest/download 直链所在的站点）
+    pub github_download_base: String,
 }
 
+/// max_cache_size 的下限（1MB），更小的值会导致几乎每次下载都触发淘汰
+const MIN_CACHE_SIZE: u64 = 1024 * 1024;
+
+/// `phpx config edit` 新建用户配置时写入的模板：只有注释，不设置任何键，
+/// 以免把内置默认值固化下来、遮住系统配置与以后版本的新默认值
+const USER_CONFIG_TEMPLATE: &str = r#"# phpx user configuration
+#
+# Only keys set here override the built-in defaults and the system config
+# (/etc/phpx/config.toml). Uncomment and edit what you need.
+
+# cache_dir = "~/.cache/phpx"
+# cache_ttl = 604800              # seconds
+# max_cache_size = 1073741824     # bytes
+# skip_verify = false
+# default_php_path = "/usr/bin/php"
+# composer_path = "/usr/local/bin/composer"
+# network_timeout = 300           # seconds
+# connect_timeout = 10            # seconds
+# min_stability = "stable"        # stable, RC, beta, alpha, dev
+# source = "auto"                 # auto, packagist, github
+
+# Mirrors (http(s):// or file://)
+# packagist_base = "https://packagist.org"
+# github_download_base = "https://github.com"
+
+# [default_args]
+# phpstan = ["analyse", "--memory-limit=1G"]
+"#;
+
 /// 配置文件磁盘格式：路径为字符串，便于 TOML 中使用 ~
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 struct ConfigFile {
     pub cache_dir: Option<String>,
     pub cache_ttl: Option<u64>,
@@ -22,11 +120,90 @@ struct ConfigFile {
     pub skip_verify: Option<bool>,
     pub default_php_path: Option<String>,
     pub composer_path: Option<String>,
+    pub composer_auth_file: Option<String>,
+    pub composer_args: Option<Vec<String>>,
+    pub allow_plugins: Option<Vec<String>>,
+    pub ignore_platform_reqs: Option<bool>,
+    pub install_dir: Option<String>,
+    pub bin_dir: Option<String>,
     pub download_mirrors: Option<Vec<String>>,
+    pub max_download_size: Option<u64>,
+    pub network_timeout: Option<u64>,
+    pub connect_timeout: Option<u64>,
+    pub min_stability: Option<Stability>,
+    pub source: Option<SourcePreference>,
+    pub default_args: Option<HashMap<String, Vec<String>>>,
+    pub default_php_ini: Option<HashMap<String, Vec<String>>>,
+    pub dedupe_phars: Option<bool>,
+    pub hash_algorithm: Option<HashAlgorithm>,
+    pub reuse_cached: Option<bool>,
+    pub prefer_cache: Option<bool>,
+    pub mirror_health_check: Option<bool>,
+    pub user_agent: Option<String>,
+    pub ignore_project_php: Option<bool>,
+    pub min_php_version: Option<String>,
+    pub strict_php_version: Option<bool>,
+    pub project_search_depth: Option<usize>,
+    pub packagist_base: Option<String>,
+    pub github_api_base: Option<String>,
+    pub github_download_base: Option<String>,
+}
+
+impl ConfigFile {
+    /// 以 over 覆盖 self：over 中出现的键生效，default_args / default_php_ini 按工具名合并
+    fn merge(self, over: ConfigFile) -> ConfigFile {
+        fn merge_map(
+            base: Option<HashMap<String, Vec<String>>>,
+            over: Option<HashMap<String, Vec<String>>>,
+        ) -> Option<HashMap<String, Vec<String>>> {
+            match (base, over) {
+                (Some(mut base), Some(over)) => {
+                    base.extend(over);
+                    Some(base)
+                }
+                (base, over) => over.or(base),
+            }
+        }
+        ConfigFile {
+            cache_dir: over.cache_dir.or(self.cache_dir),
+            cache_ttl: over.cache_ttl.or(self.cache_ttl),
+            max_cache_size: over.max_cache_size.or(self.max_cache_size),
+            skip_verify: over.skip_verify.or(self.skip_verify),
+            default_php_path: over.default_php_path.or(self.default_php_path),
+            composer_path: over.composer_path.or(self.composer_path),
+            composer_auth_file: over.composer_auth_file.or(self.composer_auth_file),
+            composer_args: over.composer_args.or(self.composer_args),
+            allow_plugins: over.allow_plugins.or(self.allow_plugins),
+            ignore_platform_reqs: over.ignore_platform_reqs.or(self.ignore_platform_reqs),
+            install_dir: over.install_dir.or(self.install_dir),
+            bin_dir: over.bin_dir.or(self.bin_dir),
+            download_mirrors: over.download_mirrors.or(self.download_mirrors),
+            max_download_size: over.max_download_size.or(self.max_download_size),
+            network_timeout: over.network_timeout.or(self.network_timeout),
+            connect_timeout: over.connect_timeout.or(self.connect_timeout),
+            min_stability: over.min_stability.or(self.min_stability),
+            source: over.source.or(self.source),
+            default_args: merge_map(self.default_args, over.default_args),
+            default_php_ini: merge_map(self.default_php_ini, over.default_php_ini),
+            dedupe_phars: over.dedupe_phars.or(self.dedupe_phars),
+            hash_algorithm: over.hash_algorithm.or(self.hash_algorithm),
+            reuse_cached: over.reuse_cached.or(self.reuse_cached),
+            prefer_cache: over.prefer_cache.or(self.prefer_cache),
+            mirror_health_check: over.mirror_health_check.or(self.mirror_health_check),
+            user_agent: over.user_agent.or(self.user_agent),
+            ignore_project_php: over.ignore_project_php.or(self.ignore_project_php),
+            min_php_version: over.min_php_version.or(self.min_php_version),
+            strict_php_version: over.strict_php_version.or(self.strict_php_version),
+            project_search_depth: over.project_search_depth.or(self.project_search_depth),
+            packagist_base: over.packagist_base.or(self.packagist_base),
+            github_api_base: over.github_api_base.or(self.github_api_base),
+            github_download_base: over.github_download_base.or(self.github_download_base),
+        }
+    }
 }
 
 /// 将 "~" 或 "~/path" 展开为家目录路径
-fn expand_tilde(path: &str) -> PathBuf {
+pub fn expand_tilde(path: &str) -> PathBuf {
     let path = path.trim();
     if path == "~" {
         return dirs::home_dir().unwrap_or_else(|| PathBuf::from("."));
@@ -46,10 +223,12 @@ fn expand_tilde(path: &str) -> PathBuf {
 
 impl Default for Config {
     fn default() -> Self {
-        // 默认缓存目录 ~/.cache/phpx（与需求一致）
-        let cache_dir = dirs::home_dir()
-            .map(|h| h.join(".cache").join("phpx"))
+        // 默认缓存目录：平台缓存目录下的 phpx（Linux 遵循 XDG_CACHE_HOME，默认 ~/.cache/phpx）
+        let cache_dir = dirs::cache_dir()
+            .or_else(|| dirs::home_dir().map(|h| h.join(".cache")))
+            .map(|d| d.join("phpx"))
             .unwrap_or_else(|| PathBuf::from(".cache").join("phpx"));
+        let hosts = ResolverConfig::default();
 
         Self {
             cache_dir,
@@ -58,30 +237,100 @@ impl Default for Config {
             skip_verify: false,
             default_php_path: None,
             composer_path: None,
-            download_mirrors: vec![
-                "https://packagist.org".to_string(),
-                "https://github.com".to_string(),
-            ],
+            composer_auth_file: None,
+            composer_args: Vec::new(),
+            allow_plugins: Vec::new(),
+            ignore_platform_reqs: false,
+            install_dir: None,
+            bin_dir: None,
+            download_mirrors: Vec::new(),
+            max_download_size: None,
+            network_timeout: 300,
+            connect_timeout: 10,
+            min_stability: Stability::Stable,
+            source: SourcePreference::Auto,
+            default_args: HashMap::new(),
+            default_php_ini: HashMap::new(),
+            dedupe_phars: false,
+            hash_algorithm: HashAlgorithm::Sha256,
+            reuse_cached: false,
+            prefer_cache: false,
+            mirror_health_check: false,
+            no_verify_tls: false,
+            user_agent: None,
+            ignore_project_php: false,
+            min_php_version: DEFAULT_MIN_PHP_VERSION.to_string(),
+            strict_php_version: false,
+            project_search_depth: DEFAULT_PROJECT_SEARCH_DEPTH,
+            packagist_base: hosts.packagist_base,
+            github_api_base: hosts.github_api_base,
+            github_download_base: hosts.github_download_base,
         }
     }
 }
 
 impl Config {
-    /// 默认配置文件路径：~/.config/phpx/config.toml（与 README 约定一致）
+    /// 实际使用的 User-Agent：配置值或默认的 phpx/<版本>
+    pub fn user_agent(&self) -> &str {
+        self.user_agent.as_deref().unwrap_or(DEFAULT_USER_AGENT)
+    }
+
+    /// Composer 隔离安装与 override 安装的根目录：install_dir（转为绝对路径）或 cache_dir
+    pub fn install_base(&self) -> PathBuf {
+        match &self.install_dir {
+            Some(dir) => std::path::absolute(dir).unwrap_or_else(|_| dir.clone()),
+            None => self.cache_dir.clone(),
+        }
+    }
+
+    /// shim 目录：bin_dir 或 ~/.phpx/bin
+    pub fn shim_dir(&self) -> PathBuf {
+        self.bin_dir
+            .clone()
+            .unwrap_or_else(|| expand_tilde("~/.phpx/bin"))
+    }
+
+    /// 默认配置文件路径：平台配置目录下的 phpx/config.toml
+    /// （Linux 遵循 XDG_CONFIG_HOME，默认 ~/.config/phpx/config.toml）
     pub fn default_config_path() -> Option<PathBuf> {
-        dirs::home_dir().map(|h| h.join(".config").join("phpx").join("config.toml"))
+        dirs::config_dir()
+            .or_else(|| dirs::home_dir().map(|h| h.join(".config")))
+            .map(|d| d.join("phpx").join("config.toml"))
     }
 
-    /// 从指定路径或默认路径加载配置；文件不存在时返回默认配置
-    pub fn load(override_path: Option<PathBuf>) -> Result<Self, Box<dyn std::error::Error>> {
-        let path = override_path.or_else(Self::default_config_path);
-        let path = match path {
-            Some(p) if p.exists() => p,
-            _ => return Ok(Self::default()),
-        };
+    /// 系统级配置文件路径：Unix 为 /etc/phpx/config.toml，Windows 为 %ProgramData%\phpx\config.toml
+    pub fn system_config_path() -> Option<PathBuf> {
+        if cfg!(windows) {
+            std::env::var_os("ProgramData")
+                .map(PathBuf::from)
+                .map(|d| d.join("phpx").join("config.toml"))
+        } else {
+            Some(PathBuf::from("/etc/phpx/config.toml"))
+        }
+    }
 
-        let content = std::fs::read_to_string(&path)?;
-        let file: ConfigFile = toml::from_str(&content)?;
+    /// 加载配置，优先级从低到高：
+    /// 内置默认值 < 系统级配置（system_config_path）< 用户配置（--config 指定的文件或 default_config_path）。
+    /// 各层只覆盖自身写出的键，default_args 按工具名合并；文件不存在的层直接跳过。
+    /// 取值不合法时返回 Error::Config
+    pub fn load(override_path: Option<PathBuf>) -> Result<Self> {
+        let layers = [
+            Self::system_config_path(),
+            override_path.or_else(Self::default_config_path),
+        ];
+        let mut file = ConfigFile::default();
+        let mut source = None;
+        for path in layers.into_iter().flatten().filter(|p| p.exists()) {
+            let content = std::fs::read_to_string(&path)
+                .map_err(|e| Error::Config(format!("Cannot read {}: {}", path.display(), e)))?;
+            let layer: ConfigFile = toml::from_str(&content)
+                .map_err(|e| Error::Config(format!("Invalid {}: {}", path.display(), e)))?;
+            file = file.merge(layer);
+            source = Some(path);
+        }
+        let Some(path) = source else {
+            return Ok(Self::default());
+        };
 
         let default = Self::default();
         let cache_dir = file
@@ -102,25 +351,211 @@ impl Config {
             .as_deref()
             .map(expand_tilde)
             .or(default.composer_path);
+        let composer_auth_file = file
+            .composer_auth_file
+            .as_deref()
+            .map(expand_tilde)
+            .or(default.composer_auth_file);
+        let composer_args = file.composer_args.unwrap_or(default.composer_args);
+        let allow_plugins = file.allow_plugins.unwrap_or(default.allow_plugins);
+        let ignore_platform_reqs = file
+            .ignore_platform_reqs
+            .unwrap_or(default.ignore_platform_reqs);
+        let install_dir = file
+            .install_dir
+            .as_deref()
+            .map(expand_tilde)
+            .or(default.install_dir);
+        let bin_dir = file
+            .bin_dir
+            .as_deref()
+            .map(expand_tilde)
+            .or(default.bin_dir);
         let download_mirrors = file.download_mirrors.unwrap_or(default.download_mirrors);
+        let max_download_size = file.max_download_size.or(default.max_download_size);
+        let network_timeout = file.network_timeout.unwrap_or(default.network_timeout);
+        let connect_timeout = file.connect_timeout.unwrap_or(default.connect_timeout);
+        let min_stability = file.min_stability.unwrap_or(default.min_stability);
+        let source = file.source.unwrap_or(default.source);
+        let default_args = file.default_args.unwrap_or(default.default_args);
+        let default_php_ini = file.default_php_ini.unwrap_or(default.default_php_ini);
+        let dedupe_phars = file.dedupe_phars.unwrap_or(default.dedupe_phars);
+        let hash_algorithm = file.hash_algorithm.unwrap_or(default.hash_algorithm);
+        let reuse_cached = file.reuse_cached.unwrap_or(default.reuse_cached);
+        let prefer_cache = file.prefer_cache.unwrap_or(default.prefer_cache);
+        let mirror_health_check = file
+            .mirror_health_check
+            .unwrap_or(default.mirror_health_check);
+        let user_agent = file.user_agent.or(default.user_agent);
+        let ignore_project_php = file
+            .ignore_project_php
+            .unwrap_or(default.ignore_project_php);
+        let min_php_version = file.min_php_version.unwrap_or(default.min_php_version);
+        let strict_php_version = file
+            .strict_php_version
+            .unwrap_or(default.strict_php_version);
+        let project_search_depth = file
+            .project_search_depth
+            .unwrap_or(default.project_search_depth);
+        let packagist_base = file.packagist_base.unwrap_or(default.packagist_base);
+        let github_api_base = file.github_api_base.unwrap_or(default.github_api_base);
+        let github_download_base = file
+            .github_download_base
+            .unwrap_or(default.github_download_base);
 
-        Ok(Self {
+        let config = Self {
             cache_dir,
             cache_ttl,
             max_cache_size,
             skip_verify,
             default_php_path,
             composer_path,
+            composer_auth_file,
+            composer_args,
+            allow_plugins,
+            ignore_platform_reqs,
+            install_dir,
+            bin_dir,
             download_mirrors,
-        })
+            max_download_size,
+            network_timeout,
+            connect_timeout,
+            min_stability,
+            source,
+            default_args,
+            default_php_ini,
+            dedupe_phars,
+            hash_algorithm,
+            reuse_cached,
+            prefer_cache,
+            mirror_health_check,
+            no_verify_tls: default.no_verify_tls,
+            user_agent,
+            ignore_project_php,
+            min_php_version,
+            strict_php_version,
+            project_search_depth,
+            packagist_base,
+            github_api_base,
+            github_download_base,
+        };
+        config
+            .validate()
+            .map_err(|e| Error::Config(format!("{}: {}", path.display(), e)))?;
+        Ok(config)
     }
 
-    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
-        // 保存到默认路径；路径字段序列化为字符串
-        let path = Self::default_config_path().ok_or("Cannot determine config directory")?;
-        if let Some(parent) = path.parent() {
-            std::fs::create_dir_all(parent)?;
+    /// 校验配置取值：明显无效的取值返回错误（消息中带字段名），
+    /// 不影响启动的问题（如路径不存在）只记录警告
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if self.cache_ttl == 0 {
+            return Err("cache_ttl must be greater than 0 seconds".to_string());
+        }
+        if self.max_cache_size < MIN_CACHE_SIZE {
+            return Err(format!(
+                "max_cache_size must be at least {} bytes, got {}",
+                MIN_CACHE_SIZE, self.max_cache_size
+            ));
+        }
+        if self.network_timeout == 0 {
+            return Err("network_timeout must be greater than 0 seconds".to_string());
+        }
+        if self.connect_timeout == 0 {
+            return Err("connect_timeout must be greater than 0 seconds".to_string());
+        }
+        if !self.min_php_version.trim().is_empty()
+            && Executor::normalize_php_version(&self.min_php_version)
+                .and_then(|v| semver::Version::parse(&v).ok())
+                .is_none()
+        {
+            return Err(format!(
+                "min_php_version must be a PHP version such as 7.4, got {:?}",
+                self.min_php_version
+            ));
+        }
+        if self.project_search_depth == 0 {
+            return Err("project_search_depth must be at least 1".to_string());
+        }
+        if let Some(user_agent) = &self.user_agent {
+            if user_agent.trim().is_empty()
+                || reqwest::header::HeaderValue::from_str(user_agent).is_err()
+            {
+                return Err(format!(
+                    "user_agent is not a valid header value: {:?}",
+                    user_agent
+                ));
+            }
+        }
+        if self.max_download_size == Some(0) {
+            return Err("max_download_size must be greater than 0 bytes".to_string());
+        }
+        let hosts = [
+            ("packagist_base", &self.packagist_base),
+            ("github_api_base", &self.github_api_base),
+            ("github_download_base", &self.github_download_base),
+        ];
+        for (field, value) in hosts {
+            // Packagist 与 GitHub 下载地址还可以是 file://，指向本地或共享挂载的镜像目录，用于离线环境
+            let file_allowed = field != "github_api_base";
+            match reqwest::Url::parse(value) {
+                Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+                Ok(url) if file_allowed && url.scheme() == "file" => {}
+                Ok(_) if file_allowed => {
+                    return Err(format!(
+                        "{} must be an http(s) or file URL: {}",
+                        field, value
+                    ))
+                }
+                Ok(_) => return Err(format!("{} must be an http(s) URL: {}", field, value)),
+                Err(e) => return Err(format!("{} is not a valid URL: {} ({})", field, value, e)),
+            }
+        }
+        if self.connect_timeout > self.network_timeout {
+            tracing::warn!(
+                "connect_timeout ({}s) exceeds network_timeout ({}s)",
+                self.connect_timeout,
+                self.network_timeout
+            );
+        }
+        if !self.download_mirrors.is_empty() {
+            tracing::warn!(
+                "download_mirrors is deprecated and ignored; set packagist_base / github_download_base to your mirror (http(s) or file://) instead"
+            );
+        }
+        if let Some(php) = &self.default_php_path {
+            if !php.exists() {
+                tracing::warn!("default_php_path does not exist: {}", php.display());
+            }
         }
+        if let Some(composer) = &self.composer_path {
+            if !composer.exists() {
+                tracing::warn!("composer_path does not exist: {}", composer.display());
+            }
+        }
+        if let Some(auth) = &self.composer_auth_file {
+            if !auth.exists() {
+                tracing::warn!("composer_auth_file does not exist: {}", auth.display());
+            }
+        }
+        Ok(())
+    }
+
+    /// 用户配置文件不存在时写入只含注释的模板，返回其路径；已存在时原样保留
+    pub fn create_user_config(path: Option<PathBuf>) -> Result<PathBuf> {
+        let path = Self::user_config_path(path)?;
+        if !path.exists() {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&path, USER_CONFIG_TEMPLATE)?;
+        }
+        Ok(path)
+    }
+
+    /// 保存到指定路径（None 为默认路径）：在原文件基础上只改写有变化的键，
+    /// 保留注释、格式及 phpx 不认识的键
+    pub fn save(&self, path: Option<PathBuf>) -> Result<()> {
+        let path = Self::user_config_path(path)?;
         let cache_dir_str = self.cache_dir.to_string_lossy();
         let default_php_str = self
             .default_php_path
@@ -137,10 +572,180 @@ impl Config {
             skip_verify: Some(self.skip_verify),
             default_php_path: default_php_str,
             composer_path: composer_path_str,
-            download_mirrors: Some(self.download_mirrors.clone()),
+            composer_auth_file: self
+                .composer_auth_file
+                .as_ref()
+                .map(|p| p.to_string_lossy().to_string()),
+            composer_args: Some(self.composer_args.clone()),
+            allow_plugins: Some(self.allow_plugins.clone()),
+            ignore_platform_reqs: Some(self.ignore_platform_reqs),
+            install_dir: self
+                .install_dir
+                .as_ref()
+                .map(|p| p.to_string_lossy().to_string()),
+            bin_dir: self
+                .bin_dir
+                .as_ref()
+                .map(|p| p.to_string_lossy().to_string()),
+            download_mirrors: None,
+            max_download_size: self.max_download_size,
+            network_timeout: Some(self.network_timeout),
+            connect_timeout: Some(self.connect_timeout),
+            min_stability: Some(self.min_stability),
+            source: Some(self.source),
+            default_args: Some(self.default_args.clone()),
+            default_php_ini: Some(self.default_php_ini.clone()),
+            dedupe_phars: Some(self.dedupe_phars),
+            hash_algorithm: Some(self.hash_algorithm),
+            reuse_cached: Some(self.reuse_cached),
+            prefer_cache: Some(self.prefer_cache),
+            mirror_health_check: Some(self.mirror_health_check),
+            user_agent: self.user_agent.clone(),
+            ignore_project_php: Some(self.ignore_project_php),
+            min_php_version: Some(self.min_php_version.clone()),
+            strict_php_version: Some(self.strict_php_version),
+            project_search_depth: Some(self.project_search_depth),
+            packagist_base: Some(self.packagist_base.clone()),
+            github_api_base: Some(self.github_api_base.clone()),
+            github_download_base: Some(self.github_download_base.clone()),
         };
-        let content = toml::to_string_pretty(&file)?;
-        std::fs::write(path, content)?;
+        let fresh: DocumentMut = toml::to_string_pretty(&file)
+            .map_err(|e| Error::Config(e.to_string()))?
+            .parse()
+            .map_err(|e: toml_edit::TomlError| Error::Config(e.to_string()))?;
+
+        let mut doc = Self::read_document(&path)?;
+        for (key, item) in fresh.iter() {
+            let unchanged = doc
+                .get(key)
+                .is_some_and(|old| old.to_string().trim() == item.to_string().trim());
+            if !unchanged {
+                doc[key] = item.clone();
+            }
+        }
+        Self::write_document(&path, &doc)
+    }
+
+    /// 修改配置文件中的单个键（支持 default_args.phpstan 这样的点分路径），其余内容原样保留。
+    /// value 先按 TOML 值解析（数字、布尔、数组），失败时视为字符串
+    pub fn set_value(path: Option<PathBuf>, key: &str, value: &str) -> Result<()> {
+        let path = Self::user_config_path(path)?;
+        let mut doc = Self::read_document(&path)?;
+
+        let parsed = value
+            .parse::<toml_edit::Value>()
+            .unwrap_or_else(|_| toml_edit::Value::from(value));
+        let mut segments: Vec<&str> = key.split('.').collect();
+        let last = segments
+            .pop()
+            .filter(|s| !s.is_empty())
+            .ok_or_else(|| Error::Config(format!("Invalid config key: {}", key)))?;
+        let mut table = doc.as_table_mut();
+        for segment in segments {
+            let entry = table.entry(segment).or_insert(toml_edit::table());
+            table = entry
+                .as_table_mut()
+                .ok_or_else(|| Error::Config(format!("{} is not a table", segment)))?;
+        }
+        table[last] = toml_edit::value(parsed);
+
+        // 已知字段的类型需与 phpx 的期望一致，避免写出下次无法加载的配置
+        toml::from_str::<ConfigFile>(&doc.to_string())
+            .map_err(|e| Error::Config(format!("Invalid value for {}: {}", key, e)))?;
+        Self::write_document(&path, &doc)
+    }
+
+    /// 用户配置文件的实际路径：--config 指定的文件，否则为 default_config_path
+    pub fn user_config_path(path: Option<PathBuf>) -> Result<PathBuf> {
+        path.or_else(Self::default_config_path)
+            .ok_or_else(|| Error::Config("Cannot determine config directory".to_string()))
+    }
+
+    fn read_document(path: &Path) -> Result<DocumentMut> {
+        if !path.exists() {
+            return Ok(DocumentMut::new());
+        }
+        let content = std::fs::read_to_string(path)?;
+        content
+            .parse()
+            .map_err(|e| Error::Config(format!("Invalid {}: {}", path.display(), e)))
+    }
+
+    fn write_document(path: &Path, doc: &DocumentMut) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, doc.to_string())?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_names_the_offending_field() {
+        // download_mirrors 已弃用：只警告，不再校验
+        let config = Config {
+            download_mirrors: vec!["not a url".to_string()],
+            ..Config::default()
+        };
+        assert!(config.validate().is_ok());
+
+        let config = Config {
+            packagist_base: "file:///mnt/phpx-mirror/packagist".to_string(),
+            github_download_base: "file:///mnt/phpx-mirror/github".to_string(),
+            ..Config::default()
+        };
+        assert!(config.validate().is_ok());
+        let config = Config {
+            github_api_base: "file:///mnt/phpx-mirror/api".to_string(),
+            ..Config::default()
+        };
+        assert!(config.validate().unwrap_err().contains("github_api_base"));
+
+        let config = Config {
+            cache_ttl: 0,
+            ..Config::default()
+        };
+        assert!(config.validate().unwrap_err().contains("cache_ttl"));
+        assert!(Config::default().validate().is_ok());
+    }
+
+    #[test]
+    fn user_config_template_sets_no_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = Config::create_user_config(Some(dir.path().join("phpx/config.toml"))).unwrap();
+        let file: ConfigFile = toml::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert!(file.cache_dir.is_none() && file.cache_ttl.is_none());
+        assert!(file.default_args.is_none() && file.packagist_base.is_none());
+
+        std::fs::write(&path, "cache_ttl = 60\n").unwrap();
+        Config::create_user_config(Some(path.clone())).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "cache_ttl = 60\n");
+    }
+
+    #[test]
+    fn set_value_preserves_comments_and_unknown_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            "# my settings\ncache_ttl = 60 # short\nfuture_option = true\n",
+        )
+        .unwrap();
+
+        Config::set_value(Some(path.clone()), "network_timeout", "30").unwrap();
+        Config::set_value(Some(path.clone()), "default_args.phpstan", r#"["analyse"]"#).unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(content.contains("# my settings"));
+        assert!(content.contains("cache_ttl = 60 # short"));
+        assert!(content.contains("future_option = true"));
+        assert!(content.contains("network_timeout = 30"));
+
+        let config = Config::load(Some(path.clone())).unwrap();
+        assert_eq!(config.default_args["phpstan"], vec!["analyse"]);
+        assert!(Config::set_value(Some(path), "cache_ttl", "soon").is_err());
+    }
+}
@@ -0,0 +1,154 @@
+//! `--env-file`：读取 .env 格式的文件，把其中的变量注入被执行工具的环境。
+//! 支持空行、# 注释、`export` 前缀、未加引号值后的行内注释、单引号（原样）与双引号（\n、\t、\"、\\ 等转义，可跨行）；
+//! 不做 ${VAR} 展开。同名变量以后出现的为准。
+
+use crate::error::{Error, Result};
+use std::path::Path;
+
+/// 读取并解析 env 文件，返回按出现顺序排列的 (变量名, 值)
+pub fn load(path: &Path) -> Result<Vec<(String, String)>> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| Error::Config(format!("Cannot read env file {}: {}", path.display(), e)))?;
+    parse(&content)
+        .map_err(|(line, reason)| Error::Config(format!("{}:{}: {}", path.display(), line, reason)))
+}
+
+/// 解析 `--env KEY=VALUE`：值原样保留，不处理引号
+pub fn parse_assignment(raw: &str) -> std::result::Result<(String, String), String> {
+    let (key, value) = raw
+        .split_once('=')
+        .ok_or_else(|| format!("expected KEY=VALUE, got '{}'", raw))?;
+    if !is_valid_name(key) {
+        return Err(format!("invalid variable name '{}'", key));
+    }
+    Ok((key.to_string(), value.to_string()))
+}
+
+/// 出错时返回 (行号, 原因)
+fn parse(content: &str) -> std::result::Result<Vec<(String, String)>, (usize, String)> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut vars = Vec::new();
+    let mut index = 0;
+    while index < lines.len() {
+        let number = index + 1;
+        let line = lines[index].trim();
+        index += 1;
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line
+            .strip_prefix("export ")
+            .map(str::trim_start)
+            .unwrap_or(line);
+        let Some((key, raw)) = line.split_once('=') else {
+            return Err((number, "expected KEY=VALUE".to_string()));
+        };
+        let key = key.trim();
+        if !is_valid_name(key) {
+            return Err((number, format!("invalid variable name '{}'", key)));
+        }
+        let raw = raw.trim_start();
+        let value = match raw.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                // 引号内的值可以跨行：读到配对的引号为止
+                let mut text = raw[1..].to_string();
+                loop {
+                    if let Some((value, rest)) = unquote(&text, quote) {
+                        let rest = rest.trim();
+                        if !rest.is_empty() && !rest.starts_with('#') {
+                            return Err((
+                                number,
+                                format!("unexpected text after closing quote: {}", rest),
+                            ));
+                        }
+                        break value;
+                    }
+                    let Some(next) = lines.get(index) else {
+                        return Err((number, format!("unterminated {} quote", quote)));
+                    };
+                    index += 1;
+                    text.push('\n');
+                    text.push_str(next);
+                }
+            }
+            _ => {
+                // 未加引号时，前面有空白的 # 开始行内注释
+                let comment = raw
+                    .char_indices()
+                    .find(|&(i, c)| c == '#' && raw[..i].ends_with(char::is_whitespace))
+                    .map_or(raw.len(), |(i, _)| i);
+                raw[..comment].trim_end().to_string()
+            }
+        };
+        vars.push((key.to_string(), value));
+    }
+    Ok(vars)
+}
+
+/// 取出引号内的值与闭合引号之后的剩余部分；没有闭合引号时为 None。只有双引号处理转义
+fn unquote(text: &str, quote: char) -> Option<(String, &str)> {
+    let mut value = String::new();
+    let mut chars = text.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            c if c == quote => return Some((value, &text[i + 1..])),
+            '\\' if quote == '"' => match chars.next() {
+                Some((_, 'n')) => value.push('\n'),
+                Some((_, 'r')) => value.push('\r'),
+                Some((_, 't')) => value.push('\t'),
+                Some((_, other)) => value.push(other),
+                None => value.push('\\'),
+            },
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+/// 变量名：字母、数字、下划线与点，不以数字开头
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_quotes_comments_and_export() {
+        let content = r#"
+# database
+export DB_HOST=localhost
+DB_PORT = 5432 # inline comment
+DB_PASS='p#ss $word'
+GREETING="hello\n\"world\""
+URL=http://example.test/#anchor
+MULTI="line one
+line two"
+EMPTY=
+"#;
+        assert_eq!(
+            parse(content).unwrap(),
+            vec![
+                ("DB_HOST".to_string(), "localhost".to_string()),
+                ("DB_PORT".to_string(), "5432".to_string()),
+                ("DB_PASS".to_string(), "p#ss $word".to_string()),
+                ("GREETING".to_string(), "hello\n\"world\"".to_string()),
+                ("URL".to_string(), "http://example.test/#anchor".to_string()),
+                ("MULTI".to_string(), "line one\nline two".to_string()),
+                ("EMPTY".to_string(), String::new()),
+            ]
+        );
+        assert_eq!(parse("A=1\nnot an assignment\n").unwrap_err().0, 2);
+        assert_eq!(parse("A=\"open\nB=2\n").unwrap_err().0, 1);
+        assert!(parse_assignment("1BAD=x").is_err());
+        assert_eq!(
+            parse_assignment("APP_ENV=a=b").unwrap(),
+            ("APP_ENV".to_string(), "a=b".to_string())
+        );
+    }
+}
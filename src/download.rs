@@ -1,11 +1,48 @@
 use crate::error::{Error, Result};
+use crate::progress::{NoProgress, ProgressObserver};
+use crate::resolver::github_token_for;
 use reqwest::Client;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::fs::File;
 use tokio::io::AsyncWriteExt;
 
+/// 所有 HTTP 请求默认使用的 User-Agent，可由配置 user_agent 覆盖
+pub const DEFAULT_USER_AGENT: &str = concat!("phpx/", env!("CARGO_PKG_VERSION"));
+
+/// 设为 1 / true / yes 时不校验 HTTPS 证书（等同 --no-verify-tls），用于会替换证书的企业代理
+pub const NO_VERIFY_TLS_ENV: &str = "PHPX_NO_VERIFY_TLS";
+
+/// 关闭证书校验时每个请求都打印的警告
+pub(crate) fn warn_tls_unverified(url: &str) {
+    tracing::warn!(
+        "TLS certificate verification is DISABLED (--no-verify-tls / {}): {} may be intercepted or tampered with",
+        NO_VERIFY_TLS_ENV,
+        url
+    );
+}
+
+/// file:// 地址对应的本地路径（如挂载在 NFS 上的镜像）；其它地址返回 None
+pub fn file_url_path(url: &str) -> Option<PathBuf> {
+    let parsed = reqwest::Url::parse(url).ok()?;
+    if parsed.scheme() != "file" {
+        return None;
+    }
+    parsed.to_file_path().ok()
+}
+
 pub struct Downloader {
     client: Client,
+    connect_timeout: Option<Duration>,
+    timeout: Option<Duration>,
+    user_agent: String,
+    /// download_file_with_progress 的进度回调
+    progress: Arc<dyn ProgressObserver>,
+    /// 单个文件的大小上限（字节），None 为不限制
+    max_size: Option<u64>,
+    /// 接受无效的 HTTPS 证书（--no-verify-tls）
+    accept_invalid_certs: bool,
 }
 
 impl Default for Downloader {
@@ -16,9 +53,97 @@ impl Default for Downloader {
 
 impl Downloader {
     pub fn new() -> Self {
-        Self {
+        let mut downloader = Self {
             client: Client::new(),
+            connect_timeout: None,
+            timeout: None,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            progress: Arc::new(NoProgress),
+            max_size: None,
+            accept_invalid_certs: false,
+        };
+        downloader.client = downloader.build_client();
+        downloader
+    }
+
+    /// 使用独立的连接超时与总超时创建下载器
+    pub fn with_timeouts(connect_timeout: Duration, timeout: Duration) -> Self {
+        let mut downloader = Self::new();
+        downloader.connect_timeout = Some(connect_timeout);
+        downloader.timeout = Some(timeout);
+        downloader.client = downloader.build_client();
+        downloader
+    }
+
+    /// 设置请求使用的 User-Agent
+    pub fn with_user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = user_agent.into();
+        self.client = self.build_client();
+        self
+    }
+
+    /// 不校验 HTTPS 证书；只应在会替换证书的代理后使用，每次请求都会警告
+    pub fn with_accept_invalid_certs(mut self, accept: bool) -> Self {
+        self.accept_invalid_certs = accept;
+        self.client = self.build_client();
+        self
+    }
+
+    /// 是否关闭了 HTTPS 证书校验
+    pub fn accepts_invalid_certs(&self) -> bool {
+        self.accept_invalid_certs
+    }
+
+    fn build_client(&self) -> Client {
+        let mut builder = Client::builder()
+            .user_agent(&self.user_agent)
+            .danger_accept_invalid_certs(self.accept_invalid_certs);
+        if let Some(t) = self.connect_timeout {
+            builder = builder.connect_timeout(t);
         }
+        if let Some(t) = self.timeout {
+            builder = builder.timeout(t);
+        }
+        builder.build().unwrap_or_else(|_| Client::new())
+    }
+
+    /// 设置单个文件的下载大小上限
+    pub fn with_max_size(mut self, max_size: Option<u64>) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// 大小超过上限时返回 DownloadTooLarge
+    fn check_size(&self, url: &str, size: u64) -> Result<()> {
+        match self.max_size {
+            Some(limit) if size > limit => Err(Error::DownloadTooLarge {
+                url: url.to_string(),
+                limit,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// 设置下载进度回调
+    pub fn with_progress(mut self, progress: Arc<dyn ProgressObserver>) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// GET 请求；目标为 GitHub 时附带 token，release asset 的 API 地址需声明要原始文件
+    fn get(&self, url: &str) -> reqwest::RequestBuilder {
+        if self.accept_invalid_certs {
+            warn_tls_unverified(url);
+        }
+        let mut request = self.client.get(url);
+        if let Some(token) = github_token_for(url) {
+            // User-Agent（GitHub API 要求必须带）由 build_client 统一设置
+            request = request.bearer_auth(token);
+            if url.contains("/releases/assets/") {
+                request = request.header(reqwest::header::ACCEPT, "application/octet-stream");
+            }
+        }
+        request
     }
 
     pub async fn download_file(&self, url: &str, destination: &PathBuf) -> Result<()> {
@@ -29,28 +154,166 @@ impl Downloader {
             tokio::fs::create_dir_all(parent).await?;
         }
 
-        let response = self.client.get(url).send().await?;
+        if let Some(source) = file_url_path(url) {
+            self.copy_local(url, &source, destination).await?;
+            return Ok(());
+        }
+
+        let mut response = self.get(url).send().await?;
 
         if !response.status().is_success() {
             return Err(Error::Network(response.error_for_status().unwrap_err()));
         }
 
-        let content = response.bytes().await?;
+        let expected_len = response.content_length();
+        if let Some(len) = expected_len {
+            self.check_size(url, len)?;
+        }
+        let written = self
+            .stream_to_file(url, &mut response, destination, expected_len, false)
+            .await?;
 
-        let mut file = File::create(destination).await?;
-        file.write_all(&content).await?;
-        file.flush().await?;
+        Self::check_length(expected_len, written, destination).await?;
 
         tracing::info!("Download completed successfully");
         Ok(())
     }
 
+    /// 流式下载并通过进度回调报告已下载字节数
     pub async fn download_file_with_progress(
         &self,
         url: &str,
         destination: &PathBuf,
     ) -> Result<()> {
-        // TODO: 实现带进度条的下载
-        self.download_file(url, destination).await
+        tracing::info!("Downloading from {} to {:?}", url, destination);
+
+        if let Some(parent) = destination.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        if let Some(source) = file_url_path(url) {
+            let total = tokio::fs::metadata(&source).await.ok().map(|m| m.len());
+            self.progress.on_download_start(url, total);
+            let copied = self.copy_local(url, &source, destination).await;
+            if let Ok(bytes) = copied {
+                self.progress.on_download_progress(bytes, total);
+            }
+            self.progress.on_download_finish(url);
+            copied?;
+            return Ok(());
+        }
+
+        let mut response = self.get(url).send().await?;
+
+        if !response.status().is_success() {
+            return Err(Error::Network(response.error_for_status().unwrap_err()));
+        }
+
+        let expected_len = response.content_length();
+        if let Some(len) = expected_len {
+            self.check_size(url, len)?;
+        }
+        self.progress.on_download_start(url, expected_len);
+        let written = self
+            .stream_to_file(url, &mut response, destination, expected_len, true)
+            .await;
+        self.progress.on_download_finish(url);
+        let written = written?;
+
+        Self::check_length(expected_len, written, destination).await?;
+
+        tracing::info!("Download completed successfully");
+        Ok(())
+    }
+
+    /// 从本地镜像复制文件，返回复制的字节数
+    async fn copy_local(&self, url: &str, source: &Path, destination: &PathBuf) -> Result<u64> {
+        tracing::info!("Copying from local mirror {}", source.display());
+        self.check_size(url, tokio::fs::metadata(source).await?.len())?;
+        // 与网络下载相同：先删除目标，避免改写去重 blob 的硬链接
+        let _ = tokio::fs::remove_file(destination).await;
+        Ok(tokio::fs::copy(source, destination).await?)
+    }
+
+    /// 逐块写入 destination，返回写入的字节数；超过大小上限时删除已写部分并中止
+    async fn stream_to_file(
+        &self,
+        url: &str,
+        response: &mut reqwest::Response,
+        destination: &PathBuf,
+        expected_len: Option<u64>,
+        report_progress: bool,
+    ) -> Result<u64> {
+        // 先删除再创建：目标可能是去重 blob 的硬链接，直接截断会破坏共享内容
+        let _ = tokio::fs::remove_file(destination).await;
+        let mut file = File::create(destination).await?;
+        let mut written: u64 = 0;
+        while let Some(chunk) = response.chunk().await? {
+            written += chunk.len() as u64;
+            if let Err(e) = self.check_size(url, written) {
+                drop(file);
+                let _ = tokio::fs::remove_file(destination).await;
+                return Err(e);
+            }
+            file.write_all(&chunk).await?;
+            if report_progress {
+                self.progress.on_download_progress(written, expected_len);
+            }
+        }
+        file.flush().await?;
+        Ok(written)
+    }
+
+    /// 校验实际写入字节数与 Content-Length 一致；不一致时删除残缺文件，下次运行会重新下载
+    async fn check_length(expected: Option<u64>, actual: u64, destination: &PathBuf) -> Result<()> {
+        match expected {
+            Some(expected) if expected != actual => {
+                let _ = tokio::fs::remove_file(destination).await;
+                Err(Error::IncompleteDownload { expected, actual })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn file_url_is_copied_without_network() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("mirror").join("tool.phar");
+        std::fs::create_dir_all(source.parent().unwrap()).unwrap();
+        std::fs::write(&source, b"phar bytes").unwrap();
+        let url = reqwest::Url::from_file_path(&source).unwrap().to_string();
+
+        let destination = dir.path().join("cache").join("tool-1.0.0.phar");
+        Downloader::new()
+            .download_file_with_progress(&url, &destination)
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(&destination).unwrap(), b"phar bytes");
+        assert!(file_url_path("https://example.com/tool.phar").is_none());
+    }
+
+    #[tokio::test]
+    async fn oversized_download_is_rejected_and_not_left_behind() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/big.phar")
+            .with_body(vec![0u8; 2048])
+            .create_async()
+            .await;
+        let dir = tempfile::tempdir().unwrap();
+        let destination = dir.path().join("big.phar");
+
+        let err = Downloader::new()
+            .with_max_size(Some(1024))
+            .download_file(&format!("{}/big.phar", server.url()), &destination)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::DownloadTooLarge { limit: 1024, .. }));
+        assert!(!destination.exists());
     }
 }
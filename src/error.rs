@@ -8,11 +8,19 @@ pub enum Error {
     #[error("Network error: {0}")]
     Network(#[from] reqwest::Error),
 
+    #[error("Incomplete download: expected {expected} bytes, got {actual}")]
+    IncompleteDownload { expected: u64, actual: u64 },
+
+    /// 下载大小超过 max_download_size（按 Content-Length 预先拒绝或在下载中途中止）
+    #[error("Download of {url} exceeds the maximum size of {limit} bytes (max_download_size)")]
+    DownloadTooLarge { url: String, limit: u64 },
+
     #[error("Configuration error: {0}")]
     Config(String),
 
-    #[error("Tool not found: {0}")]
-    ToolNotFound(String),
+    /// 各来源都没能解析出工具；附带失败原因与可能的正确名称
+    #[error("{0}")]
+    ToolNotFound(ResolutionFailure),
 
     #[error("Version constraint error: {0}")]
     VersionConstraint(String),
@@ -23,12 +31,38 @@ pub enum Error {
     #[error("Cache error: {0}")]
     Cache(String),
 
+    /// 查询的工具没有任何缓存条目（cache info 等命令据此返回非零退出码）
+    #[error("No cache entries found for tool: {0}")]
+    NotCached(String),
+
     #[error("Execution error: {0}")]
     Execution(String),
 
-    /// 工具进程以非零退出码结束（如 lint 报错），仅用于传播退出码，无需再打印错误文案
-    #[error("Tool exited with code {0}")]
-    ExecutionFailed(i32),
+    /// 工具因运行环境问题（缺少扩展、PHP 版本过低等）在启动时即失败
+    #[error("Tool failed to start (exit code {exit_code}): {reason}\n{hint}")]
+    PhpStartupFailed {
+        exit_code: i32,
+        reason: String,
+        hint: String,
+    },
+
+    /// 找到的 PHP 低于 min_php_version
+    #[error("PHP {actual} ({php}) is too old to run PHP tools; phpx needs PHP {minimum} or newer. Use --php to select another PHP.")]
+    PhpTooOld {
+        actual: String,
+        minimum: String,
+        php: String,
+    },
+
+    /// 缓存的 Composer 安装不满足当前 PHP；需用户确认或 --reinstall 后才重新安装
+    #[error(
+        "Cached {tool}@{version} {reason}. Pass --reinstall to re-install it for the current PHP."
+    )]
+    StaleComposerInstall {
+        tool: String,
+        version: String,
+        reason: String,
+    },
 
     #[error("Invalid tool identifier: {0}")]
     InvalidToolIdentifier(String),
@@ -38,9 +72,26 @@ pub enum Error {
     )]
     ComposerNotFound,
 
+    #[error("Archive error: {0}")]
+    Archive(String),
+
+    #[error("{package} has no bin named '{bin}'. Available bins: {}", available.join(", "))]
+    UnknownBin {
+        package: String,
+        bin: String,
+        available: Vec<String>,
+    },
+
     #[error("Composer install failed: {0}")]
     ComposerInstallFailed(String),
 
+    /// composer install 因平台要求失败；requirements 为 Composer 输出中对应的行
+    #[error("Composer could not install {package} because of platform requirements:\n  {}\nUse --php to select another PHP, or --ignore-platform-reqs to install anyway.", requirements.join("\n  "))]
+    ComposerPlatformReqs {
+        package: String,
+        requirements: Vec<String>,
+    },
+
     #[error("Unsupported platform: {0}")]
     UnsupportedPlatform(String),
 
@@ -49,3 +100,66 @@ pub enum Error {
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
+
+/// 工具解析失败的详情：reasons 为解析过程中收集到的可操作原因（如包存在但没有 bin、GitHub 限流），
+/// suggestions 为 Packagist 搜索得到的相近包名
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResolutionFailure {
+    pub name: String,
+    pub reasons: Vec<String>,
+    pub suggestions: Vec<String>,
+    /// 有来源因网络错误、HTTP 错误或限流而失败：此时工具未必真的不存在
+    pub transient: bool,
+}
+
+impl ResolutionFailure {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ..Self::default()
+        }
+    }
+
+    /// 记录一条原因，忽略重复项
+    pub fn add_reason(&mut self, reason: impl Into<String>) {
+        let reason = reason.into();
+        if !self.reasons.contains(&reason) {
+            self.reasons.push(reason);
+        }
+    }
+
+    /// 合并某个来源的错误：ToolNotFound 取其原因，其它错误以 "来源: 错误" 记录
+    pub fn absorb(&mut self, source: &str, error: Error) {
+        match error {
+            Error::ToolNotFound(failure) => {
+                self.transient |= failure.transient;
+                for reason in failure.reasons {
+                    self.add_reason(reason);
+                }
+            }
+            other => {
+                self.transient |= matches!(other, Error::Network(_));
+                self.add_reason(format!("{}: {}", source, other));
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for ResolutionFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "No tool '{}' found.", self.name)?;
+        for reason in &self.reasons {
+            write!(f, "\n  - {}", reason)?;
+        }
+        if !self.suggestions.is_empty() {
+            let separator = if self.reasons.is_empty() { " " } else { "\n" };
+            write!(
+                f,
+                "{}Did you mean: {}?",
+                separator,
+                self.suggestions.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
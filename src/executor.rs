@@ -1,8 +1,20 @@
 use crate::error::{Error, Result};
 use semver::VersionReq;
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 工具在此时间内以非零码退出时视为可能的启动失败，进一步诊断原因
+const STARTUP_FAILURE_WINDOW: Duration = Duration::from_secs(3);
+
+/// PHP 因致命错误（含解析错误、启动失败）退出时的退出码
+const PHP_FATAL_EXIT_CODE: i32 = 255;
+
+/// 诊断启动失败时保留的 stderr 末尾字节数
+const STDERR_TAIL_BYTES: usize = 64 * 1024;
 
 /// composer.json 中与 PHP 版本相关的字段（仅解析所需部分）
 #[derive(Deserialize)]
@@ -31,7 +43,83 @@ struct ComposerPlatform {
     php_version: Option<String>,
 }
 
-pub struct Executor;
+/// 向上查找 composer.json 时默认最多检查的目录层数（含当前目录），避免在很深的目录中一路走到文件系统根
+pub const DEFAULT_PROJECT_SEARCH_DEPTH: usize = 10;
+
+/// 默认的最低 PHP 版本：更旧的 PHP 运行现代 phar 时通常只会报出难以理解的语法错误
+pub const DEFAULT_MIN_PHP_VERSION: &str = "7.4";
+
+/// Composer 在 vendor/bin 中生成的入口对应的 PHP 脚本：
+/// Windows 的 .bat/.cmd 代理只是 `php "%BIN_TARGET%"`，取出 BIN_TARGET 指向的文件；
+/// 符号链接解析到包内真正的 bin。Composer 2.2+ 的 PHP 代理文件可以直接交给 php，保持不变
+pub fn resolve_bin_script(script_path: &Path) -> Result<PathBuf> {
+    let is_batch = script_path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("bat") || ext.eq_ignore_ascii_case("cmd"));
+    if is_batch {
+        let content = std::fs::read_to_string(script_path)?;
+        let bin_dir = script_path.parent().unwrap_or(Path::new("."));
+        return batch_bin_target(&content, bin_dir).ok_or_else(|| {
+            Error::Execution(format!(
+                "{} is a Windows batch file that does not point at a PHP script (no BIN_TARGET)",
+                script_path.display()
+            ))
+        });
+    }
+    let is_symlink =
+        std::fs::symlink_metadata(script_path).is_ok_and(|m| m.file_type().is_symlink());
+    if is_symlink {
+        return Ok(std::fs::canonicalize(script_path)?);
+    }
+    Ok(script_path.to_path_buf())
+}
+
+/// 从 Composer 的 .bat 代理中取出 `SET BIN_TARGET=%~dp0/<相对路径>`，相对于 bin_dir 解析
+fn batch_bin_target(content: &str, bin_dir: &Path) -> Option<PathBuf> {
+    let value = content.lines().find_map(|line| {
+        let line = line.trim();
+        let (set, assignment) = line.split_at_checked(4)?;
+        if !set.eq_ignore_ascii_case("SET ") {
+            return None;
+        }
+        let (name, value) = assignment.split_once('=')?;
+        name.trim()
+            .eq_ignore_ascii_case("BIN_TARGET")
+            .then(|| value.trim().trim_matches('"'))
+    })?;
+    let relative = value.strip_prefix("%~dp0")?;
+    let target = relative
+        .split(['/', '\\'])
+        .filter(|part| !part.is_empty() && *part != ".")
+        .fold(bin_dir.to_path_buf(), |path, part| path.join(part));
+    Some(target)
+}
+
+/// 经 vendor/bin 代理启动真实脚本时，与 Composer 的 bin 代理一致设置 COMPOSER_RUNTIME_BIN_DIR，
+/// 让工具知道自己是从哪个 vendor/bin 启动的
+fn set_runtime_bin_dir(command: &mut Command, script_path: &Path, target: &Path) {
+    if target != script_path {
+        if let Some(bin_dir) = script_path.parent() {
+            command.env("COMPOSER_RUNTIME_BIN_DIR", bin_dir);
+        }
+    }
+}
+
+pub struct Executor {
+    /// 不读取项目 composer.json 的 PHP 约束（不做版本比对、不给出相关提示）
+    ignore_project_php: bool,
+    /// 向上查找 composer.json 最多检查的目录层数
+    search_depth: usize,
+    /// 没有项目 PHP 约束时要求的最低 PHP 版本；None 不检查
+    min_php_version: Option<String>,
+    /// PHP 低于 min_php_version 时报错而不是警告
+    strict_php_version: bool,
+    /// 注入被执行工具环境的变量（--env-file、--env），覆盖继承的同名变量
+    env: Vec<(String, String)>,
+    /// 已探测过的 PHP 版本，按可执行文件路径缓存，同一次运行中不重复执行 php
+    php_versions: Mutex<HashMap<PathBuf, Option<String>>>,
+}
 
 impl Default for Executor {
     fn default() -> Self {
@@ -41,7 +129,53 @@ impl Default for Executor {
 
 impl Executor {
     pub fn new() -> Self {
-        Self
+        Self {
+            ignore_project_php: false,
+            search_depth: DEFAULT_PROJECT_SEARCH_DEPTH,
+            min_php_version: Some(DEFAULT_MIN_PHP_VERSION.to_string()),
+            strict_php_version: false,
+            env: Vec::new(),
+            php_versions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 设置最低 PHP 版本（空字符串关闭检查）及低于它时是否报错
+    pub fn with_min_php_version(mut self, minimum: &str, strict: bool) -> Self {
+        let minimum = minimum.trim();
+        self.min_php_version = (!minimum.is_empty()).then(|| minimum.to_string());
+        self.strict_php_version = strict;
+        self
+    }
+
+    pub fn set_strict_php_version(&mut self, strict: bool) {
+        self.strict_php_version = strict;
+    }
+
+    /// 设置注入工具环境的变量；同名变量以靠后的为准
+    pub fn set_env(&mut self, env: Vec<(String, String)>) {
+        self.env = env;
+    }
+
+    fn extra_env(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.env
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+
+    /// 设置向上查找 composer.json 的最大层数
+    pub fn with_search_depth(mut self, depth: usize) -> Self {
+        self.search_depth = depth;
+        self
+    }
+
+    /// 关闭项目 composer.json 的 PHP 版本检测（monorepo 中可能读到无关的约束）
+    pub fn with_ignore_project_php(mut self, ignore: bool) -> Self {
+        self.ignore_project_php = ignore;
+        self
+    }
+
+    pub fn set_ignore_project_php(&mut self, ignore: bool) {
+        self.ignore_project_php = ignore;
     }
 
     pub fn execute_phar(
@@ -49,23 +183,11 @@ impl Executor {
         phar_path: &PathBuf,
         args: &[String],
         php_path: Option<&PathBuf>,
-    ) -> Result<()> {
+        php_args: &[String],
+    ) -> Result<i32> {
         let php_binary = self.find_php_binary(php_path)?;
 
-        // 若项目有 composer.json 的 PHP 约束且未指定 --php，校验当前 PHP 是否满足并打日志
-        if php_path.is_none() {
-            if let Some(constraint) = self.detect_project_php_version() {
-                if let Some(actual) = Self::get_php_version(&php_binary) {
-                    if !Self::php_version_matches_constraint(&actual, &constraint) {
-                        tracing::warn!(
-                            "Project composer.json requires PHP {}, but current PHP is {}",
-                            constraint,
-                            actual
-                        );
-                    }
-                }
-            }
-        }
+        self.check_php_version(&php_binary, php_path)?;
 
         tracing::info!(
             "Executing {} with PHP: {:?}",
@@ -73,99 +195,262 @@ impl Executor {
             php_binary
         );
 
+        // 解释器参数（如 -d memory_limit=-1）位于 php 与 phar 之间
         let mut command = Command::new(&php_binary);
+        command.args(php_args);
         command.arg(phar_path);
         command.args(args);
 
-        // 继承当前环境变量
+        // 继承当前环境变量，--env-file / --env 的变量覆盖同名变量
         command.envs(std::env::vars());
+        command.envs(self.extra_env());
 
         // 设置标准输入/输出
         command.stdin(Stdio::inherit());
         command.stdout(Stdio::inherit());
-        command.stderr(Stdio::inherit());
 
-        let status = command.status()?;
-
-        if status.success() {
-            Ok(())
-        } else {
-            let code = status.code().unwrap_or(1);
-            Err(Error::ExecutionFailed(code))
-        }
+        self.run_and_diagnose(command, &php_binary, php_args)
     }
 
-    /// 执行 PHP 脚本（如 vendor/bin/rector），与 execute_phar 共用 PHP 选择与环境
+    /// 执行 PHP 脚本（如 vendor/bin/rector），与 execute_phar 共用 PHP 选择、解释器参数与环境
     pub fn execute_script(
         &self,
         script_path: &Path,
         args: &[String],
         php_path: Option<&PathBuf>,
-    ) -> Result<()> {
+        php_args: &[String],
+    ) -> Result<i32> {
         let php_binary = self.find_php_binary(php_path)?;
 
-        if php_path.is_none() {
-            if let Some(constraint) = self.detect_project_php_version() {
-                if let Some(actual) = Self::get_php_version(&php_binary) {
-                    if !Self::php_version_matches_constraint(&actual, &constraint) {
-                        tracing::warn!(
-                            "Project composer.json requires PHP {}, but current PHP is {}",
-                            constraint,
-                            actual
-                        );
-                    }
-                }
-            }
-        }
+        self.check_php_version(&php_binary, php_path)?;
 
-        tracing::info!(
-            "Executing {} with PHP: {:?}",
-            script_path.display(),
-            php_binary
-        );
+        let target = resolve_bin_script(script_path)?;
+        tracing::info!("Executing {} with PHP: {:?}", target.display(), php_binary);
 
         let mut command = Command::new(&php_binary);
-        command.arg(script_path);
+        command.args(php_args);
+        command.arg(&target);
         command.args(args);
 
         command.envs(std::env::vars());
+        command.envs(self.extra_env());
+        set_runtime_bin_dir(&mut command, script_path, &target);
         command.stdin(Stdio::inherit());
         command.stdout(Stdio::inherit());
-        command.stderr(Stdio::inherit());
 
-        let status = command.status()?;
+        self.run_and_diagnose(command, &php_binary, php_args)
+    }
 
-        if status.success() {
-            Ok(())
-        } else {
-            let code = status.code().unwrap_or(1);
-            Err(Error::ExecutionFailed(code))
-        }
+    /// 以与 execute_script 相同的 PHP 选择运行 phar 或脚本，捕获输出而不是交给终端；
+    /// 返回 (退出码, stdout + stderr)
+    pub fn capture_output(
+        &self,
+        script_path: &Path,
+        args: &[String],
+        php_path: Option<&PathBuf>,
+        php_args: &[String],
+    ) -> Result<(i32, String)> {
+        let php_binary = self.find_php_binary(php_path)?;
+        self.check_php_version(&php_binary, php_path)?;
+        let target = resolve_bin_script(script_path)?;
+        let mut command = Command::new(&php_binary);
+        command
+            .args(php_args)
+            .arg(&target)
+            .args(args)
+            .envs(self.extra_env());
+        set_runtime_bin_dir(&mut command, script_path, &target);
+        let output = command.stdin(Stdio::null()).output()?;
+        Ok((
+            output.status.code().unwrap_or(1),
+            format!(
+                "{}{}",
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
+            ),
+        ))
     }
 
-    fn find_php_binary(&self, custom_path: Option<&PathBuf>) -> Result<PathBuf> {
-        if let Some(path) = custom_path {
-            if path.exists() {
-                return Ok(path.clone());
-            } else {
-                return Err(Error::Execution(format!(
-                    "Custom PHP path does not exist: {}",
-                    path.display()
-                )));
+    /// 从工具的 --version 输出中取出版本号：第一个以数字开头且含 '.' 的词，
+    /// 去掉前缀 v 与 Psalm 等附带的 @提交号（如 "PHPStan - PHP Static Analysis Tool 1.11.2" → 1.11.2）
+    pub fn reported_version(output: &str) -> Option<String> {
+        output
+            .split_whitespace()
+            .map(|word| word.trim_matches(|c: char| matches!(c, '(' | ')' | ',' | ';' | '"')))
+            .map(|word| word.strip_prefix(['v', 'V']).unwrap_or(word))
+            .map(|word| word.split('@').next().unwrap_or(word))
+            .find(|word| {
+                word.starts_with(|c: char| c.is_ascii_digit())
+                    && word.contains('.')
+                    && word
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '+'))
+            })
+            .map(str::to_string)
+    }
+
+    /// 运行前检查 PHP 版本：未指定 --php 且项目 composer.json 有 PHP 约束时按约束校验并告警；
+    /// 否则与 min_php_version 比较，过旧时告警（strict 时报错），避免工具报出费解的语法错误
+    fn check_php_version(&self, php_binary: &Path, php_path: Option<&PathBuf>) -> Result<()> {
+        let project_constraint = php_path
+            .is_none()
+            .then(|| self.detect_project_php_version())
+            .flatten();
+        let actual = self.php_version(php_binary);
+        if let Some(constraint) = project_constraint {
+            if let Some(actual) = actual {
+                if !Self::php_version_matches_constraint(&actual, &constraint) {
+                    tracing::warn!(
+                        "Project composer.json requires PHP {}, but current PHP is {}",
+                        constraint,
+                        actual
+                    );
+                }
             }
+            return Ok(());
+        }
+
+        let (Some(actual), Some(minimum)) = (actual, &self.min_php_version) else {
+            return Ok(());
+        };
+        tracing::debug!("Using PHP {} ({})", actual, php_binary.display());
+        if Self::php_version_matches_constraint(&actual, &format!(">={}", minimum)) {
+            return Ok(());
+        }
+        let too_old = Error::PhpTooOld {
+            actual,
+            minimum: minimum.clone(),
+            php: php_binary.display().to_string(),
+        };
+        if self.strict_php_version {
+            return Err(too_old);
+        }
+        tracing::warn!("{}", too_old);
+        Ok(())
+    }
+
+    /// 运行工具并返回退出码。非零退出码（如 lint 报错）属于工具自身结果，原样返回；
+    /// 工具启动后立即以 PHP 致命错误码（255）退出、且有 PHP 启动错误或缺少扩展的迹象时，
+    /// 识别为缺少扩展、PHP 版本过低等启动失败。
+    /// stderr 是终端时直接继承（保留颜色、进度条与交互），失败后另行探测 PHP 本身能否正常启动；
+    /// 否则一边转发一边保留末尾部分用于诊断
+    fn run_and_diagnose(
+        &self,
+        mut command: Command,
+        php_binary: &Path,
+        php_args: &[String],
+    ) -> Result<i32> {
+        use std::io::IsTerminal;
+
+        let started = Instant::now();
+        let stderr_is_terminal = std::io::stderr().is_terminal();
+        if stderr_is_terminal {
+            command.stderr(Stdio::inherit());
+        } else {
+            command.stderr(Stdio::piped());
         }
+        let mut child = command.spawn()?;
+        let tee = child.stderr.take().map(|mut pipe| {
+            std::thread::spawn(move || {
+                use std::io::{Read, Write};
+
+                let mut tail = Vec::new();
+                let mut buffer = [0u8; 8192];
+                let mut stderr = std::io::stderr();
+                while let Ok(read) = pipe.read(&mut buffer) {
+                    if read == 0 {
+                        break;
+                    }
+                    let _ = stderr.write_all(&buffer[..read]);
+                    tail.extend_from_slice(&buffer[..read]);
+                    if tail.len() > STDERR_TAIL_BYTES {
+                        tail.drain(..tail.len() - STDERR_TAIL_BYTES);
+                    }
+                }
+                tail
+            })
+        });
+        let status = child.wait()?;
+        let stderr = tee
+            .and_then(|handle| handle.join().ok())
+            .unwrap_or_default();
+        let exit_code = status.code().unwrap_or(1);
+        if exit_code != PHP_FATAL_EXIT_CODE || started.elapsed() > STARTUP_FAILURE_WINDOW {
+            return Ok(exit_code);
+        }
+
+        let stderr = if stderr_is_terminal {
+            Self::probe_php_startup(php_binary, php_args)
+        } else {
+            String::from_utf8_lossy(&stderr).into_owned()
+        };
+        let Some(reason) = Self::startup_failure_reason(&stderr) else {
+            return Ok(exit_code);
+        };
 
-        // 查找系统 PHP
-        let possible_paths = [
-            PathBuf::from("php"),
-            PathBuf::from("/usr/bin/php"),
-            PathBuf::from("/usr/local/bin/php"),
+        let actual = self
+            .php_version(php_binary)
+            .unwrap_or_else(|| "unknown".to_string());
+        let hint = match self.detect_project_php_version() {
+            Some(constraint) => format!(
+                "Current PHP is {} ({}); project composer.json requires PHP {}. Use --php to select another PHP.",
+                actual,
+                php_binary.display(),
+                constraint
+            ),
+            None => format!(
+                "Current PHP is {} ({}). Use --php to select another PHP.",
+                actual,
+                php_binary.display()
+            ),
+        };
+        Err(Error::PhpStartupFailed {
+            exit_code,
+            reason,
+            hint,
+        })
+    }
+
+    /// 以相同的解释器参数启动一次不执行任何代码的 PHP，返回其 stderr；用于 stderr 直接交给终端时的诊断
+    fn probe_php_startup(php_binary: &Path, php_args: &[String]) -> String {
+        Command::new(php_binary)
+            .args(php_args)
+            .args(["-r", ""])
+            .stdin(Stdio::null())
+            .output()
+            .map(|out| String::from_utf8_lossy(&out.stderr).into_owned())
+            .unwrap_or_default()
+    }
+
+    /// 从 stderr 中识别启动失败：PHP 自身的启动错误（扩展加载失败、旧版 PHP 无法解析工具代码）、
+    /// Composer 的平台检查（缺少扩展或 PHP 版本不满足），以及未启用扩展导致的未定义函数，返回对应的那一行；
+    /// 工具自己输出的错误（如 lint 结果）不匹配
+    pub fn startup_failure_reason(output: &str) -> Option<String> {
+        const PATTERNS: &[&str] = &[
+            "PHP Startup:",
+            "Unable to load dynamic library",
+            "PHP Parse error:",
+            "Composer detected issues in your platform",
+            "Call to undefined function",
         ];
+        output
+            .lines()
+            .map(str::trim)
+            .find(|line| PATTERNS.iter().any(|p| line.contains(p)))
+            .map(str::to_string)
+    }
 
-        for path in possible_paths {
-            if Command::new(&path).arg("--version").output().is_ok() {
-                return Ok(path);
-            }
+    pub(crate) fn find_php_binary(&self, custom_path: Option<&PathBuf>) -> Result<PathBuf> {
+        if let Some(path) = custom_path {
+            return Self::resolve_php_path(path);
+        }
+
+        // 项目 .php-version（phpenv/asdf）指定的版本优先，其次为系统 PHP
+        if let Some(path) = Self::php_from_version_file() {
+            return Ok(path);
+        }
+        if let Some(path) = Self::discover_php() {
+            return Ok(path);
         }
 
         Err(Error::Execution(
@@ -173,9 +458,185 @@ impl Executor {
         ))
     }
 
+    /// 解析 --php 的值：磁盘上存在的路径原样使用；否则 8.2、php8.2 这类版本标签
+    /// 在已探测到的 PHP 中选出版本匹配的一个
+    pub fn resolve_php_path(path: &Path) -> Result<PathBuf> {
+        if path.exists() {
+            return Ok(path.to_path_buf());
+        }
+        // Windows 下允许省略 .exe（如 --php C:\php\php）
+        let with_exe = path.with_extension("exe");
+        if cfg!(windows) && path.extension().is_none() && with_exe.exists() {
+            return Ok(with_exe);
+        }
+        let Some(wanted) = path.to_str().and_then(Self::php_version_label) else {
+            return Err(Error::Execution(format!(
+                "Custom PHP path does not exist: {}",
+                path.display()
+            )));
+        };
+        Self::php_matching_version(wanted).ok_or_else(|| {
+            Error::Execution(format!(
+                "No PHP {} found among the discovered PHP binaries; pass a full path with --php",
+                wanted
+            ))
+        })
+    }
+
+    /// 识别 8.2、8.2.1、php8.2、php-8.2 这类版本标签，返回其中的版本号
+    fn php_version_label(value: &str) -> Option<&str> {
+        let version = value
+            .strip_prefix("php")
+            .map(|v| v.trim_start_matches('-'))
+            .unwrap_or(value);
+        let parts: Vec<&str> = version.split('.').collect();
+        let valid = parts.len() <= 3
+            && parts
+                .iter()
+                .all(|p| !p.is_empty() && p.bytes().all(|b| b.is_ascii_digit()));
+        valid.then_some(version)
+    }
+
+    /// 在 phpenv/asdf、php8.2 这类命名及常规位置中找出版本匹配 wanted 的 PHP
+    fn php_matching_version(wanted: &str) -> Option<PathBuf> {
+        Self::versioned_php_candidates(wanted)
+            .into_iter()
+            .chain(Self::php_candidates())
+            .find(|path| {
+                Self::get_php_version(path).is_some_and(|v| Self::version_has_prefix(&v, wanted))
+            })
+    }
+
+    /// 按平台常见位置探测可运行的 PHP，返回第一个能执行 `--version` 的路径
+    pub fn discover_php() -> Option<PathBuf> {
+        Self::php_candidates()
+            .into_iter()
+            .find(|path| Command::new(path).arg("--version").output().is_ok())
+    }
+
+    /// 从当前目录向上查找 .php-version，在已探测到的 PHP 中选出版本匹配的一个
+    pub fn php_from_version_file() -> Option<PathBuf> {
+        let version_file = Self::find_upwards(".php-version")?;
+        let content = std::fs::read_to_string(&version_file).ok()?;
+        let wanted = content
+            .lines()
+            .map(str::trim)
+            .find(|l| !l.is_empty() && !l.starts_with('#'))?
+            .trim_start_matches("php-");
+        // phpenv 的 "system" 表示使用系统 PHP，交给常规探测
+        if wanted == "system" {
+            return None;
+        }
+
+        let found = Self::php_matching_version(wanted);
+        if found.is_none() {
+            tracing::warn!(
+                "{} requests PHP {}, but no matching PHP binary was found",
+                version_file.display(),
+                wanted
+            );
+        }
+        found
+    }
+
+    /// phpenv/asdf 安装目录及 php8.2 这类带版本号的可执行文件名
+    fn versioned_php_candidates(wanted: &str) -> Vec<PathBuf> {
+        let mut candidates = Vec::new();
+        if let Some(home) = dirs::home_dir() {
+            for base in [
+                home.join(".phpenv").join("versions"),
+                home.join(".asdf").join("installs").join("php"),
+            ] {
+                if let Ok(entries) = std::fs::read_dir(&base) {
+                    let mut dirs: Vec<PathBuf> = entries
+                        .filter_map(|e| e.ok())
+                        .map(|e| e.path())
+                        .filter(|p| {
+                            p.file_name()
+                                .and_then(|n| n.to_str())
+                                .is_some_and(|n| n.starts_with(wanted))
+                        })
+                        .collect();
+                    // 目录名即版本号，倒序使较新补丁版本优先
+                    dirs.sort();
+                    dirs.reverse();
+                    candidates.extend(dirs.into_iter().map(|d| d.join("bin").join("php")));
+                }
+            }
+        }
+        // Debian/Ubuntu 及 Homebrew 多版本并存时的命名（php8.2）
+        let minor: Vec<&str> = wanted.split('.').take(2).collect();
+        if minor.len() == 2 {
+            candidates.push(PathBuf::from(format!("php{}", minor.join("."))));
+        }
+        candidates
+    }
+
+    /// 判断实际版本是否落在 .php-version 指定的版本内（8.2 匹配 8.2.x，8.2.1 仅匹配 8.2.1）
+    fn version_has_prefix(actual: &str, wanted: &str) -> bool {
+        let actual: Vec<&str> = actual.split(['.', '-']).collect();
+        wanted
+            .split('.')
+            .enumerate()
+            .all(|(i, part)| actual.get(i) == Some(&part))
+    }
+
+    /// PHP 候选路径：PATH 中的 php 优先，其次为各平台的常见安装位置
+    fn php_candidates() -> Vec<PathBuf> {
+        if !cfg!(windows) {
+            return vec![
+                PathBuf::from("php"),
+                PathBuf::from("/usr/bin/php"),
+                PathBuf::from("/usr/local/bin/php"),
+            ];
+        }
+
+        let mut candidates = vec![PathBuf::from("php.exe")];
+        // where 可能返回多个结果，逐个加入
+        if let Ok(out) = Command::new("where").arg("php").output() {
+            if out.status.success() {
+                let s = String::from_utf8_lossy(&out.stdout);
+                candidates.extend(
+                    s.lines()
+                        .map(str::trim)
+                        .filter(|l| !l.is_empty())
+                        .map(PathBuf::from),
+                );
+            }
+        }
+        candidates.push(PathBuf::from(r"C:\php\php.exe"));
+        candidates.push(PathBuf::from(r"C:\xampp\php\php.exe"));
+        // Laragon / Chocolatey 按版本分目录安装（如 C:\laragon\bin\php\php-8.2.0-Win32\php.exe）
+        for base in [r"C:\laragon\bin\php", r"C:\tools"] {
+            if let Ok(entries) = std::fs::read_dir(base) {
+                let mut dirs: Vec<PathBuf> = entries
+                    .filter_map(|e| e.ok())
+                    .map(|e| e.path())
+                    .filter(|p| {
+                        p.file_name()
+                            .and_then(|n| n.to_str())
+                            .is_some_and(|n| n.to_ascii_lowercase().starts_with("php"))
+                    })
+                    .collect();
+                // 目录名含版本号，倒序使较新版本优先
+                dirs.sort();
+                dirs.reverse();
+                candidates.extend(dirs.into_iter().map(|d| d.join("php.exe")));
+            }
+        }
+        candidates
+            .into_iter()
+            .filter(|p| p.components().count() == 1 || p.exists())
+            .collect()
+    }
+
     /// 从当前目录向上查找 composer.json，解析 require.php 或 config.platform.php，返回 PHP 版本约束字符串
     pub fn detect_project_php_version(&self) -> Option<String> {
-        let composer_path = Self::find_composer_json()?;
+        if self.ignore_project_php {
+            return None;
+        }
+        let cwd = std::env::current_dir().ok()?;
+        let composer_path = Self::find_composer_json(&cwd, self.search_depth)?;
         let content = std::fs::read_to_string(&composer_path).ok()?;
         let composer: ComposerJson = serde_json::from_str(&content).ok()?;
         composer
@@ -186,7 +647,26 @@ impl Executor {
             .filter(|s| !s.is_empty())
     }
 
-    /// 获取指定 PHP 可执行文件的版本号（如 "8.2.1"）；若有后缀如 -ubuntu 则只取主版本段
+    /// 读取某个 composer.json（如已安装包的 vendor/<package>/composer.json）中的 require.php
+    pub fn php_constraint_of(composer_json: &Path) -> Option<String> {
+        let content = std::fs::read_to_string(composer_json).ok()?;
+        let composer: ComposerJson = serde_json::from_str(&content).ok()?;
+        composer.require.php_constraint.filter(|s| !s.is_empty())
+    }
+
+    /// 同 get_php_version，但每个 PHP 可执行文件只探测一次
+    pub fn php_version(&self, php_binary: &Path) -> Option<String> {
+        let mut versions = self
+            .php_versions
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        versions
+            .entry(php_binary.to_path_buf())
+            .or_insert_with(|| Self::get_php_version(php_binary))
+            .clone()
+    }
+
+    /// 获取指定 PHP 可执行文件的版本号（如 "8.2.1"、"8.3.0-rc1"），见 normalize_php_version
     pub fn get_php_version(php_binary: &Path) -> Option<String> {
         let out = Command::new(php_binary)
             .arg("-r")
@@ -197,19 +677,41 @@ impl Executor {
             return None;
         }
         let v = String::from_utf8_lossy(&out.stdout);
-        let v = v.trim();
-        if v.is_empty() {
-            return None;
-        }
-        // 只保留主版本号段（如 8.2.1），去掉 -ubuntu、-dev 等后缀以便 semver 解析
-        let core: String = v
-            .chars()
-            .take_while(|c| c.is_ascii_digit() || *c == '.')
-            .collect();
+        Self::normalize_php_version(&v)
+    }
+
+    /// 将 PHP_VERSION 规范化为 semver：不足三段补 0（8.2 -> 8.2.0），
+    /// 保留 RC/alpha/beta/dev 预发布标记（8.3.0RC1 -> 8.3.0-rc1），丢弃 -ubuntu 等发行版后缀
+    pub fn normalize_php_version(raw: &str) -> Option<String> {
+        let v = raw.trim();
+        let core_len = v
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(v.len());
+        let core = v[..core_len].trim_end_matches('.');
         if core.is_empty() {
             return None;
         }
-        Some(core)
+        let mut parts: Vec<&str> = core.split('.').take(3).collect();
+        while parts.len() < 3 {
+            parts.push("0");
+        }
+        let mut version = parts.join(".");
+
+        let suffix = v[core_len..]
+            .trim_start_matches(['-', '.'])
+            .to_ascii_lowercase();
+        let pre_len = suffix
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(suffix.len());
+        let pre = &suffix[..pre_len];
+        if ["rc", "alpha", "beta", "dev"]
+            .iter()
+            .any(|tag| pre.starts_with(tag))
+        {
+            version.push('-');
+            version.push_str(pre);
+        }
+        Some(version)
     }
 
     /// 检查 PHP 版本是否满足 composer 约束（require.php 或 config.platform.php）
@@ -218,26 +720,48 @@ impl Executor {
         if constraint.is_empty() {
             return true;
         }
+        let Some(actual) =
+            Self::normalize_php_version(version).and_then(|v| semver::Version::parse(&v).ok())
+        else {
+            return false;
+        };
+        // PHP 预发布版（如 8.3.0RC1）按对应正式版判断，避免 >=8.2 之类约束误报
+        let mut release = actual.clone();
+        release.pre = semver::Prerelease::EMPTY;
+
         // 尝试解析为版本约束（^8.2.0, >=7.4 等）
         if let Ok(req) = VersionReq::parse(constraint) {
-            if let Ok(v) = semver::Version::parse(version) {
-                return req.matches(&v);
-            }
+            return req.matches(&actual) || req.matches(&release);
         }
         // 可能是纯版本号如 8.2.0，当作最低版本
-        if let Ok(min_ver) = semver::Version::parse(constraint) {
-            if let Ok(actual) = semver::Version::parse(version) {
-                return actual >= min_ver;
-            }
+        if let Some(min_ver) =
+            Self::normalize_php_version(constraint).and_then(|v| semver::Version::parse(&v).ok())
+        {
+            return release >= min_ver;
         }
         false
     }
 
-    /// 从当前目录向上查找直到找到 composer.json 或到达根目录
-    fn find_composer_json() -> Option<PathBuf> {
+    /// 从 start 向上查找最近的 composer.json，至多检查 max_depth 层目录；
+    /// 到达含 .git 的目录（仓库根）后不再继续，避免越过项目边界读到外层的 composer.json
+    fn find_composer_json(start: &Path, max_depth: usize) -> Option<PathBuf> {
+        for dir in start.ancestors().take(max_depth) {
+            let candidate = dir.join("composer.json");
+            if candidate.exists() {
+                return Some(candidate);
+            }
+            if dir.join(".git").exists() {
+                return None;
+            }
+        }
+        None
+    }
+
+    /// 从当前目录向上查找指定文件名
+    fn find_upwards(file_name: &str) -> Option<PathBuf> {
         let mut dir = std::env::current_dir().ok()?;
         loop {
-            let candidate = dir.join("composer.json");
+            let candidate = dir.join(file_name);
             if candidate.exists() {
                 return Some(candidate);
             }
@@ -245,3 +769,167 @@ impl Executor {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn composer_batch_proxy_resolves_to_its_bin_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let bin_dir = dir.path().join("vendor").join("bin");
+        std::fs::create_dir_all(&bin_dir).unwrap();
+        let bat = bin_dir.join("phpstan.bat");
+        std::fs::write(
+            &bat,
+            "@ECHO OFF\r\nsetlocal DISABLEDELAYEDEXPANSION\r\nSET BIN_TARGET=%~dp0/../phpstan/phpstan/phpstan\r\nSET COMPOSER_RUNTIME_BIN_DIR=%~dp0\r\nphp \"%BIN_TARGET%\" %*\r\n",
+        )
+        .unwrap();
+        assert_eq!(
+            resolve_bin_script(&bat).unwrap(),
+            bin_dir
+                .join("..")
+                .join("phpstan")
+                .join("phpstan")
+                .join("phpstan")
+        );
+
+        std::fs::write(bin_dir.join("other.cmd"), "@echo off\r\nother.exe %*\r\n").unwrap();
+        assert!(resolve_bin_script(&bin_dir.join("other.cmd")).is_err());
+
+        let proxy = bin_dir.join("phpstan");
+        std::fs::write(&proxy, "#!/usr/bin/env php\n<?php\n").unwrap();
+        assert_eq!(resolve_bin_script(&proxy).unwrap(), proxy);
+    }
+
+    #[test]
+    fn normalize_php_version_pads_and_keeps_prerelease() {
+        assert_eq!(
+            Executor::normalize_php_version("8.2").as_deref(),
+            Some("8.2.0")
+        );
+        assert_eq!(
+            Executor::normalize_php_version("8.3.0RC1").as_deref(),
+            Some("8.3.0-rc1")
+        );
+        assert_eq!(
+            Executor::normalize_php_version("8.1.2-1ubuntu2.14").as_deref(),
+            Some("8.1.2")
+        );
+        assert!(Executor::php_version_matches_constraint(
+            "8.3.0RC1", ">=8.2"
+        ));
+    }
+
+    #[test]
+    fn php_version_label_accepts_bare_and_prefixed_versions() {
+        assert_eq!(Executor::php_version_label("8.2"), Some("8.2"));
+        assert_eq!(Executor::php_version_label("php8.2"), Some("8.2"));
+        assert_eq!(Executor::php_version_label("php-8.3.1"), Some("8.3.1"));
+        assert_eq!(Executor::php_version_label("php"), None);
+        assert_eq!(Executor::php_version_label("/usr/bin/php8.2"), None);
+        assert_eq!(Executor::php_version_label("8.x"), None);
+    }
+
+    #[test]
+    fn reported_version_picks_the_version_word() {
+        let cases = [
+            ("PHPStan - PHP Static Analysis Tool 1.11.2\n", "1.11.2"),
+            (
+                "PHP CS Fixer 3.59.3 15 Keys Accelerate by Fabien Potencier",
+                "3.59.3",
+            ),
+            (
+                "Psalm 5.24.0@462c80e31c34e58cc4f750c656be3927e80e550e",
+                "5.24.0",
+            ),
+            (
+                "PHPUnit 10.5.20 by Sebastian Bergmann and contributors.",
+                "10.5.20",
+            ),
+            ("Composer version 2.7.7 2024-06-10 22:11:12", "2.7.7"),
+            ("Rector v1.1.0", "1.1.0"),
+        ];
+        for (output, version) in cases {
+            assert_eq!(Executor::reported_version(output).as_deref(), Some(version));
+        }
+        assert_eq!(Executor::reported_version("Usage: tool [options]"), None);
+    }
+
+    #[test]
+    fn startup_failure_reason_detects_missing_extension() {
+        let output = "PHP Warning:  PHP Startup: Unable to load dynamic library 'intl' (tried: /usr/lib/php/intl.so)\nPHP Fatal error:  Uncaught Error: Call to undefined function mb_strlen()";
+        assert!(Executor::startup_failure_reason(output)
+            .unwrap()
+            .contains("Unable to load dynamic library 'intl'"));
+        assert!(Executor::startup_failure_reason("Found 3 errors in src/Foo.php").is_none());
+
+        // 从未配置过的扩展没有 PHP Startup 行，只能从 Composer 平台检查或未定义函数看出
+        let platform = "PHP Fatal error:  Composer detected issues in your platform: Your Composer dependencies require the following PHP extensions to be installed: intl. in /app/vendor/composer/platform_check.php on line 24";
+        assert!(Executor::startup_failure_reason(platform)
+            .unwrap()
+            .contains("require the following PHP extensions"));
+        let version = "Composer detected issues in your platform: Your Composer dependencies require a PHP version \">= 8.2.0\".";
+        assert!(Executor::startup_failure_reason(version)
+            .unwrap()
+            .contains("require a PHP version"));
+        let undefined = "PHP Fatal error:  Uncaught Error: Call to undefined function mb_strlen() in /app/src/Cli.php:12";
+        assert!(Executor::startup_failure_reason(undefined)
+            .unwrap()
+            .contains("mb_strlen()"));
+        assert!(Executor::startup_failure_reason(
+            "Error: the mbstring extension is missing, your PHP version is too old"
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn composer_json_search_prefers_nearest_and_stops_at_repo_root() {
+        let root = tempfile::tempdir().unwrap();
+        let repo = root.path().join("repo");
+        let inner = repo.join("packages").join("inner");
+        let other = repo.join("packages").join("other").join("src");
+        std::fs::create_dir_all(repo.join(".git")).unwrap();
+        std::fs::create_dir_all(inner.join("src")).unwrap();
+        std::fs::create_dir_all(&other).unwrap();
+        std::fs::write(root.path().join("composer.json"), "{}").unwrap();
+        std::fs::write(inner.join("composer.json"), "{}").unwrap();
+
+        assert_eq!(
+            Executor::find_composer_json(&inner.join("src"), DEFAULT_PROJECT_SEARCH_DEPTH),
+            Some(inner.join("composer.json"))
+        );
+        // repo 自身没有 composer.json：在 .git 处停止，不读取仓库外的 composer.json
+        assert_eq!(
+            Executor::find_composer_json(&other, DEFAULT_PROJECT_SEARCH_DEPTH),
+            None
+        );
+        // 超出层数限制时找不到
+        assert_eq!(Executor::find_composer_json(&inner.join("src"), 1), None);
+    }
+
+    #[test]
+    fn php_constraint_of_reads_package_require() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = dir.path().join("composer.json");
+        std::fs::write(
+            &manifest,
+            r#"{"require":{"php":"^8.2","symfony/console":"^7.0"}}"#,
+        )
+        .unwrap();
+        let constraint = Executor::php_constraint_of(&manifest).unwrap();
+        assert_eq!(constraint, "^8.2");
+        assert!(!Executor::php_version_matches_constraint(
+            "8.1.27",
+            &constraint
+        ));
+        assert!(Executor::php_constraint_of(&dir.path().join("missing.json")).is_none());
+    }
+
+    #[test]
+    fn php_version_file_matches_by_prefix() {
+        assert!(Executor::version_has_prefix("8.2.14", "8.2"));
+        assert!(!Executor::version_has_prefix("8.21.0", "8.2"));
+        assert!(!Executor::version_has_prefix("8.2.14", "8.2.1"));
+    }
+}
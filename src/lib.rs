@@ -1,25 +1,463 @@
+pub mod archive;
 pub mod cache;
 pub mod cli;
 pub mod composer;
 pub mod config;
+pub mod dotenv;
 pub mod download;
 pub mod error;
 pub mod executor;
+pub mod lockfile;
+pub mod manifest;
+pub mod progress;
 pub mod resolver;
 pub mod runner;
 pub mod security;
+pub mod shim;
+pub mod units;
 
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
-pub use error::{Error, Result};
+pub use error::{Error, ResolutionFailure, Result};
+pub use progress::ProgressObserver;
 
 #[derive(Debug, Clone, Default)]
 pub struct ToolOptions {
     pub clear_cache: bool,
     pub no_cache: bool,
+    /// 忽略缓存命中，重新解析并下载，新结果覆盖原缓存条目
+    pub force: bool,
     pub skip_verify: bool,
     pub php: Option<PathBuf>,
     pub no_local: bool,
+    /// 已有 phpx 缓存的副本时优先使用缓存，而不是 vendor/bin 或全局 Composer 中的工具
+    pub prefer_cache: bool,
+    /// 本地有该工具时总是使用本地副本，即使配置了 prefer_cache
+    pub prefer_local: bool,
     /// 向子工具追加 --no-interaction，避免交互式提示（如 rector 询问是否生成配置）
     pub no_interaction: bool,
+    /// 强制保留交互模式：stdin 非 TTY 时也不对已知工具自动追加 --no-interaction
+    pub interaction: bool,
+    /// 不使用配置中该工具的 default_args
+    pub no_default_args: bool,
+    /// 将下载（或缓存）的 phar 另存到该路径；为目录时保存为 <tool>.phar
+    pub keep_download: Option<PathBuf>,
+    /// 传给 PHP 解释器本身的参数（如 -dmemory_limit=-1），置于 phar 路径之前
+    pub php_args: Vec<String>,
+    /// 不使用内置及配置中该工具的 default_php_ini
+    pub no_default_php_args: bool,
+    /// 不检测项目 composer.json 的 PHP 约束
+    pub ignore_project_php: bool,
+    /// PHP 低于配置的 min_php_version 时报错退出，而不只是警告
+    pub strict_php: bool,
+    /// 解析失败时不搜索 Packagist 给出相近包名
+    pub no_suggest: bool,
+    /// 允许解析到预发布版本（等同 min_stability = dev），包括 GitHub 上标记为 prerelease 的发布
+    pub pre: bool,
+    /// 只从该来源解析，覆盖配置中的 source
+    pub source: Option<resolver::SourcePreference>,
+    /// Composer 安装的工具连同其 require-dev 一起安装（不加 --no-dev），与普通安装分开缓存
+    pub with_dev: bool,
+    /// 按版本约束运行时直接复用缓存中满足约束的版本，不重新解析（等同配置 reuse_cached = true）
+    pub reuse_cached: bool,
+    /// 按版本约束运行时总是重新解析，即使配置了 reuse_cached
+    pub prefer_newer: bool,
+    /// 从该 .env 格式文件读取变量注入工具环境，覆盖继承的同名变量
+    pub env_file: Option<PathBuf>,
+    /// 注入工具环境的变量，覆盖 env_file 与继承的同名变量
+    pub env: Vec<(String, String)>,
+}
+
+/// 被执行工具的来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToolSource {
+    /// 项目 vendor/bin
+    LocalVendor,
+    /// 全局 Composer（~/.composer/vendor/bin）
+    GlobalComposer,
+    /// phpx 缓存（phar 或已安装的 Composer 包）
+    Cache,
+    /// 本次新下载的 phar
+    DownloadedPhar,
+    /// 本次在隔离目录中用 Composer 安装
+    ComposerInstall,
+    /// 命令行直接指定的本地 phar 文件
+    LocalFile,
+}
+
+impl std::fmt::Display for ToolSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            ToolSource::LocalVendor => "local vendor/bin",
+            ToolSource::GlobalComposer => "global composer",
+            ToolSource::Cache => "cache",
+            ToolSource::DownloadedPhar => "downloaded phar",
+            ToolSource::ComposerInstall => "composer install",
+            ToolSource::LocalFile => "local file",
+        };
+        f.write_str(s)
+    }
+}
+
+/// 一次工具运行的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunOutcome {
+    /// 实际执行的版本；使用项目本地工具时为 "local"
+    pub version: String,
+    /// 工具进程的退出码
+    pub exit_code: i32,
+    /// 工具来源（本地、缓存或新下载）
+    pub source: ToolSource,
+    /// 各阶段耗时
+    pub timings: RunTimings,
+}
+
+/// 一次运行中各阶段的耗时；未经历的阶段为 0
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RunTimings {
+    /// 解析版本与下载地址（Packagist / GitHub 等）
+    pub resolve: Duration,
+    /// 下载 phar 或压缩包
+    pub download: Duration,
+    /// 签名、哈希校验（含缓存命中时的校验）
+    pub verify: Duration,
+    /// Composer 隔离安装
+    pub install: Duration,
+    /// 工具进程运行
+    pub execute: Duration,
+}
+
+impl std::fmt::Display for RunTimings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "resolve {:.2}s, download {:.2}s, verify {:.2}s, install {:.2}s, execute {:.2}s",
+            self.resolve.as_secs_f64(),
+            self.download.as_secs_f64(),
+            self.verify.as_secs_f64(),
+            self.install.as_secs_f64(),
+            self.execute.as_secs_f64()
+        )
+    }
+}
+
+/// 以库的方式运行工具的构建器，供其它 Rust 程序嵌入 phpx：
+///
+/// ```no_run
+/// # async fn demo() -> phpx::Result<()> {
+/// let outcome = phpx::RunBuilder::new()
+///     .tool("phpstan")
+///     .version("^1.10")
+///     .args(["analyse", "src"])
+///     .no_cache(true)
+///     .run()
+///     .await?;
+/// println!("phpstan {} exited with {}", outcome.version, outcome.exit_code);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct RunBuilder {
+    tool: String,
+    version: Option<String>,
+    args: Vec<String>,
+    config_path: Option<PathBuf>,
+    cache_dir: Option<PathBuf>,
+    install_dir: Option<PathBuf>,
+    max_download_size: Option<u64>,
+    composer_args: Vec<String>,
+    allow_plugins: Vec<String>,
+    ignore_platform_reqs: bool,
+    no_verify_tls: bool,
+    options: ToolOptions,
+    progress: Option<Arc<dyn ProgressObserver>>,
+}
+
+impl std::fmt::Debug for RunBuilder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RunBuilder")
+            .field("tool", &self.tool)
+            .field("version", &self.version)
+            .field("args", &self.args)
+            .field("config_path", &self.config_path)
+            .field("cache_dir", &self.cache_dir)
+            .field("install_dir", &self.install_dir)
+            .field("max_download_size", &self.max_download_size)
+            .field("composer_args", &self.composer_args)
+            .field("allow_plugins", &self.allow_plugins)
+            .field("ignore_platform_reqs", &self.ignore_platform_reqs)
+            .field("no_verify_tls", &self.no_verify_tls)
+            .field("options", &self.options)
+            .field("progress", &self.progress.is_some())
+            .finish()
+    }
+}
+
+impl RunBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 工具名，如 phpstan、laravel/pint；也可直接传入带版本的标识符（phpstan@^1.10）
+    pub fn tool(mut self, tool: impl Into<String>) -> Self {
+        self.tool = tool.into();
+        self
+    }
+
+    /// 版本或版本约束（如 ^1.10、1.11.0、latest）
+    pub fn version(mut self, version: impl Into<String>) -> Self {
+        self.version = Some(version.into());
+        self
+    }
+
+    pub fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// 使用指定配置文件代替 ~/.config/phpx/config.toml
+    pub fn config_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config_path = Some(path.into());
+        self
+    }
+
+    /// 本次运行使用的缓存目录，覆盖配置中的 cache_dir
+    pub fn cache_dir(mut self, path: impl Into<PathBuf>) -> Self {
+        self.cache_dir = Some(path.into());
+        self
+    }
+
+    /// Composer 隔离安装与 override 安装的根目录，覆盖配置中的 install_dir
+    pub fn install_dir(mut self, path: impl Into<PathBuf>) -> Self {
+        self.install_dir = Some(path.into());
+        self
+    }
+
+    /// 单个下载文件的大小上限（字节），覆盖配置中的 max_download_size
+    pub fn max_download_size(mut self, bytes: u64) -> Self {
+        self.max_download_size = Some(bytes);
+        self
+    }
+
+    /// 追加到 composer install 的额外参数，附加在配置 composer_args 之后
+    pub fn composer_args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.composer_args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// 隔离安装时允许启用的 Composer 插件，附加在配置 allow_plugins 之后
+    pub fn allow_plugin(mut self, plugin: impl Into<String>) -> Self {
+        self.allow_plugins.push(plugin.into());
+        self
+    }
+
+    /// composer install 因平台要求失败时自动忽略这些要求重试（等同配置 ignore_platform_reqs = true）
+    pub fn ignore_platform_reqs(mut self, value: bool) -> Self {
+        self.ignore_platform_reqs = value;
+        self
+    }
+
+    /// 不校验 HTTPS 证书（等同 --no-verify-tls），仅用于会替换证书的企业代理之后
+    pub fn no_verify_tls(mut self, value: bool) -> Self {
+        self.no_verify_tls = value;
+        self
+    }
+
+    pub fn php(mut self, path: impl Into<PathBuf>) -> Self {
+        self.options.php = Some(path.into());
+        self
+    }
+
+    /// 追加一个 PHP 解释器参数（如 -dmemory_limit=-1）
+    pub fn php_arg(mut self, arg: impl Into<String>) -> Self {
+        self.options.php_args.push(arg.into());
+        self
+    }
+
+    pub fn clear_cache(mut self, value: bool) -> Self {
+        self.options.clear_cache = value;
+        self
+    }
+
+    pub fn no_cache(mut self, value: bool) -> Self {
+        self.options.no_cache = value;
+        self
+    }
+
+    /// 忽略有效缓存，重新解析、下载并替换缓存条目
+    pub fn force(mut self, value: bool) -> Self {
+        self.options.force = value;
+        self
+    }
+
+    pub fn skip_verify(mut self, value: bool) -> Self {
+        self.options.skip_verify = value;
+        self
+    }
+
+    pub fn no_local(mut self, value: bool) -> Self {
+        self.options.no_local = value;
+        self
+    }
+
+    /// 已缓存时优先使用 phpx 的副本，未缓存时仍可使用本地工具
+    pub fn prefer_cache(mut self, value: bool) -> Self {
+        self.options.prefer_cache = value;
+        self
+    }
+
+    /// 本地有该工具时使用本地副本，即使配置了 prefer_cache
+    pub fn prefer_local(mut self, value: bool) -> Self {
+        self.options.prefer_local = value;
+        self
+    }
+
+    pub fn no_interaction(mut self, value: bool) -> Self {
+        self.options.no_interaction = value;
+        self
+    }
+
+    pub fn interaction(mut self, value: bool) -> Self {
+        self.options.interaction = value;
+        self
+    }
+
+    pub fn no_default_args(mut self, value: bool) -> Self {
+        self.options.no_default_args = value;
+        self
+    }
+
+    /// 运行前将 phar 另存到指定文件或目录
+    pub fn keep_download(mut self, path: impl Into<PathBuf>) -> Self {
+        self.options.keep_download = Some(path.into());
+        self
+    }
+
+    pub fn no_default_php_args(mut self, value: bool) -> Self {
+        self.options.no_default_php_args = value;
+        self
+    }
+
+    pub fn ignore_project_php(mut self, value: bool) -> Self {
+        self.options.ignore_project_php = value;
+        self
+    }
+
+    /// PHP 过旧时报错而不是警告
+    pub fn strict_php(mut self, value: bool) -> Self {
+        self.options.strict_php = value;
+        self
+    }
+
+    /// 从 .env 格式文件读取变量注入工具环境
+    pub fn env_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.options.env_file = Some(path.into());
+        self
+    }
+
+    /// 为工具设置一个环境变量（可多次调用），优先于 env_file 中的同名变量
+    pub fn env(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.options.env.push((key.into(), value.into()));
+        self
+    }
+
+    /// Composer 安装的工具同时安装其开发依赖
+    pub fn with_dev(mut self, value: bool) -> Self {
+        self.options.with_dev = value;
+        self
+    }
+
+    /// 按版本约束运行时复用已缓存的匹配版本，不重新解析
+    pub fn reuse_cached(mut self, value: bool) -> Self {
+        self.options.reuse_cached = value;
+        self
+    }
+
+    /// 版本约束内有更新的版本时安装它，即使配置了 reuse_cached
+    pub fn prefer_newer(mut self, value: bool) -> Self {
+        self.options.prefer_newer = value;
+        self
+    }
+
+    /// 允许解析到预发布版本
+    pub fn pre(mut self, value: bool) -> Self {
+        self.options.pre = value;
+        self
+    }
+
+    /// 只从 Packagist 或 GitHub 解析
+    pub fn source(mut self, source: resolver::SourcePreference) -> Self {
+        self.options.source = Some(source);
+        self
+    }
+
+    /// 接收解析、下载与安装进度的回调；不设置时不报告进度
+    pub fn progress(mut self, observer: Arc<dyn ProgressObserver>) -> Self {
+        self.progress = Some(observer);
+        self
+    }
+
+    /// 整体替换运行选项
+    pub fn options(mut self, options: ToolOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// 拼出传给解析器的工具标识符（tool@version）
+    pub fn identifier(&self) -> String {
+        match &self.version {
+            Some(version) => format!("{}@{}", self.tool, version),
+            None => self.tool.clone(),
+        }
+    }
+
+    /// 运行工具；工具以非零码退出时仍返回 Ok，退出码见 RunOutcome::exit_code
+    pub async fn run(self) -> Result<RunOutcome> {
+        if self.tool.is_empty() {
+            return Err(Error::InvalidToolIdentifier(
+                "Tool name is empty".to_string(),
+            ));
+        }
+        let identifier = self.identifier();
+        let mut config = config::Config::load(self.config_path)?;
+        if let Some(cache_dir) = self.cache_dir {
+            config.cache_dir = cache_dir;
+        }
+        if self.install_dir.is_some() {
+            config.install_dir = self.install_dir;
+        }
+        if self.max_download_size.is_some() {
+            config.max_download_size = self.max_download_size;
+        }
+        config.composer_args.extend(self.composer_args);
+        config.allow_plugins.extend(self.allow_plugins);
+        if self.ignore_platform_reqs {
+            config.ignore_platform_reqs = true;
+        }
+        if self.no_verify_tls {
+            config.no_verify_tls = true;
+        }
+        let mut runner = runner::Runner::from_config(config)?;
+        if let Some(progress) = self.progress {
+            runner = runner.with_progress(progress);
+        }
+        runner
+            .run_tool_with_options(&identifier, &self.args, &self.options)
+            .await
+    }
 }
@@ -0,0 +1,73 @@
+//! 项目级 phpx.lock：记录每个工具解析得到的确切版本与下载地址，
+//! 使未指定版本的 `phpx <tool>` 在团队内、CI 中得到相同结果。
+
+use crate::error::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+pub const LOCK_FILE_NAME: &str = "phpx.lock";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LockFile {
+    /// 工具名 -> 锁定信息；BTreeMap 保证写出顺序稳定，便于代码评审
+    #[serde(default)]
+    pub tools: BTreeMap<String, LockedTool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockedTool {
+    /// 锁定时使用的版本约束（如 ^1.10），`phpx lock` 刷新时据此重新解析
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub constraint: Option<String>,
+    pub version: String,
+    /// phar 的下载地址；Composer 安装的工具为 None
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub download_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+}
+
+impl LockFile {
+    /// 从当前目录向上查找 phpx.lock
+    pub fn find() -> Option<PathBuf> {
+        let mut dir = std::env::current_dir().ok()?;
+        loop {
+            let candidate = dir.join(LOCK_FILE_NAME);
+            if candidate.exists() {
+                return Some(candidate);
+            }
+            dir = dir.parent()?.to_path_buf();
+        }
+    }
+
+    /// 新建 lock 文件的位置：已有 phpx.lock 时沿用，否则放在 composer.json 所在目录（项目根），再否则当前目录
+    pub fn default_path() -> PathBuf {
+        if let Some(existing) = Self::find() {
+            return existing;
+        }
+        let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+        let mut dir = cwd.as_path();
+        loop {
+            if dir.join("composer.json").exists() {
+                return dir.join(LOCK_FILE_NAME);
+            }
+            match dir.parent() {
+                Some(parent) => dir = parent,
+                None => return cwd.join(LOCK_FILE_NAME),
+            }
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut content = serde_json::to_string_pretty(self)?;
+        content.push('\n');
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+}
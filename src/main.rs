@@ -1,21 +1,43 @@
 use clap::Parser;
-use phpx::cli::Cli;
-use phpx::Error;
+use phpx::cli::{Cli, LogFormat};
+use tracing_subscriber::EnvFilter;
+
+/// 按解析后的 CLI 初始化日志；PHPX_LOG / RUST_LOG 优先于命令行的 -v / -q
+fn init_tracing(cli: &Cli) {
+    let filter = std::env::var("PHPX_LOG")
+        .or_else(|_| std::env::var("RUST_LOG"))
+        .ok()
+        .and_then(|spec| EnvFilter::try_new(spec).ok())
+        .unwrap_or_else(|| EnvFilter::new(cli.log_level().as_str()));
+    // 已存在全局 subscriber 时 try_init 返回错误，忽略即可，避免重复初始化 panic
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+    let _ = match cli.log_format {
+        LogFormat::Text => builder.try_init(),
+        LogFormat::Json => builder.json().try_init(),
+    };
+}
 
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt()
-        .with_max_level(tracing::Level::INFO)
-        .init();
-
-    let cli = Cli::parse();
+    // 先解析参数再初始化日志，使 --verbose / --quiet 生效；
+    // 解析失败（含 --help）由 clap 自行输出并以对应退出码结束
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(e) => e.exit(),
+    };
+    init_tracing(&cli);
 
-    if let Err(e) = cli.execute().await {
+    match cli.execute().await {
         // 工具因自身逻辑退出（如 lint 报错）时只传播退出码，不再打印冗余错误
-        if let Error::ExecutionFailed(code) = e {
+        Ok(code) => std::process::exit(code),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            // 启动失败时仍沿用工具本身的退出码
+            let code = match e {
+                phpx::Error::PhpStartupFailed { exit_code, .. } => exit_code,
+                _ => 1,
+            };
             std::process::exit(code);
         }
-        eprintln!("Error: {}", e);
-        std::process::exit(1);
     }
 }
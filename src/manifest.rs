@@ -0,0 +1,152 @@
+//! 项目级 .phpx.toml（或 phpx.json）：在 `[scripts]` 中为常用工具调用命名，
+//! 如 `stan = "phpstan analyse src"`，通过 `phpx run-script stan` 执行（类似 composer scripts）；
+//! 在 `[tools]` 中声明项目使用的工具及版本（如 `phpstan = "^1.10"`），
+//! `phpx install` 不带参数时安装全部，`phpx <tool>` 未指定版本时使用这里的版本。
+
+use crate::error::{Error, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+pub const MANIFEST_FILE_NAME: &str = ".phpx.toml";
+/// 与 .phpx.toml 结构相同的 JSON 写法；同一目录两者都有时以 .phpx.toml 为准
+pub const JSON_MANIFEST_FILE_NAME: &str = "phpx.json";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ProjectManifest {
+    /// 脚本名 -> 完整的工具调用（首段为工具标识符，其余为参数）
+    #[serde(default)]
+    pub scripts: BTreeMap<String, String>,
+    /// 工具名 -> 版本或版本约束
+    #[serde(default)]
+    pub tools: BTreeMap<String, String>,
+}
+
+impl ProjectManifest {
+    /// 从当前目录向上查找 .phpx.toml 或 phpx.json
+    pub fn find() -> Option<PathBuf> {
+        let mut dir = std::env::current_dir().ok()?;
+        loop {
+            for name in [MANIFEST_FILE_NAME, JSON_MANIFEST_FILE_NAME] {
+                let candidate = dir.join(name);
+                if candidate.exists() {
+                    return Some(candidate);
+                }
+            }
+            dir = dir.parent()?.to_path_buf();
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        if path.extension().is_some_and(|ext| ext == "json") {
+            return serde_json::from_str(&content)
+                .map_err(|e| Error::Config(format!("Invalid {}: {}", path.display(), e)));
+        }
+        toml::from_str(&content)
+            .map_err(|e| Error::Config(format!("Invalid {}: {}", path.display(), e)))
+    }
+
+    /// 清单中的工具标识符（name@version），按工具名排序
+    pub fn tool_specs(&self) -> Vec<String> {
+        self.tools
+            .iter()
+            .map(|(name, version)| format!("{}@{}", name, version))
+            .collect()
+    }
+
+    /// 取出脚本并拆分为 (工具标识符, 参数)
+    pub fn script(&self, name: &str) -> Result<(String, Vec<String>)> {
+        let command = self
+            .scripts
+            .get(name)
+            .ok_or_else(|| Error::Config(format!("Script not found: {}", name)))?;
+        let mut words = split_command_line(command)?.into_iter();
+        let tool = words
+            .next()
+            .ok_or_else(|| Error::Config(format!("Script '{}' is empty", name)))?;
+        Ok((tool, words.collect()))
+    }
+}
+
+/// 按空白拆分命令行，支持单/双引号与反斜杠转义（不做变量展开等 shell 语义）
+pub fn split_command_line(command: &str) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut in_word = false;
+    let mut quote: Option<char> = None;
+    let mut chars = command.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"'), '\\') | (None, '\\') => {
+                if let Some(next) = chars.next() {
+                    current.push(next);
+                    in_word = true;
+                }
+            }
+            (Some(_), c) => current.push(c),
+            (None, '\'') | (None, '"') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                current.push(c);
+                in_word = true;
+            }
+        }
+    }
+
+    if quote.is_some() {
+        return Err(Error::Config(format!(
+            "Unterminated quote in command: {}",
+            command
+        )));
+    }
+    if in_word {
+        words.push(current);
+    }
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tools_are_read_from_toml_and_json_manifests() {
+        let dir = tempfile::tempdir().unwrap();
+        let toml_path = dir.path().join(MANIFEST_FILE_NAME);
+        std::fs::write(
+            &toml_path,
+            "[tools]\nphpstan = \"^1.10\"\n\"friendsofphp/php-cs-fixer\" = \"3.64.0\"\n",
+        )
+        .unwrap();
+        let json_path = dir.path().join(JSON_MANIFEST_FILE_NAME);
+        std::fs::write(
+            &json_path,
+            r#"{"tools":{"phpstan":"^1.10","friendsofphp/php-cs-fixer":"3.64.0"}}"#,
+        )
+        .unwrap();
+
+        let expected = vec!["friendsofphp/php-cs-fixer@3.64.0", "phpstan@^1.10"];
+        for path in [toml_path, json_path] {
+            let manifest = ProjectManifest::load(&path).unwrap();
+            assert_eq!(manifest.tool_specs(), expected);
+            assert!(manifest.scripts.is_empty());
+        }
+    }
+
+    #[test]
+    fn split_command_line_handles_quotes() {
+        let words = split_command_line(r#"phpstan analyse "src dir" --level='max'"#).unwrap();
+        assert_eq!(words, vec!["phpstan", "analyse", "src dir", "--level=max"]);
+    }
+}
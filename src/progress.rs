@@ -0,0 +1,107 @@
+//! 解析、下载与安装过程的进度回调，供嵌入 phpx 的程序渲染自己的界面。
+//! 库默认不输出进度（NoProgress），命令行使用基于 indicatif 的 TerminalProgress。
+
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::sync::Mutex;
+
+/// 进度观察者；所有方法都有空的默认实现，按需覆盖即可
+pub trait ProgressObserver: Send + Sync {
+    /// 开始解析工具版本（访问 Packagist / GitHub 等）
+    fn on_resolve_start(&self, _tool: &str) {}
+
+    /// 开始下载；total 为服务器给出的 Content-Length
+    fn on_download_start(&self, _url: &str, _total: Option<u64>) {}
+
+    /// 已下载 downloaded 字节（累计值）
+    fn on_download_progress(&self, _downloaded: u64, _total: Option<u64>) {}
+
+    /// 下载结束（成功或失败都会调用）
+    fn on_download_finish(&self, _url: &str) {}
+
+    /// 开始在隔离目录中用 Composer 安装包
+    fn on_install_start(&self, _package: &str, _version: &str) {}
+
+    /// Composer 安装结束（成功或失败都会调用）
+    fn on_install_finish(&self, _package: &str, _version: &str) {}
+}
+
+/// 不报告任何进度
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoProgress;
+
+impl ProgressObserver for NoProgress {}
+
+/// 在 stderr 上绘制下载进度条与安装提示
+#[derive(Debug, Default)]
+pub struct TerminalProgress {
+    bar: Mutex<Option<ProgressBar>>,
+}
+
+impl TerminalProgress {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn progress_bar(total: Option<u64>) -> ProgressBar {
+        // 限制刷新频率，避免大文件下载时刷屏
+        let target = ProgressDrawTarget::stderr_with_hz(4);
+        match total {
+            Some(len) => {
+                let bar = ProgressBar::with_draw_target(Some(len), target);
+                bar.set_style(
+                    ProgressStyle::with_template(
+                        "{bar:40.cyan/blue} {bytes}/{total_bytes} ({bytes_per_sec}, ETA {eta})",
+                    )
+                    .unwrap_or_else(|_| ProgressStyle::default_bar()),
+                );
+                bar
+            }
+            None => {
+                let bar = ProgressBar::with_draw_target(None, target);
+                bar.set_style(
+                    ProgressStyle::with_template("{spinner} {bytes} ({bytes_per_sec})")
+                        .unwrap_or_else(|_| ProgressStyle::default_spinner()),
+                );
+                bar
+            }
+        }
+    }
+
+    fn finish(&self) {
+        if let Some(bar) = self.bar.lock().unwrap_or_else(|e| e.into_inner()).take() {
+            bar.finish_and_clear();
+        }
+    }
+}
+
+impl ProgressObserver for TerminalProgress {
+    fn on_download_start(&self, _url: &str, total: Option<u64>) {
+        let mut bar = self.bar.lock().unwrap_or_else(|e| e.into_inner());
+        *bar = Some(Self::progress_bar(total));
+    }
+
+    fn on_download_progress(&self, downloaded: u64, _total: Option<u64>) {
+        if let Some(bar) = self.bar.lock().unwrap_or_else(|e| e.into_inner()).as_ref() {
+            bar.set_position(downloaded);
+        }
+    }
+
+    fn on_download_finish(&self, _url: &str) {
+        self.finish();
+    }
+
+    fn on_install_start(&self, package: &str, version: &str) {
+        let spinner = ProgressBar::with_draw_target(None, ProgressDrawTarget::stderr());
+        spinner.set_style(
+            ProgressStyle::with_template("{spinner} {msg}")
+                .unwrap_or_else(|_| ProgressStyle::default_spinner()),
+        );
+        spinner.set_message(format!("Installing {}@{} with Composer", package, version));
+        spinner.enable_steady_tick(std::time::Duration::from_millis(120));
+        *self.bar.lock().unwrap_or_else(|e| e.into_inner()) = Some(spinner);
+    }
+
+    fn on_install_finish(&self, _package: &str, _version: &str) {
+        self.finish();
+    }
+}
@@ -1,13 +1,80 @@
-use crate::error::{Error, Result};
+use crate::archive::ArchiveKind;
+use crate::download::{file_url_path, warn_tls_unverified, DEFAULT_USER_AGENT};
+use crate::error::{Error, ResolutionFailure, Result};
+use crate::security::{parse_hash, HashAlgorithm};
+use futures::stream::{self, StreamExt};
 use semver::{Version, VersionReq};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+/// 同时请求 GitHub API 的 URL 变体数上限，避免触发限流
+const GITHUB_CONCURRENCY: usize = 4;
+
+/// 解析失败时给出的相近包名数上限
+const SUGGESTION_LIMIT: usize = 5;
 
 #[derive(Debug, Clone)]
 pub struct ToolIdentifier {
     pub name: String,
     pub version_constraint: Option<VersionReq>,
     pub version: Option<String>,
+    /// 标识符本身是 http(s) 地址时，直接从该地址下载 phar，不经过 Packagist/GitHub
+    pub url: Option<String>,
+    /// `vendor/package:bin` 指定要运行的 bin（Composer 包带多个 bin 时），默认运行第一个
+    pub bin: Option<String>,
+}
+
+/// 解析工具时查询的来源。auto 按固定顺序依次尝试 Packagist → GitHub Releases → GitHub tags → 直链，
+/// 第一个给出匹配版本的来源胜出：同一版本在 Packagist 与 GitHub 上都有时总是使用 Packagist 的产物
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum SourcePreference {
+    #[default]
+    Auto,
+    /// 只查询 Packagist
+    Packagist,
+    /// 只查询 GitHub（Releases、tags 与直链）
+    Github,
+}
+
+/// 版本稳定性，按 Composer minimum-stability 的顺序从低到高排列
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stability {
+    Dev,
+    Alpha,
+    Beta,
+    #[serde(rename = "RC", alias = "rc")]
+    Rc,
+    #[default]
+    Stable,
+}
+
+impl Stability {
+    /// 按 semver 预发布段分类：无预发布为 stable，rc/beta/alpha（含 b1、a2 这类简写）对应各级，其余视为 dev
+    pub fn of(version: &Version) -> Self {
+        let pre = version.pre.as_str().to_ascii_lowercase();
+        // b / a 简写后面只能是数字或结尾，避免把 build.5 之类的标签当成 beta
+        let tagged = |long: &str, short: char| {
+            pre.starts_with(long)
+                || pre.strip_prefix(short).is_some_and(|rest| {
+                    rest.is_empty() || rest.starts_with(|c: char| c.is_ascii_digit())
+                })
+        };
+        if pre.is_empty() {
+            Self::Stable
+        } else if pre.starts_with("rc") {
+            Self::Rc
+        } else if tagged("beta", 'b') {
+            Self::Beta
+        } else if tagged("alpha", 'a') {
+            Self::Alpha
+        } else {
+            Self::Dev
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -31,6 +98,40 @@ pub struct ComposerPackage {
     pub package: String,
     pub version: String,
     pub bin_names: Vec<String>,
+    /// 包自身的 require-dev，`--with-dev` 安装时一并安装
+    pub require_dev: HashMap<String, String>,
+}
+
+/// 解析器发请求用的客户端：关闭证书校验时与 Downloader::get 一样，每个请求都带上实际地址告警
+struct HttpClient {
+    inner: reqwest::Client,
+    accept_invalid_certs: bool,
+}
+
+impl HttpClient {
+    fn get(&self, url: &str) -> reqwest::RequestBuilder {
+        if self.accept_invalid_certs {
+            warn_tls_unverified(url);
+        }
+        self.inner.get(url)
+    }
+
+    fn head(&self, url: &str) -> reqwest::RequestBuilder {
+        if self.accept_invalid_certs {
+            warn_tls_unverified(url);
+        }
+        self.inner.head(url)
+    }
+}
+
+/// 单个 GitHub releases URL 没有给出工具的原因
+enum ReleaseMiss {
+    /// 仓库不存在（HTTP 404）
+    Missing,
+    /// 请求失败、HTTP 错误或限流，工具未必不存在；可能附带值得告诉用户的原因
+    Failed(Option<String>),
+    /// 仓库存在但没有合适的发布
+    Unsuitable(String),
 }
 
 // Packagist 相关类型
@@ -39,6 +140,30 @@ struct PackagistVersionInfo {
     dist: PackagistDist,
     #[serde(default)]
     bin: Option<Vec<String>>,
+    #[serde(
+        rename = "require-dev",
+        default,
+        deserialize_with = "string_map_or_empty"
+    )]
+    require_dev: HashMap<String, String>,
+}
+
+/// Packagist 对空的 require-dev 可能给出 [] 或 "__unset"，只接受对象，其它视为空
+fn string_map_or_empty<'de, D>(
+    deserializer: D,
+) -> std::result::Result<HashMap<String, String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value = serde_json::Value::deserialize(deserializer)?;
+    Ok(value
+        .as_object()
+        .map(|map| {
+            map.iter()
+                .filter_map(|(k, v)| Some((k.clone(), v.as_str()?.to_string())))
+                .collect()
+        })
+        .unwrap_or_default())
 }
 
 #[derive(Deserialize)]
@@ -46,45 +171,374 @@ struct PackagistDist {
     url: String,
     #[serde(rename = "type")]
     dist_type: String,
+    /// 发布对应的提交/标签引用，用于把下载地址固定到不可变的制品
+    #[serde(default)]
+    reference: Option<String>,
+}
+
+impl PackagistDist {
+    /// 返回指向不可变制品的下载地址：URL 已含 reference 时原样返回；
+    /// GitHub/GitLab 的 zipball/tarball/archive 地址末段替换为 reference；其它情况无法固定，原样返回
+    fn stable_url(&self) -> String {
+        let reference = match self.reference.as_deref().map(str::trim) {
+            Some(r) if !r.is_empty() => r,
+            _ => return self.url.clone(),
+        };
+        if self.url.contains(reference) {
+            return self.url.clone();
+        }
+        if let Some((base, _mutable_ref)) = self.url.rsplit_once('/') {
+            let kind = base.rsplit('/').next().unwrap_or("");
+            if matches!(kind, "zipball" | "tarball" | "archive") {
+                return format!("{}/{}", base, reference);
+            }
+        }
+        tracing::debug!(
+            "Cannot pin dist URL {} to reference {}, using it as-is",
+            self.url,
+            reference
+        );
+        self.url.clone()
+    }
 }
 
 // GitHub 相关类型
 #[derive(Deserialize)]
 struct GitHubRelease {
     tag_name: String,
+    /// GitHub 上标记为预发布；tag 无法按 semver 解析时据此判断稳定性
+    #[serde(default)]
+    prerelease: bool,
+    /// 草稿发布尚未公开，解析时一律跳过
+    #[serde(default)]
+    draft: bool,
     assets: Vec<GitHubAsset>,
+    /// 发布说明（Markdown），用于 outdated / update --changelog
+    #[serde(default)]
+    body: Option<String>,
+    /// 发布时间（ISO 8601）
+    #[serde(default)]
+    published_at: Option<String>,
+}
+
+/// 一个版本的 GitHub 发布说明
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ReleaseNote {
+    pub version: String,
+    /// 发布日期（YYYY-MM-DD），GitHub 未给出时为 None
+    pub published_at: Option<String>,
+    pub body: String,
+}
+
+/// /repos/{owner}/{repo}/tags 中的一项
+#[derive(Deserialize)]
+struct GitHubTag {
+    name: String,
 }
 
 #[derive(Deserialize)]
 struct GitHubAsset {
     name: String,
     browser_download_url: String,
+    /// API 地址（api.github.com/repos/.../releases/assets/<id>），私有仓库需带 token 经此下载
+    #[serde(default)]
+    url: String,
 }
 
-pub struct ToolResolver;
+/// 访问 GitHub 的 token：GITHUB_TOKEN 优先，其次 GH_TOKEN
+pub fn github_token() -> Option<String> {
+    ["GITHUB_TOKEN", "GH_TOKEN"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|token| !token.trim().is_empty())
+}
+
+/// 仅对 github.com / api.github.com 返回 token，避免泄露给镜像或其它下载源
+pub fn github_token_for(url: &str) -> Option<String> {
+    scope_token_to_github(github_token(), url)
+}
+
+/// url 的主机为 github.com / api.github.com 时原样返回 token，否则为 None
+fn scope_token_to_github(token: Option<String>, url: &str) -> Option<String> {
+    let host = reqwest::Url::parse(url)
+        .ok()?
+        .host_str()?
+        .to_ascii_lowercase();
+    if host == "github.com" || host == "api.github.com" {
+        token
+    } else {
+        None
+    }
+}
+
+pub struct ToolResolver {
+    connect_timeout: Option<Duration>,
+    timeout: Option<Duration>,
+    /// 未指定具体版本时可接受的最低稳定性
+    min_stability: Stability,
+    /// Packagist / GitHub 地址
+    hosts: ResolverConfig,
+    /// 解析开始时先探测各地址是否可达，不可达的来源直接跳过
+    health_check: bool,
+    /// 解析失败时到 Packagist 搜索相近包名作为提示
+    suggest: bool,
+    /// 查询哪些来源，见 SourcePreference
+    source: SourcePreference,
+    /// Packagist 同一版本同时有 path（phar）与 zip（库）dist 时优先 zip；phpx add 安装库包时开启
+    prefer_library: bool,
+    /// 所有请求使用的 User-Agent（GitHub API 要求必须带）
+    user_agent: String,
+    /// 接受无效的 HTTPS 证书（--no-verify-tls）
+    accept_invalid_certs: bool,
+}
+
+/// 地址探测的超时；只判断能否连通，不需要等待完整响应
+const HEALTH_PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// 两个字符串的 Levenshtein 编辑距离
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = prev[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(prev[j + 1] + 1).min(current[j] + 1);
+        }
+        prev = current;
+    }
+    prev[b.len()]
+}
+
+/// 本进程内各地址的探测结果，每个地址最多探测一次
+fn host_health() -> &'static Mutex<HashMap<String, bool>> {
+    static HEALTH: OnceLock<Mutex<HashMap<String, bool>>> = OnceLock::new();
+    HEALTH.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// 解析器访问的服务地址，默认指向公共 Packagist 与 GitHub；可替换为自建镜像或测试用的 mock 服务器
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResolverConfig {
+    /// Packagist 地址，如 https://packagist.org
+    pub packagist_base: String,
+    /// GitHub API 地址，如 https://api.github.com
+    pub github_api_base: String,
+    /// GitHub 网页/下载地址，用于 releases/latest/download 直链，如 https://github.com
+    pub github_download_base: String,
+}
+
+impl Default for ResolverConfig {
+    fn default() -> Self {
+        Self {
+            packagist_base: "https://packagist.org".to_string(),
+            github_api_base: "https://api.github.com".to_string(),
+            github_download_base: "https://github.com".to_string(),
+        }
+    }
+}
+
+impl From<&crate::config::Config> for ResolverConfig {
+    fn from(config: &crate::config::Config) -> Self {
+        Self {
+            packagist_base: config.packagist_base.clone(),
+            github_api_base: config.github_api_base.clone(),
+            github_download_base: config.github_download_base.clone(),
+        }
+    }
+}
 
 impl Default for ToolResolver {
     fn default() -> Self {
-        Self::new()
+        Self::new(ResolverConfig::default())
     }
 }
 
 impl ToolResolver {
-    pub fn new() -> Self {
-        Self
+    /// 使用给定服务地址创建解析器；地址末尾的 / 会被去掉，便于拼接路径
+    pub fn new(hosts: ResolverConfig) -> Self {
+        let trim = |url: String| url.trim_end_matches('/').to_string();
+        Self {
+            connect_timeout: None,
+            timeout: None,
+            min_stability: Stability::default(),
+            hosts: ResolverConfig {
+                packagist_base: trim(hosts.packagist_base),
+                github_api_base: trim(hosts.github_api_base),
+                github_download_base: trim(hosts.github_download_base),
+            },
+            health_check: false,
+            suggest: true,
+            source: SourcePreference::default(),
+            prefer_library: false,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            accept_invalid_certs: false,
+        }
+    }
+
+    /// 只查询指定来源（默认 auto：按固定顺序查询全部来源）
+    pub fn with_source(mut self, source: SourcePreference) -> Self {
+        self.source = source;
+        self
+    }
+
+    pub fn set_source(&mut self, source: SourcePreference) {
+        self.source = source;
+    }
+
+    /// Packagist 同一版本有多个 dist 时优先库包（zip）而不是 phar（path）
+    pub fn with_prefer_library(mut self, prefer_library: bool) -> Self {
+        self.prefer_library = prefer_library;
+        self
+    }
+
+    pub fn set_prefer_library(&mut self, prefer_library: bool) {
+        self.prefer_library = prefer_library;
+    }
+
+    /// 设置请求使用的 User-Agent
+    pub fn with_user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    /// 解析失败时是否搜索 Packagist 给出相近包名（默认开启）
+    pub fn with_suggestions(mut self, enabled: bool) -> Self {
+        self.suggest = enabled;
+        self
+    }
+
+    pub fn set_suggestions(&mut self, enabled: bool) {
+        self.suggest = enabled;
+    }
+
+    pub fn set_min_stability(&mut self, min_stability: Stability) {
+        self.min_stability = min_stability;
+    }
+
+    /// 解析前探测 Packagist / GitHub 地址的可达性，跳过不可达的镜像
+    pub fn with_health_check(mut self, enabled: bool) -> Self {
+        self.health_check = enabled;
+        self
+    }
+
+    /// 地址是否可达：任意 HTTP 响应（含 4xx/5xx）都算可达，只有连接失败或超时算不可达。
+    /// 未开启 health_check 时总是返回 true；结果在进程内缓存
+    async fn host_is_up(&self, base: &str) -> bool {
+        if !self.health_check {
+            return true;
+        }
+        if let Some(&up) = host_health()
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(base)
+        {
+            return up;
+        }
+
+        let up = match file_url_path(base) {
+            Some(path) => path.exists(),
+            None => self
+                .http_client()
+                .head(base)
+                .timeout(HEALTH_PROBE_TIMEOUT)
+                .send()
+                .await
+                .is_ok(),
+        };
+        if up {
+            tracing::debug!("Mirror {} is reachable", base);
+        } else {
+            tracing::warn!("Mirror {} is unreachable, skipping it for this run", base);
+        }
+        host_health()
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(base.to_string(), up);
+        up
+    }
+
+    /// 设置独立的连接超时与总超时
+    pub fn with_timeouts(mut self, connect_timeout: Duration, timeout: Duration) -> Self {
+        self.connect_timeout = Some(connect_timeout);
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// 设置最低稳定性（如 beta 允许 beta 与 RC，但排除 alpha/dev）
+    pub fn with_min_stability(mut self, min_stability: Stability) -> Self {
+        self.min_stability = min_stability;
+        self
+    }
+
+    /// 不校验 HTTPS 证书；只应在会替换证书的代理后使用，每次请求都会警告
+    pub fn with_accept_invalid_certs(mut self, accept: bool) -> Self {
+        self.accept_invalid_certs = accept;
+        self
+    }
+
+    /// 是否关闭了 HTTPS 证书校验
+    pub fn accepts_invalid_certs(&self) -> bool {
+        self.accept_invalid_certs
+    }
+
+    /// 带超时设置的 HTTP 客户端构建器
+    fn client_builder(&self) -> reqwest::ClientBuilder {
+        let mut builder = reqwest::Client::builder()
+            .user_agent(&self.user_agent)
+            .danger_accept_invalid_certs(self.accept_invalid_certs);
+        if let Some(t) = self.connect_timeout {
+            builder = builder.connect_timeout(t);
+        }
+        if let Some(t) = self.timeout {
+            builder = builder.timeout(t);
+        }
+        builder
+    }
+
+    fn http_client(&self) -> HttpClient {
+        HttpClient {
+            inner: self
+                .client_builder()
+                .build()
+                .unwrap_or_else(|_| reqwest::Client::new()),
+            accept_invalid_certs: self.accept_invalid_certs,
+        }
     }
 
     pub fn parse_identifier(&self, identifier: &str) -> Result<ToolIdentifier> {
+        if identifier.starts_with("https://")
+            || identifier.starts_with("http://")
+            || identifier.starts_with("file://")
+        {
+            return Self::parse_url_identifier(identifier);
+        }
+
         let parts: Vec<&str> = identifier.split('@').collect();
 
+        // name:bin 选择 Composer 包中的某个 bin
+        let (name, bin) = match parts[0].split_once(':') {
+            Some((name, bin)) => {
+                if name.is_empty() || bin.is_empty() || bin.contains(['/', ':']) {
+                    return Err(Error::InvalidToolIdentifier(format!(
+                        "Invalid bin selector in '{}', expected vendor/package:bin",
+                        identifier
+                    )));
+                }
+                (name, Some(bin.to_string()))
+            }
+            None => (parts[0], None),
+        };
+
         match parts.len() {
             1 => Ok(ToolIdentifier {
-                name: parts[0].to_string(),
+                name: name.to_string(),
                 version_constraint: None,
                 version: None,
+                url: None,
+                bin,
             }),
             2 => {
-                let name = parts[0].to_string();
+                let name = name.to_string();
                 let version_str = parts[1];
 
                 if version_str == "latest" {
@@ -92,6 +546,8 @@ impl ToolResolver {
                         name,
                         version_constraint: None,
                         version: Some("latest".to_string()),
+                        url: None,
+                        bin,
                     })
                 } else {
                     match VersionReq::parse(version_str) {
@@ -99,11 +555,15 @@ impl ToolResolver {
                             name,
                             version_constraint: Some(constraint),
                             version: None,
+                            url: None,
+                            bin,
                         }),
                         Err(_) => Ok(ToolIdentifier {
                             name,
                             version_constraint: None,
                             version: Some(version_str.to_string()),
+                            url: None,
+                            bin,
                         }),
                     }
                 }
@@ -114,7 +574,40 @@ impl ToolResolver {
         }
     }
 
+    /// URL 形式的标识符：缓存名取文件名（去掉 .phar），版本取 URL 的短哈希，使不同地址的同名 phar 互不覆盖
+    fn parse_url_identifier(url: &str) -> Result<ToolIdentifier> {
+        let path = url.split(['?', '#']).next().unwrap_or(url);
+        let file_name = path.rsplit('/').next().unwrap_or("");
+        let name = [".phar", ".tar.gz", ".tgz", ".zip"]
+            .iter()
+            .find_map(|ext| file_name.strip_suffix(ext))
+            .unwrap_or(file_name);
+        if name.is_empty() {
+            return Err(Error::InvalidToolIdentifier(format!(
+                "Cannot derive a tool name from URL: {}",
+                url
+            )));
+        }
+        let digest = format!("{:x}", md5::compute(url));
+        Ok(ToolIdentifier {
+            name: name.to_string(),
+            version_constraint: None,
+            version: Some(format!("url-{}", &digest[..8])),
+            url: Some(url.to_string()),
+            bin: None,
+        })
+    }
+
+    /// 解析工具。来源顺序固定且先成功者胜出（见 SourcePreference）：Packagist（path dist → phar，
+    /// zip dist → Composer 安装）、GitHub Releases、GitHub tags，最后在未指定版本时尝试直链。
+    /// 同一 Packagist 版本有多个 dist 时的取舍见 find_matching_version
     pub async fn resolve_tool(&self, identifier: &ToolIdentifier) -> Result<ResolvedTool> {
+        if let Some(url) = &identifier.url {
+            return Ok(ResolvedTool::Phar(
+                self.resolve_from_url(identifier, url).await,
+            ));
+        }
+
         // 内置 composer：从 getcomposer.org 下载 composer.phar
         if identifier.name == "composer" {
             return Ok(ResolvedTool::Phar(
@@ -122,30 +615,158 @@ impl ToolResolver {
             ));
         }
 
+        let use_packagist = self.source != SourcePreference::Github;
+        let use_github = self.source != SourcePreference::Packagist;
+        // 同时探测用到的各个地址，避免不可达的镜像耗尽整个解析的超时
+        let (packagist_up, github_up, download_up) = futures::join!(
+            async { !use_packagist || self.host_is_up(&self.hosts.packagist_base).await },
+            async { !use_github || self.host_is_up(&self.hosts.github_api_base).await },
+            async { !use_github || self.host_is_up(&self.hosts.github_download_base).await }
+        );
+
+        let mut failure = ResolutionFailure::new(&identifier.name);
+        failure.transient = !packagist_up || !github_up || !download_up;
+        for (up, source, base) in [
+            (packagist_up, "Packagist", &self.hosts.packagist_base),
+            (github_up, "GitHub", &self.hosts.github_api_base),
+            (download_up, "GitHub", &self.hosts.github_download_base),
+        ] {
+            if !up {
+                failure.add_reason(format!("{}: {} is unreachable", source, base));
+            }
+        }
+
         // 首先尝试从 Packagist 解析（path → Phar，zip → Composer）
-        if let Ok(resolved) = self.resolve_from_packagist(identifier).await {
-            return Ok(resolved);
+        if use_packagist && packagist_up {
+            tracing::debug!(
+                "Resolving {}: trying Packagist at {}",
+                identifier.name,
+                self.hosts.packagist_base
+            );
+            match self.resolve_from_packagist(identifier).await {
+                Ok(resolved) => return Ok(resolved),
+                Err(e) => {
+                    tracing::debug!("Packagist did not resolve {}: {}", identifier.name, e);
+                    failure.absorb("Packagist", e);
+                }
+            }
         }
 
         // 然后尝试从 GitHub Releases 解析
-        if let Ok(tool_info) = self.resolve_from_github(identifier).await {
-            return Ok(ResolvedTool::Phar(tool_info));
+        if use_github && github_up {
+            tracing::debug!(
+                "Resolving {}: trying GitHub releases at {}",
+                identifier.name,
+                self.hosts.github_api_base
+            );
+            match self.resolve_from_github(identifier).await {
+                Ok(tool_info) => return Ok(ResolvedTool::Phar(tool_info)),
+                Err(e) => {
+                    tracing::debug!("GitHub did not resolve {}: {}", identifier.name, e);
+                    failure.absorb("GitHub", e);
+                }
+            }
+        }
+
+        // 只打 tag、不建 Release 的仓库：按 tag 匹配版本，在 github_download_base 下的常见位置找 phar
+        if use_github && github_up && download_up {
+            tracing::debug!("Resolving {}: trying GitHub tags", identifier.name);
+            match self.resolve_from_github_tags(identifier).await {
+                Ok(tool_info) => return Ok(ResolvedTool::Phar(tool_info)),
+                Err(e) => {
+                    tracing::debug!("GitHub tags did not resolve {}: {}", identifier.name, e);
+                    failure.absorb("GitHub", e);
+                }
+            }
         }
 
         // 仅当用户未指定版本约束且未指定具体版本（或明确 @latest）时，才尝试直接 URL（latest）
-        let use_direct_url = identifier.version_constraint.is_none()
+        let use_direct_url = use_github
+            && download_up
+            && identifier.version_constraint.is_none()
             && identifier
                 .version
                 .as_deref()
                 .map(|v| v == "latest")
                 .unwrap_or(true);
         if use_direct_url {
+            tracing::debug!("Resolving {}: trying direct download URLs", identifier.name);
             if let Ok(tool_info) = self.resolve_from_direct_url(identifier).await {
                 return Ok(ResolvedTool::Phar(tool_info));
             }
+        } else if download_up {
+            tracing::debug!(
+                "Skipping direct download URLs for {}: a specific version was requested",
+                identifier.name
+            );
+        }
+
+        // 各来源都明确表示没有该工具时，到 Packagist 搜索相近的包名作为提示；
+        // 有来源不可达、出错或被限流时工具未必真的不存在，不给出名称建议
+        if self.suggest && !failure.transient {
+            failure.suggestions = self
+                .search_packagist(&identifier.name, SUGGESTION_LIMIT)
+                .await;
+        }
+        Err(Error::ToolNotFound(failure))
+    }
+
+    /// 用户请求的版本，用于错误提示：约束、具体版本，或未指定时的最低稳定性
+    fn describe_request(&self, identifier: &ToolIdentifier) -> String {
+        match (
+            &identifier.version_constraint,
+            identifier.version.as_deref(),
+        ) {
+            (Some(constraint), _) => constraint.to_string(),
+            (None, Some(version)) if version != "latest" => version.to_string(),
+            _ => format!("minimum stability {:?}", self.min_stability).to_lowercase(),
+        }
+    }
+
+    /// 在 Packagist 搜索与 query 相近的包名，最多 limit 个；按包名末段与 query 末段的编辑距离排序，
+    /// 距离相同时保留 Packagist 的相关度顺序。搜索失败时返回空列表
+    pub async fn search_packagist(&self, query: &str, limit: usize) -> Vec<String> {
+        #[derive(Deserialize)]
+        struct SearchResponse {
+            results: Vec<SearchResult>,
         }
 
-        Err(Error::ToolNotFound(identifier.name.clone()))
+        #[derive(Deserialize)]
+        struct SearchResult {
+            name: String,
+        }
+
+        let url = format!("{}/search.json", self.hosts.packagist_base);
+        let response = match self
+            .http_client()
+            .get(&url)
+            .query(&[("q", query), ("per_page", "15")])
+            .send()
+            .await
+        {
+            Ok(response) if response.status().is_success() => response,
+            Ok(response) => {
+                tracing::debug!("Packagist search {}: HTTP {}", url, response.status());
+                return Vec::new();
+            }
+            Err(e) => {
+                tracing::debug!("Packagist search {}: request failed: {}", url, e);
+                return Vec::new();
+            }
+        };
+        let Ok(search) = response.json::<SearchResponse>().await else {
+            return Vec::new();
+        };
+
+        let wanted = query.rsplit('/').next().unwrap_or(query).to_lowercase();
+        let mut names: Vec<String> = search.results.into_iter().map(|r| r.name).collect();
+        // sort_by_key 是稳定排序
+        names.sort_by_key(|candidate| {
+            let segment = candidate.rsplit('/').next().unwrap_or(candidate);
+            edit_distance(&segment.to_lowercase(), &wanted)
+        });
+        names.truncate(limit);
+        names
     }
 
     /// 内置 composer 工具：getcomposer.org 的 composer.phar
@@ -186,71 +807,119 @@ impl ToolResolver {
             ]
         };
 
-        let client = reqwest::Client::new();
+        let mut failure = ResolutionFailure::new(&identifier.name);
+        let client = self.http_client();
         for packagist_name in names_to_try {
-            let url = format!("https://packagist.org/packages/{}.json", packagist_name);
-            let response = client.get(&url).send().await?;
-            if !response.status().is_success() {
-                continue;
-            }
+            let url = format!(
+                "{}/packages/{}.json",
+                self.hosts.packagist_base, packagist_name
+            );
+            // packagist_base 为 file:// 时从本地（或共享挂载的）镜像目录读取 packages/<name>.json
+            let body = match file_url_path(&url) {
+                Some(path) => match tokio::fs::read_to_string(&path).await {
+                    Ok(body) => body,
+                    Err(e) => {
+                        tracing::debug!("Packagist {}: {}", url, e);
+                        continue;
+                    }
+                },
+                None => {
+                    let response = match client.get(&url).send().await {
+                        Ok(response) => response,
+                        Err(e) => {
+                            tracing::debug!("Packagist {}: request failed: {}", url, e);
+                            return Err(e.into());
+                        }
+                    };
+                    if !response.status().is_success() {
+                        tracing::debug!("Packagist {}: HTTP {}", url, response.status());
+                        failure.transient |= response.status() != reqwest::StatusCode::NOT_FOUND;
+                        continue;
+                    }
+                    response.text().await?
+                }
+            };
 
             // 响应可能为 HTML（如单段名重定向页），解析失败则尝试下一个包名
-            let packagist_response: PackagistResponse = match response.json().await {
+            let packagist_response: PackagistResponse = match serde_json::from_str(&body) {
                 Ok(p) => p,
-                Err(_) => continue,
+                Err(e) => {
+                    tracing::debug!("Packagist {}: not package metadata ({})", url, e);
+                    continue;
+                }
             };
 
-            let version =
-                match self.find_matching_version(&packagist_response.package.versions, identifier) {
-                    Ok(v) => v,
-                    Err(_) => continue,
-                };
+            let key = match self
+                .find_matching_version(&packagist_response.package.versions, identifier)
+            {
+                Ok(key) => key,
+                Err(e) => {
+                    tracing::debug!("Packagist {}: no matching version ({})", url, e);
+                    failure.add_reason(format!(
+                        "Packagist: {} exists but no version matches {}",
+                        packagist_name,
+                        self.describe_request(identifier)
+                    ));
+                    continue;
+                }
+            };
 
-            let version_info = &packagist_response.package.versions[&version];
+            let version_info = &packagist_response.package.versions[&key];
+            // 带 v 前缀的 semver 键按不带前缀的版本记录，与 GitHub 来源的版本号一致
+            let version = match key.strip_prefix('v') {
+                Some(stripped) if Version::parse(stripped).is_ok() => stripped.to_string(),
+                _ => key.clone(),
+            };
             let dist = &version_info.dist;
+            tracing::debug!(
+                "Packagist {}: matched {} with {} dist",
+                url,
+                version,
+                dist.dist_type
+            );
 
             return match dist.dist_type.as_str() {
                 "path" => Ok(ResolvedTool::Phar(ToolInfo {
                     name: identifier.name.clone(),
                     version: version.clone(),
-                    download_url: dist.url.clone(),
+                    download_url: dist.stable_url(),
                     signature_url: None,
                     hash: None,
                 })),
                 "zip" => {
-                    let bin_names = version_info
-                        .bin
-                        .clone()
-                        .filter(|b| !b.is_empty())
-                        .unwrap_or_else(|| {
-                            let default = packagist_name
-                                .split('/')
-                                .last()
-                                .unwrap_or("tool")
-                                .to_string();
-                            vec![default]
-                        });
+                    // 没有声明 bin 的库包装好后 vendor/bin 里也没有可执行文件
+                    let Some(bin_names) = version_info.bin.clone().filter(|b| !b.is_empty()) else {
+                        tracing::debug!("Packagist {}: {} declares no bin", url, version);
+                        failure.add_reason(format!(
+                            "Packagist: {} exists but is a library with no phar or bin to run",
+                            packagist_name
+                        ));
+                        continue;
+                    };
                     // 标准化 bin：Packagist 可能为 "bin/rector"，取最后一段
                     let bin_names: Vec<String> = bin_names
                         .into_iter()
-                        .map(|b| {
-                            b.split('/')
-                                .last()
-                                .map(String::from)
-                                .unwrap_or(b)
-                        })
+                        .map(|b| b.split('/').next_back().map(String::from).unwrap_or(b))
                         .collect();
                     Ok(ResolvedTool::Composer(ComposerPackage {
                         package: packagist_name,
                         version,
                         bin_names,
+                        require_dev: version_info.require_dev.clone(),
                     }))
                 }
-                _ => continue,
+                other => {
+                    tracing::debug!("Packagist {}: unsupported dist type {}", url, other);
+                    failure.add_reason(format!(
+                        "Packagist: {} exists but has no phar or bin ({} dist)",
+                        packagist_name, other
+                    ));
+                    continue;
+                }
             };
         }
 
-        Err(Error::ToolNotFound(identifier.name.clone()))
+        Err(Error::ToolNotFound(failure))
     }
 
     /// 将工具名解析为 GitHub (owner, repo)。支持 vendor/package 如 laravel/pint -> (laravel, pint)
@@ -370,82 +1039,425 @@ impl ToolResolver {
     }
 
     async fn resolve_from_github(&self, identifier: &ToolIdentifier) -> Result<ToolInfo> {
-        // GitHub API 要求带 User-Agent，且部分仓库使用大写（如 PHP-CS-Fixer）
-        let client = reqwest::Client::builder()
-            .user_agent("phpx/0.1")
-            .build()
-            .unwrap_or_else(|_| reqwest::Client::new());
+        // 部分仓库使用大写（如 PHP-CS-Fixer），需尝试多种写法
+        let client = self.http_client();
 
         let base_urls: Vec<String> = Self::github_owner_repo_variants(&identifier.name)
             .into_iter()
             .flat_map(|(owner, repo)| {
+                let api = &self.hosts.github_api_base;
                 vec![
-                    format!("https://api.github.com/repos/{}/{}/releases", owner, repo),
-                    format!(
-                        "https://api.github.com/repos/{}/php-{}/releases",
-                        owner, repo
-                    ),
-                    format!(
-                        "https://api.github.com/repos/php-{}/{}/releases",
-                        owner, repo
-                    ),
+                    format!("{}/repos/{}/{}/releases", api, owner, repo),
+                    format!("{}/repos/{}/php-{}/releases", api, owner, repo),
+                    format!("{}/repos/php-{}/{}/releases", api, owner, repo),
                 ]
             })
             .collect();
 
-        for url in base_urls {
-            if let Ok(response) = client.get(&url).send().await {
-                if response.status().is_success() {
-                    let releases: Vec<GitHubRelease> = response.json().await?;
-
-                    // 找到合适的版本
-                    if let Some(release) = self.find_matching_github_release(&releases, identifier)
-                    {
-                        // 查找 .phar 文件
-                        if let Some(asset) =
-                            release.assets.iter().find(|a| a.name.ends_with(".phar"))
-                        {
-                            return Ok(ToolInfo {
-                                name: identifier.name.clone(),
-                                version: release.tag_name.trim_start_matches('v').to_string(),
-                                download_url: asset.browser_download_url.clone(),
-                                signature_url: self.find_signature_url(&release.assets),
-                                hash: None,
-                            });
-                        }
+        // 并发请求各 URL 变体（限制并发数），buffered 保持原有顺序，优先返回靠前变体的结果
+        let mut results = stream::iter(base_urls)
+            .map(|url| self.fetch_github_release(&client, url, identifier))
+            .buffered(GITHUB_CONCURRENCY);
+        let mut failure = ResolutionFailure::new(&identifier.name);
+        while let Some(result) = results.next().await {
+            match result {
+                Ok(tool_info) => return Ok(tool_info),
+                Err(ReleaseMiss::Missing) => {}
+                Err(ReleaseMiss::Failed(reason)) => {
+                    failure.transient = true;
+                    if let Some(reason) = reason {
+                        failure.add_reason(reason);
                     }
                 }
+                Err(ReleaseMiss::Unsuitable(reason)) => failure.add_reason(reason),
+            }
+        }
+
+        Err(Error::ToolNotFound(failure))
+    }
+
+    /// 仓库没有 Release 时按 tag 解析：列出 tags，按版本约束选出 tag，
+    /// 再依次探测该 tag 下常见的 phar 位置（仓库内提交的 phar、同名 Release 资源）
+    async fn resolve_from_github_tags(&self, identifier: &ToolIdentifier) -> Result<ToolInfo> {
+        let client = self.http_client();
+        let mut failure = ResolutionFailure::new(&identifier.name);
+        for (owner, repo) in Self::github_owner_repo_variants(&identifier.name) {
+            let url = format!(
+                "{}/repos/{}/{}/tags?per_page=100",
+                self.hosts.github_api_base, owner, repo
+            );
+            let mut request = client.get(&url);
+            if let Some(token) = github_token_for(&url) {
+                request = request.bearer_auth(token);
+            }
+            let tags: Vec<GitHubTag> = match request.send().await {
+                Ok(response) if response.status().is_success() => {
+                    response.json().await.unwrap_or_default()
+                }
+                Ok(response) => {
+                    tracing::debug!("GitHub {}: HTTP {}", url, response.status());
+                    failure.transient |= response.status() != reqwest::StatusCode::NOT_FOUND;
+                    continue;
+                }
+                Err(e) => {
+                    tracing::debug!("GitHub {}: request failed: {}", url, e);
+                    failure.transient = true;
+                    continue;
+                }
+            };
+            if tags.is_empty() {
+                continue;
+            }
+
+            // /tags 按名称而非版本排序；按版本从新到旧排列后复用 Release 的匹配规则
+            let mut releases: Vec<GitHubRelease> = tags
+                .into_iter()
+                .map(|tag| GitHubRelease {
+                    tag_name: tag.name,
+                    prerelease: false,
+                    draft: false,
+                    assets: Vec::new(),
+                    body: None,
+                    published_at: None,
+                })
+                .collect();
+            releases.sort_by_cached_key(|r| {
+                std::cmp::Reverse(Version::parse(r.tag_name.trim_start_matches('v')).ok())
+            });
+            let Some(tag) = self.find_matching_github_release(&releases, identifier) else {
+                failure.add_reason(format!(
+                    "GitHub: {}/{} has no tag matching {}",
+                    owner,
+                    repo,
+                    self.describe_request(identifier)
+                ));
+                continue;
+            };
+
+            let base = &self.hosts.github_download_base;
+            let tag = &tag.tag_name;
+            let candidates = [
+                format!("{}/{}/{}/raw/{}/{}.phar", base, owner, repo, tag, repo),
+                format!(
+                    "{}/{}/{}/raw/{}/build/{}.phar",
+                    base, owner, repo, tag, repo
+                ),
+                format!("{}/{}/{}/raw/{}/bin/{}.phar", base, owner, repo, tag, repo),
+                format!(
+                    "{}/{}/{}/releases/download/{}/{}.phar",
+                    base, owner, repo, tag, repo
+                ),
+            ];
+            for download_url in candidates {
+                if Self::url_exists(&client, &download_url).await {
+                    tracing::debug!("GitHub tag {} of {}/{}: {}", tag, owner, repo, download_url);
+                    return Ok(ToolInfo {
+                        name: identifier.name.clone(),
+                        version: tag.trim_start_matches('v').to_string(),
+                        download_url,
+                        signature_url: None,
+                        hash: None,
+                    });
+                }
             }
+            failure.add_reason(format!(
+                "GitHub: {}/{} tag {} has no phar at the usual download paths",
+                owner, repo, tag
+            ));
         }
+        Err(Error::ToolNotFound(failure))
+    }
 
-        Err(Error::ToolNotFound(identifier.name.clone()))
+    /// 取工具在 GitHub 上介于 current（不含）与 latest（含）之间各版本的发布说明，按版本从新到旧排列。
+    /// 仓库按 github_owner_repo_variants 查找；两个版本需为 semver，找不到仓库或发布时返回空列表
+    pub async fn release_notes(&self, name: &str, current: &str, latest: &str) -> Vec<ReleaseNote> {
+        let (Ok(current), Ok(latest)) = (Version::parse(current), Version::parse(latest)) else {
+            return Vec::new();
+        };
+        let client = self.http_client();
+        for (owner, repo) in Self::github_owner_repo_variants(name) {
+            let url = format!(
+                "{}/repos/{}/{}/releases?per_page=100",
+                self.hosts.github_api_base, owner, repo
+            );
+            let mut request = client.get(&url);
+            if let Some(token) = github_token_for(&url) {
+                request = request.bearer_auth(token);
+            }
+            let releases: Vec<GitHubRelease> = match request.send().await {
+                Ok(response) if response.status().is_success() => {
+                    response.json().await.unwrap_or_default()
+                }
+                Ok(response) => {
+                    tracing::debug!("GitHub {}: HTTP {}", url, response.status());
+                    continue;
+                }
+                Err(e) => {
+                    tracing::debug!("GitHub {}: request failed: {}", url, e);
+                    continue;
+                }
+            };
+            if releases.is_empty() {
+                continue;
+            }
+            return Self::notes_between(releases, &current, &latest);
+        }
+        Vec::new()
+    }
+
+    fn notes_between(
+        releases: Vec<GitHubRelease>,
+        current: &Version,
+        latest: &Version,
+    ) -> Vec<ReleaseNote> {
+        let mut notes: Vec<(Version, ReleaseNote)> = releases
+            .into_iter()
+            .filter(|release| !release.draft)
+            .filter_map(|release| {
+                let version = Version::parse(release.tag_name.trim_start_matches('v')).ok()?;
+                (version > *current && version <= *latest).then(|| {
+                    let note = ReleaseNote {
+                        version: version.to_string(),
+                        published_at: release.published_at.map(|at| at.chars().take(10).collect()),
+                        body: release.body.unwrap_or_default().trim().to_string(),
+                    };
+                    (version, note)
+                })
+            })
+            .collect();
+        notes.sort_by(|a, b| b.0.cmp(&a.0));
+        notes.into_iter().map(|(_, note)| note).collect()
+    }
+
+    /// 请求单个 GitHub releases URL，返回匹配版本中带 .phar 资源的工具信息；
+    /// 失败时区分仓库不存在、请求本身失败（网络、HTTP 错误、限流）与仓库存在但没有合适的发布
+    async fn fetch_github_release(
+        &self,
+        client: &HttpClient,
+        url: String,
+        identifier: &ToolIdentifier,
+    ) -> std::result::Result<ToolInfo, ReleaseMiss> {
+        let mut request = client.get(&url);
+        if let Some(token) = github_token_for(&url) {
+            request = request.bearer_auth(token);
+        }
+        let response = match request.send().await {
+            Ok(response) => response,
+            Err(e) => {
+                tracing::debug!("GitHub {}: request failed: {}", url, e);
+                return Err(ReleaseMiss::Failed(None));
+            }
+        };
+        if !response.status().is_success() {
+            tracing::debug!("GitHub {}: HTTP {}", url, response.status());
+            let rate_limited = response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS
+                || (response.status() == reqwest::StatusCode::FORBIDDEN
+                    && response
+                        .headers()
+                        .get("x-ratelimit-remaining")
+                        .is_some_and(|v| v.as_bytes() == b"0"));
+            if response.status() == reqwest::StatusCode::NOT_FOUND {
+                return Err(ReleaseMiss::Missing);
+            }
+            return Err(ReleaseMiss::Failed(rate_limited.then(|| {
+                "GitHub: API rate limit exceeded (set GITHUB_TOKEN to raise the limit)".to_string()
+            })));
+        }
+        let repo = Self::repo_of_releases_url(&url);
+        let body: serde_json::Value = match response.json().await {
+            Ok(body) => body,
+            Err(e) => {
+                tracing::debug!("GitHub {}: response is not JSON ({})", url, e);
+                return Err(ReleaseMiss::Failed(None));
+            }
+        };
+        // 错误对象（如 {"message": "..."}）与空列表分开处理：前者说明请求本身出了问题
+        if !body.is_array() {
+            let message = body
+                .get("message")
+                .and_then(|m| m.as_str())
+                .unwrap_or("not a list of releases");
+            tracing::debug!(
+                "GitHub {}: error object instead of releases: {}",
+                url,
+                message
+            );
+            return Err(ReleaseMiss::Failed(Some(format!(
+                "GitHub: {} returned an error instead of releases: {}",
+                repo, message
+            ))));
+        }
+        let releases: Vec<GitHubRelease> = match serde_json::from_value(body) {
+            Ok(releases) => releases,
+            Err(e) => {
+                tracing::debug!("GitHub {}: unexpected release format ({})", url, e);
+                return Err(ReleaseMiss::Unsuitable(format!(
+                    "GitHub: {} returned releases in an unexpected format",
+                    repo
+                )));
+            }
+        };
+        if releases.is_empty() {
+            tracing::debug!("GitHub {}: repository has no releases", url);
+            return Err(ReleaseMiss::Unsuitable(format!(
+                "GitHub: {} has no releases",
+                repo
+            )));
+        }
+
+        // 找到合适的版本并查找 .phar 文件
+        let Some(release) = self.find_matching_github_release(&releases, identifier) else {
+            tracing::debug!(
+                "GitHub {}: none of {} releases matches",
+                url,
+                releases.len()
+            );
+            return Err(ReleaseMiss::Unsuitable(format!(
+                "GitHub: {} has no release matching {}",
+                repo,
+                self.describe_request(identifier)
+            )));
+        };
+        // 优先直接的 .phar，其次为内含 phar 的压缩包（.tar.gz / .zip）
+        let asset = release
+            .assets
+            .iter()
+            .find(|a| a.name.ends_with(".phar"))
+            .or_else(|| {
+                release
+                    .assets
+                    .iter()
+                    .find(|a| ArchiveKind::detect(&a.name).is_some())
+            });
+        let Some(asset) = asset else {
+            tracing::debug!(
+                "GitHub {}: release {} has no phar or archive asset",
+                url,
+                release.tag_name
+            );
+            return Err(ReleaseMiss::Unsuitable(format!(
+                "GitHub: {} release {} has no phar asset",
+                repo, release.tag_name
+            )));
+        };
+        tracing::debug!(
+            "GitHub {}: matched release {} (published {}) asset {}",
+            url,
+            release.tag_name,
+            release.published_at.as_deref().unwrap_or("unknown"),
+            asset.name
+        );
+        // 有 token 时经 API 地址下载，私有仓库的 browser_download_url 不接受 token
+        let download_url = if github_token().is_some() && !asset.url.is_empty() {
+            asset.url.clone()
+        } else {
+            asset.browser_download_url.clone()
+        };
+        Ok(ToolInfo {
+            name: identifier.name.clone(),
+            version: release.tag_name.trim_start_matches('v').to_string(),
+            download_url,
+            signature_url: self.find_signature_url(&release.assets),
+            hash: None,
+        })
+    }
+
+    /// 从 .../repos/<owner>/<repo>/releases 中取出 owner/repo
+    fn repo_of_releases_url(url: &str) -> &str {
+        url.split_once("/repos/")
+            .map(|(_, rest)| rest.trim_end_matches("/releases"))
+            .unwrap_or(url)
+    }
+
+    /// 用户直接给出的 phar 地址：顺带探测同目录的 .asc 签名与 .sha256（其次 .md5）校验和
+    async fn resolve_from_url(&self, identifier: &ToolIdentifier, url: &str) -> ToolInfo {
+        let client = self.http_client();
+        let signature_url = format!("{}.asc", url);
+        let signature_url = Self::url_exists(&client, &signature_url)
+            .await
+            .then_some(signature_url);
+        let mut hash = None;
+        for algorithm in [HashAlgorithm::Sha256, HashAlgorithm::Md5] {
+            let checksum_url = format!("{}.{}", url, algorithm.name());
+            hash = Self::fetch_checksum(&client, &checksum_url, algorithm).await;
+            if hash.is_some() {
+                break;
+            }
+        }
+        ToolInfo {
+            name: identifier.name.clone(),
+            version: identifier.version.clone().unwrap_or_default(),
+            download_url: url.to_string(),
+            signature_url,
+            hash,
+        }
+    }
+
+    async fn url_exists(client: &HttpClient, url: &str) -> bool {
+        if let Some(path) = file_url_path(url) {
+            return path.is_file();
+        }
+        client
+            .head(url)
+            .send()
+            .await
+            .is_ok_and(|r| r.status().is_success())
+    }
+
+    /// 读取校验和文件（格式为 "<hash>" 或 "<hash>  <filename>"），取第一段并加上算法前缀
+    async fn fetch_checksum(
+        client: &HttpClient,
+        url: &str,
+        algorithm: HashAlgorithm,
+    ) -> Option<String> {
+        let body = match file_url_path(url) {
+            Some(path) => tokio::fs::read_to_string(path).await.ok()?,
+            None => {
+                let response = client.get(url).send().await.ok()?;
+                if !response.status().is_success() {
+                    return None;
+                }
+                response.text().await.ok()?
+            }
+        };
+        body.split_whitespace()
+            .next()
+            .filter(|h| parse_hash(h).is_ok_and(|(parsed, _)| parsed == algorithm))
+            .filter(|h| h.chars().all(|c| c.is_ascii_hexdigit()))
+            .map(|h| algorithm.tag(&h.to_ascii_lowercase()))
     }
 
     async fn resolve_from_direct_url(&self, identifier: &ToolIdentifier) -> Result<ToolInfo> {
         let (owner, repo) = Self::github_owner_repo(&identifier.name);
         // 尝试常见的直接下载 URL：owner/repo，下载文件名多为 repo.phar 或 vendor-repo.phar
+        let base = &self.hosts.github_download_base;
         let direct_urls = vec![
             format!(
-                "https://github.com/{}/{}/releases/latest/download/{}.phar",
-                owner, repo, repo
+                "{}/{}/{}/releases/latest/download/{}.phar",
+                base, owner, repo, repo
             ),
             format!(
-                "https://github.com/{}/{}/releases/latest/download/{}-{}.phar",
-                owner, repo, owner, repo
+                "{}/{}/{}/releases/latest/download/{}-{}.phar",
+                base, owner, repo, owner, repo
             ),
             format!(
-                "https://github.com/{}/{}/releases/latest/download/{}.phar",
+                "{}/{}/{}/releases/latest/download/{}.phar",
+                base,
                 owner,
                 repo,
                 identifier.name.replace('/', "-")
             ),
         ];
 
+        let client = self.http_client();
         for url in direct_urls {
-            let client = reqwest::Client::new();
-            let response = client.head(&url).send().await?;
-
-            if response.status().is_success() {
+            let exists = Self::url_exists(&client, &url).await;
+            tracing::debug!(
+                "Direct URL {}: {}",
+                url,
+                if exists { "found" } else { "not found" }
+            );
+            if exists {
                 return Ok(ToolInfo {
                     name: identifier.name.clone(),
                     version: "latest".to_string(),
@@ -456,44 +1468,62 @@ impl ToolResolver {
             }
         }
 
-        Err(Error::ToolNotFound(identifier.name.clone()))
+        Err(Error::ToolNotFound(ResolutionFailure::new(
+            &identifier.name,
+        )))
     }
 
+    /// 选出匹配的 Packagist 版本，返回 versions 中的键。键可带前缀 v（如 v1.2.0）。
+    /// 同一版本出现多次（如 1.2.0 为 path dist、v1.2.0 为 zip dist）时按确定的顺序取舍：
+    /// 运行工具时优先 path（phar），prefer_library 时优先 zip，再按键名排序
     fn find_matching_version(
         &self,
         versions: &HashMap<String, PackagistVersionInfo>,
         identifier: &ToolIdentifier,
     ) -> Result<String> {
-        let mut candidate_versions: Vec<Version> = versions
+        let dist_rank = |key: &String| {
+            let is_phar = versions[key].dist.dist_type == "path";
+            is_phar == self.prefer_library
+        };
+        let mut all: Vec<(Version, &String)> = versions
             .keys()
-            .filter_map(|v| Version::parse(v).ok())
+            .filter_map(|key| Some((Version::parse(key.trim_start_matches('v')).ok()?, key)))
+            .collect();
+        all.sort_by(|a, b| {
+            b.0.cmp(&a.0)
+                .then_with(|| dist_rank(a.1).cmp(&dist_rank(b.1)))
+                .then_with(|| a.1.cmp(b.1))
+        });
+        // 约束与 latest 只考虑不低于 min_stability 的版本；指定具体版本时不受稳定性限制，与 GitHub 来源一致
+        let candidates: Vec<&(Version, &String)> = all
+            .iter()
+            .filter(|(v, _)| Stability::of(v) >= self.min_stability)
             .collect();
-
-        candidate_versions.sort();
-        candidate_versions.reverse();
 
         if let Some(constraint) = &identifier.version_constraint {
-            for version in &candidate_versions {
-                if constraint.matches(version) {
-                    return Ok(version.to_string());
+            for (version, key) in &all {
+                let acceptable = Stability::of(version) >= self.min_stability
+                    || Self::names_prerelease(constraint, version);
+                if acceptable && self.constraint_matches(constraint, version) {
+                    return Ok((*key).clone());
                 }
             }
         } else if identifier.version.as_deref() == Some("latest") {
-            if let Some(latest) = candidate_versions.first() {
-                return Ok(latest.to_string());
+            if let Some((_, key)) = candidates.first() {
+                return Ok((*key).clone());
             }
         } else if let Some(version_str) = &identifier.version {
-            if let Ok(version) = Version::parse(version_str) {
-                if candidate_versions.contains(&version) {
-                    return Ok(version.to_string());
+            if let Ok(wanted) = Version::parse(version_str.trim_start_matches('v')) {
+                if let Some((_, key)) = all.iter().find(|(v, _)| *v == wanted) {
+                    return Ok((*key).clone());
                 }
             } else if versions.contains_key(version_str) {
                 return Ok(version_str.clone());
             }
         } else {
             // 没有版本约束，使用最新版本
-            if let Some(latest) = candidate_versions.first() {
-                return Ok(latest.to_string());
+            if let Some((_, key)) = candidates.first() {
+                return Ok((*key).clone());
             }
         }
 
@@ -507,32 +1537,70 @@ impl ToolResolver {
         releases: &'a [GitHubRelease],
         identifier: &ToolIdentifier,
     ) -> Option<&'a GitHubRelease> {
-        for release in releases {
+        for release in releases.iter().filter(|r| !r.draft) {
             let version_str = release.tag_name.trim_start_matches('v');
+            let parsed = Version::parse(version_str).ok();
+            // GitHub 标记为预发布但 tag 看起来是正式版（如 v2.0.0）时，按最低稳定性处理，只有 --pre 才会选中
+            let stability = match &parsed {
+                Some(version) if version.pre.is_empty() && release.prerelease => Stability::Dev,
+                Some(version) => Stability::of(version),
+                None if release.prerelease => Stability::Dev,
+                None => Stability::Stable,
+            };
+            // 指定具体版本时不受稳定性限制，其余情况跳过低于 min_stability 的发布
+            let acceptable = stability >= self.min_stability;
 
             if let Some(constraint) = &identifier.version_constraint {
-                if let Ok(version) = Version::parse(version_str) {
-                    if constraint.matches(&version) {
+                if let Some(version) = &parsed {
+                    let acceptable = acceptable || Self::names_prerelease(constraint, version);
+                    if acceptable && self.constraint_matches(constraint, version) {
                         return Some(release);
                     }
                 }
             } else if identifier.version.as_deref() == Some("latest") {
-                return releases.first();
+                if acceptable {
+                    return Some(release);
+                }
             } else if let Some(version_str) = &identifier.version {
                 if release.tag_name == *version_str
                     || release.tag_name == format!("v{}", version_str)
                 {
                     return Some(release);
                 }
-            } else {
-                // 没有版本约束，使用最新版本
-                return releases.first();
+            } else if acceptable {
+                // 没有版本约束，使用满足稳定性要求的最新版本
+                return Some(release);
             }
         }
 
         None
     }
 
+    /// 约束是否逐字写出了这个预发布版本（如 tool@2.0.0-beta1、tool@=2.0.0-beta1），
+    /// 这时视为指定具体版本，不受 min_stability 限制
+    fn names_prerelease(constraint: &VersionReq, version: &Version) -> bool {
+        !version.pre.is_empty()
+            && constraint.comparators.iter().any(|c| {
+                c.major == version.major
+                    && c.minor == Some(version.minor)
+                    && c.patch == Some(version.patch)
+                    && c.pre == version.pre
+            })
+    }
+
+    /// semver 的约束默认不匹配预发布版本；放宽了 min_stability 时，预发布版按其正式版号参与约束匹配
+    fn constraint_matches(&self, constraint: &VersionReq, version: &Version) -> bool {
+        if constraint.matches(version) {
+            return true;
+        }
+        if version.pre.is_empty() || self.min_stability == Stability::Stable {
+            return false;
+        }
+        let mut release = version.clone();
+        release.pre = semver::Prerelease::EMPTY;
+        constraint.matches(&release)
+    }
+
     fn find_signature_url(&self, assets: &[GitHubAsset]) -> Option<String> {
         assets
             .iter()
@@ -547,7 +1615,7 @@ mod tests {
 
     #[test]
     fn parse_caret_version_sets_constraint() {
-        let resolver = ToolResolver::new();
+        let resolver = ToolResolver::default();
         let id = resolver.parse_identifier("php-cs-fixer@^3.14").unwrap();
         assert!(
             id.version_constraint.is_some(),
@@ -555,4 +1623,107 @@ mod tests {
             id.version
         );
     }
+
+    #[test]
+    fn parse_bin_selector() {
+        let resolver = ToolResolver::default();
+        let id = resolver
+            .parse_identifier("phpunit/phpunit:phpunit-watch@^10")
+            .unwrap();
+        assert_eq!(id.name, "phpunit/phpunit");
+        assert_eq!(id.bin.as_deref(), Some("phpunit-watch"));
+        assert!(id.version_constraint.is_some());
+
+        assert!(resolver.parse_identifier("phpunit/phpunit:").is_err());
+        assert!(resolver.parse_identifier(":phpunit").is_err());
+    }
+
+    #[test]
+    fn packagist_require_dev_tolerates_non_object_values() {
+        let parse = |require_dev: &str| {
+            let json = format!(
+                r#"{{"dist":{{"url":"u","type":"zip"}},"require-dev":{}}}"#,
+                require_dev
+            );
+            serde_json::from_str::<PackagistVersionInfo>(&json)
+                .unwrap()
+                .require_dev
+        };
+        assert_eq!(parse(r#"{"phpunit/phpunit":"^10"}"#).len(), 1);
+        assert!(parse("[]").is_empty());
+        assert!(parse(r#""__unset""#).is_empty());
+    }
+
+    #[test]
+    fn edit_distance_counts_single_character_edits() {
+        assert_eq!(edit_distance("phpstn", "phpstan"), 1);
+        assert_eq!(edit_distance("phpstan", "phpstan"), 0);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn stability_classifies_prerelease_tags() {
+        let of = |v: &str| Stability::of(&Version::parse(v).unwrap());
+        assert_eq!(of("1.2.0"), Stability::Stable);
+        assert_eq!(of("1.2.0-RC1"), Stability::Rc);
+        assert_eq!(of("1.2.0-beta.2"), Stability::Beta);
+        assert_eq!(of("1.2.0-alpha"), Stability::Alpha);
+        assert_eq!(of("1.2.0-dev"), Stability::Dev);
+        assert_eq!(of("1.2.0-b3"), Stability::Beta);
+        assert_eq!(of("1.2.0-a1"), Stability::Alpha);
+        assert_eq!(of("1.2.0-build.5"), Stability::Dev);
+        assert_eq!(of("1.2.0-abc"), Stability::Dev);
+        // beta 允许 RC 与 beta，排除 alpha/dev
+        assert!(of("1.2.0-RC1") >= Stability::Beta);
+        assert!(of("1.2.0-alpha") < Stability::Beta);
+    }
+
+    #[test]
+    fn github_token_is_only_sent_to_github_hosts() {
+        let token = || Some("secret".to_string());
+        assert_eq!(
+            scope_token_to_github(
+                token(),
+                "https://api.github.com/repos/a/b/releases/assets/1"
+            ),
+            token()
+        );
+        assert_eq!(
+            scope_token_to_github(
+                token(),
+                "https://github.com/a/b/releases/download/v1/b.phar"
+            ),
+            token()
+        );
+        assert!(
+            scope_token_to_github(token(), "https://mirror.example.com/github.com/b.phar")
+                .is_none()
+        );
+        assert!(scope_token_to_github(token(), "https://github.com.evil.test/b.phar").is_none());
+    }
+
+    #[test]
+    fn parse_url_identifier_uses_file_name() {
+        let resolver = ToolResolver::default();
+        let id = resolver
+            .parse_identifier("https://example.com/tools/mytool.phar?token=1")
+            .unwrap();
+        assert_eq!(id.name, "mytool");
+        assert!(id.version.unwrap().starts_with("url-"));
+        assert!(id.url.is_some());
+    }
+
+    #[test]
+    fn packagist_dist_url_is_pinned_to_reference() {
+        let dist = PackagistDist {
+            url: "https://api.github.com/repos/acme/tool/zipball/main".to_string(),
+            dist_type: "path".to_string(),
+            reference: Some("0123abcd".to_string()),
+        };
+        assert_eq!(
+            dist.stable_url(),
+            "https://api.github.com/repos/acme/tool/zipball/0123abcd"
+        );
+    }
 }
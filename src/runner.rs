@@ -1,12 +1,79 @@
-use crate::cache::CacheManager;
+use crate::archive::{self, ArchiveKind};
+use crate::cache::{sanitize_component, CacheEntry, CacheManager};
 use crate::composer;
 use crate::config::Config;
-use crate::download::Downloader;
-use crate::error::{Error, Result};
+use crate::download::{Downloader, NO_VERIFY_TLS_ENV};
+use crate::error::{Error, ResolutionFailure, Result};
 use crate::executor::Executor;
-use crate::resolver::{ResolvedTool, ToolIdentifier, ToolResolver};
-use crate::security::SecurityManager;
-use std::path::PathBuf;
+use crate::lockfile::{LockFile, LockedTool, LOCK_FILE_NAME};
+use crate::manifest::ProjectManifest;
+use crate::progress::{NoProgress, ProgressObserver};
+use crate::resolver::{
+    ReleaseNote, ResolvedTool, ResolverConfig, Stability, ToolIdentifier, ToolInfo, ToolResolver,
+};
+use crate::security::{is_truthy, HashCheck, SecurityManager};
+use crate::units;
+use crate::{RunOutcome, RunTimings, ToolOptions, ToolSource};
+use futures::stream::{self, StreamExt};
+use serde::Serialize;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// 自动 TTL 清理的最小间隔（秒）
+const GC_INTERVAL: u64 = 60 * 60;
+
+/// 解析失败（工具不存在）的结果缓存秒数，期间重复查询直接返回 ToolNotFound
+const NEGATIVE_CACHE_TTL: u64 = 5 * 60;
+
+/// 内置的默认 PHP ini：这些工具在默认 memory_limit 下常因内存不足崩溃
+const BUILTIN_PHP_INI: &[(&str, &[&str])] = &[
+    ("phpstan", &["memory_limit=-1"]),
+    ("psalm", &["memory_limit=-1"]),
+    ("rector", &["memory_limit=-1"]),
+];
+
+/// stdin 非终端时自动追加 --no-interaction 的工具：基于 Symfony Console、已知接受该选项的工具。
+/// PHPUnit、phpcs、psalm 等会把它当作未知选项报错，不在此列
+const NO_INTERACTION_TOOLS: &[&str] = &["composer", "rector", "php-cs-fixer", "phpstan"];
+
+/// `phpx outdated` 中的一行：缓存中的版本与可用的最新版本
+#[derive(Debug, Clone, Serialize)]
+pub struct OutdatedTool {
+    pub tool: String,
+    pub current: String,
+    /// 解析失败时为 None
+    pub latest: Option<String>,
+    /// 锁文件中记录的约束（若有），最新版本在该约束内解析
+    pub constraint: Option<String>,
+    pub outdated: bool,
+    /// current 之后到 latest 的发布说明（outdated --changelog），从新到旧
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub changelog: Vec<ReleaseNote>,
+}
+
+/// `phpx verify` 的结果：单独下载的产物及其签名、哈希校验情况
+#[derive(Debug, Clone, Serialize)]
+pub struct VerifyReport {
+    pub tool: String,
+    pub version: String,
+    pub url: String,
+    /// 下载产物的 sha256，便于与发布页公布的值人工比对
+    pub sha256: String,
+    /// 解析源给出了哈希时的比对结果
+    pub hash: Option<HashCheck>,
+    pub signature_url: Option<String>,
+    /// 签名的签发者；GPG 验证尚未实现，目前总为 None
+    pub signer: Option<String>,
+}
+
+impl VerifyReport {
+    /// 哈希不匹配即失败；没有可比对的哈希时视为通过
+    pub fn passed(&self) -> bool {
+        self.hash.as_ref().is_none_or(HashCheck::matches)
+    }
+}
 
 pub struct Runner {
     config: Config,
@@ -15,28 +82,97 @@ pub struct Runner {
     resolver: ToolResolver,
     security_manager: SecurityManager,
     executor: Executor,
+    progress: Arc<dyn ProgressObserver>,
+    /// 当前这次运行各阶段的耗时
+    timings: RunTimings,
+    /// 配置的 cache_dir 不可写时本次运行改用的临时缓存目录，Runner 销毁时删除；
+    /// 声明在 cache_manager 之后，保证 cache.json 先写回、目录后删除
+    temp_cache_dir: Option<tempfile::TempDir>,
+}
+
+/// 执行 f 并把耗时累加到 slot
+fn timed<T>(slot: &mut Duration, f: impl FnOnce() -> T) -> T {
+    let started = Instant::now();
+    let result = f();
+    *slot += started.elapsed();
+    result
 }
 
 impl Runner {
     /// 使用可选配置文件路径创建 Runner；无则使用默认路径，加载失败则回退默认配置
     pub fn new(config_path: Option<PathBuf>) -> Result<Self> {
-        let config =
-            Config::load(config_path).map_err(|e| crate::error::Error::Config(e.to_string()))?;
+        let config = Config::load(config_path)?;
+        Self::from_config(config)
+    }
+
+    /// 使用已加载（可能已被命令行覆盖）的配置创建 Runner
+    pub fn from_config(mut config: Config) -> Result<Self> {
+        let mut temp_cache_dir = None;
+        if !crate::cache::is_writable_dir(&config.cache_dir) {
+            // 随机目录名，避免其他本地用户抢先在可预测的路径上创建目录或符号链接
+            let fallback = tempfile::Builder::new().prefix("phpx-cache-").tempdir()?;
+            tracing::warn!(
+                "Cache directory {} is not writable; using a temporary cache at {} for this run, nothing will be kept",
+                config.cache_dir.display(),
+                fallback.path().display()
+            );
+            config.cache_dir = fallback.path().to_path_buf();
+            temp_cache_dir = Some(fallback);
+        }
         let skip_verify = config.skip_verify;
-        let mut cache_manager = CacheManager::new(config.cache_dir.clone())?;
-        // 按配置 TTL 清理过期缓存（每次创建 Runner 时执行一次）
-        cache_manager.cleanup_old_entries(config.cache_ttl)?;
+        let hash_algorithm = config.hash_algorithm;
+        let no_verify_tls = Self::tls_verification_disabled(
+            &config,
+            std::env::var(NO_VERIFY_TLS_ENV).ok().as_deref(),
+        );
+        let max_download_size = config.max_download_size;
+        let user_agent = config.user_agent().to_string();
+        let executor = Executor::new()
+            .with_ignore_project_php(config.ignore_project_php)
+            .with_min_php_version(&config.min_php_version, config.strict_php_version)
+            .with_search_depth(config.project_search_depth);
+        let connect_timeout = Duration::from_secs(config.connect_timeout);
+        let network_timeout = Duration::from_secs(config.network_timeout);
+        let resolver = ToolResolver::new(ResolverConfig::from(&config))
+            .with_timeouts(connect_timeout, network_timeout)
+            .with_min_stability(config.min_stability)
+            .with_source(config.source)
+            .with_health_check(config.mirror_health_check)
+            .with_user_agent(config.user_agent())
+            .with_accept_invalid_certs(no_verify_tls);
+        let mut cache_manager =
+            CacheManager::new(config.cache_dir.clone())?.with_dedupe(config.dedupe_phars);
+        // 按配置 TTL 清理过期缓存；至多每小时执行一次，避免每次调用都扫描并重写 cache.json
+        cache_manager.cleanup_if_due(config.cache_ttl, GC_INTERVAL)?;
 
         Ok(Self {
             config,
             cache_manager,
-            downloader: Downloader::new(),
-            resolver: ToolResolver::new(),
-            security_manager: SecurityManager::new(skip_verify),
-            executor: Executor::new(),
+            downloader: Downloader::with_timeouts(connect_timeout, network_timeout)
+                .with_max_size(max_download_size)
+                .with_user_agent(user_agent)
+                .with_accept_invalid_certs(no_verify_tls),
+            resolver,
+            security_manager: SecurityManager::new(skip_verify).with_hash_algorithm(hash_algorithm),
+            executor,
+            progress: Arc::new(NoProgress),
+            timings: RunTimings::default(),
+            temp_cache_dir,
         })
     }
 
+    /// 本次运行是否因 cache_dir 不可写而使用临时缓存
+    pub fn uses_temporary_cache(&self) -> bool {
+        self.temp_cache_dir.is_some()
+    }
+
+    /// 设置解析、下载与 Composer 安装的进度回调（默认不报告）
+    pub fn with_progress(mut self, progress: Arc<dyn ProgressObserver>) -> Self {
+        self.downloader = std::mem::take(&mut self.downloader).with_progress(progress.clone());
+        self.progress = progress;
+        self
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub async fn run_tool(
         &mut self,
@@ -48,77 +184,326 @@ impl Runner {
         php_path: Option<&PathBuf>,
         no_local: bool,
         no_interaction: bool,
-    ) -> Result<()> {
+        interaction: bool,
+        no_default_args: bool,
+    ) -> Result<RunOutcome> {
+        let options = ToolOptions {
+            clear_cache,
+            no_cache,
+            skip_verify,
+            php: php_path.cloned(),
+            no_local,
+            no_interaction,
+            interaction,
+            no_default_args,
+            ..Default::default()
+        };
+        self.run_tool_with_options(tool_identifier, args, &options)
+            .await
+    }
+
+    /// 按选项运行工具，返回实际执行的版本与退出码；工具以非零码退出不视为错误
+    pub async fn run_tool_with_options(
+        &mut self,
+        tool_identifier: &str,
+        args: &[String],
+        options: &ToolOptions,
+    ) -> Result<RunOutcome> {
         tracing::info!("Running tool: {}", tool_identifier);
+        self.timings = RunTimings::default();
+        let skip_verify = options.skip_verify;
+        if options.strict_php {
+            self.executor.set_strict_php_version(true);
+        }
+        let mut env = match &options.env_file {
+            Some(path) => crate::dotenv::load(path)?,
+            None => Vec::new(),
+        };
+        env.extend(options.env.iter().cloned());
+        self.executor.set_env(env);
+        if options.ignore_project_php {
+            self.executor.set_ignore_project_php(true);
+        }
+        if options.no_suggest {
+            self.resolver.set_suggestions(false);
+        }
+        if options.pre {
+            self.resolver.set_min_stability(Stability::Dev);
+        }
+        if let Some(source) = options.source {
+            self.resolver.set_source(source);
+        }
+
+        // stdin 非终端（如 CI）时视为非交互：对已知接受 --no-interaction 的工具自动追加，避免提示时永久挂起；
+        // --interaction 可关闭
+        let auto_no_interaction = !options.interaction && !std::io::stdin().is_terminal();
+        let no_interaction = options.no_interaction || auto_no_interaction;
+
+        // 标识符是本地 phar 文件时直接执行，不经过解析与缓存
+        if let Some(phar_path) = Self::local_phar_path(tool_identifier) {
+            tracing::info!("Running local phar: {}", phar_path.display());
+            let mut effective_args = args.to_vec();
+            let tool = phar_path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .unwrap_or_default();
+            push_no_interaction(
+                &mut effective_args,
+                tool,
+                options.no_interaction,
+                auto_no_interaction,
+            );
+            let effective_php = options
+                .php
+                .clone()
+                .or_else(|| self.config.default_php_path.clone());
+            let exit_code = timed(&mut self.timings.execute, || {
+                self.executor.execute_phar(
+                    &phar_path,
+                    &effective_args,
+                    effective_php.as_ref(),
+                    &options.php_args,
+                )
+            })?;
+            return Ok(RunOutcome {
+                version: "local".to_string(),
+                exit_code,
+                source: ToolSource::LocalFile,
+                timings: self.timings,
+            });
+        }
+
+        // 解析工具标识符
+        let mut identifier = self.resolver.parse_identifier(tool_identifier)?;
 
-        // 需要向子工具追加 --no-interaction 时，在参数末尾加上
-        let effective_args: Vec<String> = if no_interaction {
-            let mut a = args.to_vec();
-            a.push("--no-interaction".to_string());
-            a
+        // 配置中该工具的默认参数放在用户参数之前（--no-default-args 可跳过）
+        let mut effective_args: Vec<String> = if options.no_default_args {
+            Vec::new()
         } else {
-            args.to_vec()
+            self.config
+                .default_args
+                .get(&identifier.name)
+                .cloned()
+                .unwrap_or_default()
         };
+        effective_args.extend_from_slice(args);
+        push_no_interaction(
+            &mut effective_args,
+            &identifier.name,
+            options.no_interaction,
+            auto_no_interaction,
+        );
         let effective_args: &[String] = &effective_args;
 
+        // 默认 PHP ini（如 phpstan 的 memory_limit=-1）以 -d 传入，用户的 --php-arg 放在其后以便覆盖
+        let mut php_args = if options.no_default_php_args {
+            Vec::new()
+        } else {
+            self.default_php_ini(&identifier.name)
+                .into_iter()
+                .map(|setting| format!("-d{}", setting))
+                .collect()
+        };
+        php_args.extend_from_slice(&options.php_args);
+
         // 命令行 --php 优先，否则使用配置中的 default_php_path（克隆避免长期借用 self）
-        let effective_php = php_path
-            .cloned()
+        let effective_php = options
+            .php
+            .clone()
             .or_else(|| self.config.default_php_path.clone());
 
-        // 解析工具标识符
-        let identifier = self.resolver.parse_identifier(tool_identifier)?;
+        // 未指定版本时，若项目 phpx.lock 锁定了该工具则使用锁定版本，不再解析 latest
+        let locked = if identifier.version.is_none()
+            && identifier.version_constraint.is_none()
+            && identifier.url.is_none()
+        {
+            Self::find_locked_tool(&identifier.name)
+        } else {
+            None
+        };
+        if let Some(ref locked) = locked {
+            tracing::info!(
+                "Using locked version {}@{} from {}",
+                identifier.name,
+                locked.version,
+                LOCK_FILE_NAME
+            );
+            identifier.version = Some(locked.version.clone());
+        } else if identifier.version.is_none()
+            && identifier.version_constraint.is_none()
+            && identifier.url.is_none()
+        {
+            // 没有锁定版本时使用项目清单 [tools] 中声明的版本或约束
+            if let Some((version, manifest_path)) = Self::find_manifest_version(&identifier.name) {
+                tracing::info!(
+                    "Using {}@{} from {}",
+                    identifier.name,
+                    version,
+                    manifest_path.display()
+                );
+                let pinned = self
+                    .resolver
+                    .parse_identifier(&format!("{}@{}", identifier.name, version))?;
+                identifier.version = pinned.version;
+                identifier.version_constraint = pinned.version_constraint;
+            }
+        }
 
-        // 检查本地项目是否有该工具
-        if !no_local {
-            if let Some(local_path) = self.find_local_tool(&identifier.name) {
+        // 检查本地项目是否有该工具（直接给出 URL 或需要另存 phar 时不使用本地工具）
+        if !options.no_local && identifier.url.is_none() && options.keep_download.is_none() {
+            let local_name = identifier.bin.as_deref().unwrap_or(&identifier.name);
+            let local = self.find_local_tool(local_name).filter(|(path, source)| {
+                Self::local_satisfies(&identifier, path, local_name, source)
+            });
+            let skip_local = local.is_some()
+                && (options.prefer_cache || self.config.prefer_cache)
+                && !options.prefer_local
+                && self.has_cached_copy(&identifier, options.with_dev);
+            if skip_local {
+                tracing::debug!(
+                    "{} is cached by phpx, ignoring the local copy \
+                     (prefer_cache; pass --prefer-local to change)",
+                    identifier.name
+                );
+            }
+            if let Some((local_path, source)) = local.filter(|_| !skip_local) {
                 tracing::info!("Found local tool at: {:?}", local_path);
-                return self
-                    .executor
-                    .execute_phar(&local_path, effective_args, effective_php.as_ref());
+                tracing::debug!(
+                    "Using local {} {} from {} instead of phpx's copy \
+                     (pass --prefer-cache or --no-local to change)",
+                    local_name,
+                    Self::local_tool_version(&local_path, local_name)
+                        .unwrap_or_else(|| "(unknown version)".to_string()),
+                    source
+                );
+                let exit_code = timed(&mut self.timings.execute, || {
+                    self.executor.execute_phar(
+                        &local_path,
+                        effective_args,
+                        effective_php.as_ref(),
+                        &php_args,
+                    )
+                })?;
+                return Ok(RunOutcome {
+                    version: "local".to_string(),
+                    exit_code,
+                    source,
+                    timings: self.timings,
+                });
             }
         }
 
         // 清理缓存（如果需要）
-        if clear_cache {
+        if options.clear_cache {
             self.cache_manager.remove_entry(&identifier.name, None)?;
         }
 
-        // 查找缓存中的工具
-        if !no_cache {
-            if let Some(version) = self.get_tool_version(&identifier).await? {
-                let entry_owned = self
-                    .cache_manager
-                    .get_entry(&identifier.name, &version)
-                    .cloned();
+        // 查找缓存中的工具（--force 时跳过，下载结果会覆盖原条目）
+        if !options.no_cache && !options.force {
+            let reuse_cached =
+                (options.reuse_cached || self.config.reuse_cached) && !options.prefer_newer;
+            if let Some(version) = self
+                .get_tool_version(&identifier, reuse_cached, options.with_dev)
+                .await?
+            {
+                let entry_owned = self.cached_entry(&identifier.name, &version, options.with_dev);
                 if let Some(cache_entry) = entry_owned {
                     // 用户指定了具体版本或约束时，不得使用 version 为 "latest" 的缓存，否则会跑错版本
                     let user_wants_specific_version = identifier.version_constraint.is_some()
-                        || identifier
-                            .version
-                            .as_deref()
-                            .map_or(false, |v| v != "latest");
-                    if user_wants_specific_version && cache_entry.version == "latest" {
+                        || identifier.version.as_deref().is_some_and(|v| v != "latest");
+                    let stale_latest =
+                        user_wants_specific_version && cache_entry.version == "latest";
+                    let started = Instant::now();
+                    let verified = if stale_latest {
+                        Ok(())
+                    } else {
+                        self.verify_cached_tool(&cache_entry, skip_verify)
+                    };
+                    self.timings.verify += started.elapsed();
+                    if stale_latest {
                         // 视为缓存未命中，继续走解析与下载
-                    } else if self.verify_cached_tool(&cache_entry, skip_verify).is_ok() {
+                    } else if let Err(e) = verified {
+                        // 缓存损坏（文件缺失、大小或哈希不符）：删除该条目后重新解析安装，无需手动 cache clean
+                        tracing::warn!(
+                            "Cached {}@{} is corrupted ({}), re-installing",
+                            identifier.name,
+                            version,
+                            e
+                        );
+                        self.cache_manager
+                            .remove_entry(&identifier.name, Some(&cache_entry.version))?;
+                    } else if let Some(reason) =
+                        self.composer_php_mismatch(&cache_entry, effective_php.as_ref())
+                    {
+                        // 换了 PHP 后旧安装不再满足要求：交互时询问是否重新安装，否则报错并提示 --reinstall
+                        if no_interaction || !confirm_reinstall(&identifier.name, &version, &reason)
+                        {
+                            return Err(Error::StaleComposerInstall {
+                                tool: identifier.name.clone(),
+                                version,
+                                reason,
+                            });
+                        }
+                        tracing::info!("Re-installing {}@{}", identifier.name, version);
+                        self.cache_manager
+                            .remove_entry(&identifier.name, Some(&cache_entry.version))?;
+                    } else {
                         tracing::info!("Using cached tool: {}@{}", identifier.name, version);
+                        tracing::debug!("Tool source: {}", ToolSource::Cache);
+                        if cache_entry.is_composer && options.keep_download.is_some() {
+                            return Err(Self::no_phar_to_keep(&identifier.name));
+                        }
                         if cache_entry.is_composer {
-                            let bin_path = cache_entry
-                                .file_path
-                                .join("vendor")
-                                .join("bin")
-                                .join(cache_entry.bin_name.as_deref().unwrap_or("tool"));
-                            return self.executor.execute_script(
-                                &bin_path,
-                                effective_args,
-                                effective_php.as_ref(),
-                            );
+                            let default_bin = cache_entry.bin_name.as_deref().unwrap_or("tool");
+                            let bin_path = match identifier.bin.as_deref() {
+                                Some(bin) => {
+                                    // 旧缓存条目没有记录全部 bin，只能确认默认 bin
+                                    let available = if cache_entry.bin_names.is_empty() {
+                                        vec![default_bin.to_string()]
+                                    } else {
+                                        cache_entry.bin_names.clone()
+                                    };
+                                    composer::check_bin(&identifier.name, bin, &available)?;
+                                    cache_entry.file_path.join("vendor").join("bin").join(bin)
+                                }
+                                None => cache_entry
+                                    .file_path
+                                    .join("vendor")
+                                    .join("bin")
+                                    .join(default_bin),
+                            };
+                            let exit_code = timed(&mut self.timings.execute, || {
+                                self.executor.execute_script(
+                                    &bin_path,
+                                    effective_args,
+                                    effective_php.as_ref(),
+                                    &php_args,
+                                )
+                            })?;
+                            return Ok(RunOutcome {
+                                version,
+                                exit_code,
+                                source: ToolSource::Cache,
+                                timings: self.timings,
+                            });
                         } else {
-                            return self.executor.execute_phar(
-                                &cache_entry.file_path,
-                                effective_args,
-                                effective_php.as_ref(),
-                            );
+                            if let Some(dest) = &options.keep_download {
+                                Self::keep_phar(&cache_entry.file_path, dest, &identifier.name)?;
+                            }
+                            let exit_code = timed(&mut self.timings.execute, || {
+                                self.executor.execute_phar(
+                                    &cache_entry.file_path,
+                                    effective_args,
+                                    effective_php.as_ref(),
+                                    &php_args,
+                                )
+                            })?;
+                            return Ok(RunOutcome {
+                                version,
+                                exit_code,
+                                source: ToolSource::Cache,
+                                timings: self.timings,
+                            });
                         }
                     }
                 }
@@ -126,74 +511,677 @@ impl Runner {
         }
 
         // 解析并执行：Phar 下载后执行，Composer 在隔离目录安装后执行 vendor/bin
-        let resolved = self.resolver.resolve_tool(&identifier).await?;
+        // 锁定了下载地址的 phar 直接使用锁定信息（含哈希），无需再访问解析源
+        let locked_phar = locked.and_then(|l| {
+            l.download_url.map(|download_url| ToolInfo {
+                name: identifier.name.clone(),
+                version: l.version,
+                download_url,
+                signature_url: None,
+                hash: l.hash,
+            })
+        });
+        let resolved = match locked_phar {
+            Some(tool_info) => ResolvedTool::Phar(tool_info),
+            None => {
+                self.resolve_tool_cached(&identifier, options.no_cache || options.force)
+                    .await?
+            }
+        };
         match resolved {
             ResolvedTool::Phar(tool_info) => {
+                if let Some(bin) = &identifier.bin {
+                    return Err(Error::InvalidToolIdentifier(format!(
+                        "{} is distributed as a phar; ':{}' only applies to Composer packages",
+                        identifier.name, bin
+                    )));
+                }
                 let downloaded_path = self
                     .download_and_cache_tool(&tool_info, skip_verify)
                     .await?;
-                self.executor
-                    .execute_phar(&downloaded_path, effective_args, effective_php.as_ref())
+                self.record_constraint(&identifier, &tool_info.version);
+                tracing::debug!("Tool source: {}", ToolSource::DownloadedPhar);
+                if let Some(dest) = &options.keep_download {
+                    Self::keep_phar(&downloaded_path, dest, &identifier.name)?;
+                }
+                let exit_code = timed(&mut self.timings.execute, || {
+                    self.executor.execute_phar(
+                        &downloaded_path,
+                        effective_args,
+                        effective_php.as_ref(),
+                        &php_args,
+                    )
+                })?;
+                Ok(RunOutcome {
+                    version: tool_info.version,
+                    exit_code,
+                    source: ToolSource::DownloadedPhar,
+                    timings: self.timings,
+                })
+            }
+            ResolvedTool::Composer(composer_pkg) => {
+                if options.keep_download.is_some() {
+                    return Err(Self::no_phar_to_keep(&identifier.name));
+                }
+                // 先校验 bin 选择，避免为不存在的 bin 执行一次完整安装
+                if let Some(bin) = &identifier.bin {
+                    composer::check_bin(&composer_pkg.package, bin, &composer_pkg.bin_names)?;
+                }
+                if options.force {
+                    // 删除已有条目与安装目录，强制重新安装
+                    self.cache_manager.remove_entry(
+                        &composer_pkg.package,
+                        Some(&composer::install_version(
+                            &composer_pkg.version,
+                            options.with_dev,
+                        )),
+                    )?;
+                }
+                // --no-cache 的安装在临时目录中，_temp_install 离开作用域（含出错返回）时删除
+                let (install_dir, bin_path, _temp_install) =
+                    timed(&mut self.timings.install, || {
+                        if options.no_cache {
+                            let (temp, bin_path) = composer::install_uncached(
+                                &composer_pkg,
+                                options.with_dev,
+                                &std::env::temp_dir(),
+                                &mut self.cache_manager,
+                                &self.config,
+                                effective_php.as_ref(),
+                                &*self.progress,
+                            )?;
+                            Ok::<_, Error>((temp.path().to_path_buf(), bin_path, Some(temp)))
+                        } else {
+                            let (install_dir, bin_path) = composer::ensure_composer_installed(
+                                &composer_pkg,
+                                options.with_dev,
+                                &self.config.install_base(),
+                                &mut self.cache_manager,
+                                &self.config,
+                                effective_php.as_ref(),
+                                &*self.progress,
+                            )?;
+                            Ok((install_dir, bin_path, None))
+                        }
+                    })?;
+                if !options.no_cache {
+                    self.record_constraint(
+                        &identifier,
+                        &composer::install_version(&composer_pkg.version, options.with_dev),
+                    );
+                }
+                let bin_path = match &identifier.bin {
+                    Some(bin) => install_dir.join("vendor").join("bin").join(bin),
+                    None => bin_path,
+                };
+                tracing::debug!("Tool source: {}", ToolSource::ComposerInstall);
+                let exit_code = timed(&mut self.timings.execute, || {
+                    self.executor.execute_script(
+                        &bin_path,
+                        effective_args,
+                        effective_php.as_ref(),
+                        &php_args,
+                    )
+                })?;
+                Ok(RunOutcome {
+                    version: composer_pkg.version,
+                    exit_code,
+                    source: ToolSource::ComposerInstall,
+                    timings: self.timings,
+                })
+            }
+        }
+    }
+
+    /// 解析、下载、校验并缓存工具（Composer 包则在隔离目录安装），但不执行。
+    /// 已有可用缓存时直接返回。返回 (版本, phar 或 vendor/bin 路径)。
+    pub async fn install_tool(
+        &mut self,
+        tool_identifier: &str,
+        skip_verify: bool,
+        php_path: Option<&PathBuf>,
+    ) -> Result<(String, PathBuf)> {
+        let effective_php = php_path
+            .cloned()
+            .or_else(|| self.config.default_php_path.clone());
+        let identifier = self.resolver.parse_identifier(tool_identifier)?;
+        let resolved = self.resolve_tool_cached(&identifier, false).await?;
+        match resolved {
+            ResolvedTool::Phar(tool_info) => {
+                let entry_owned = self
+                    .cache_manager
+                    .get_entry(&tool_info.name, &tool_info.version)
+                    .cloned();
+                if let Some(cache_entry) = entry_owned {
+                    if self.verify_cached_tool(&cache_entry, skip_verify).is_ok() {
+                        return Ok((tool_info.version, cache_entry.file_path));
+                    }
+                }
+                let path = self
+                    .download_and_cache_tool(&tool_info, skip_verify)
+                    .await?;
+                Ok((tool_info.version, path))
             }
             ResolvedTool::Composer(composer_pkg) => {
                 let (_dir, bin_path) = composer::ensure_composer_installed(
                     &composer_pkg,
-                    &self.config.cache_dir,
+                    false,
+                    &self.config.install_base(),
                     &mut self.cache_manager,
                     &self.config,
                     effective_php.as_ref(),
+                    &*self.progress,
                 )?;
-                self.executor
-                    .execute_script(&bin_path, effective_args, effective_php.as_ref())
+                Ok((composer_pkg.version, bin_path))
             }
         }
     }
 
-    fn find_local_tool(&self, tool_name: &str) -> Option<PathBuf> {
+    /// 安装（或复用缓存的）工具后运行 `<tool> --version`，返回工具自己报告的版本号
+    pub async fn tool_reported_version(
+        &mut self,
+        tool_identifier: &str,
+        skip_verify: bool,
+        php_path: Option<&PathBuf>,
+    ) -> Result<String> {
+        let (_, path) = self
+            .install_tool(tool_identifier, skip_verify, php_path)
+            .await?;
+        let effective_php = php_path
+            .cloned()
+            .or_else(|| self.config.default_php_path.clone());
+        let (exit_code, output) = self.executor.capture_output(
+            &path,
+            &["--version".to_string()],
+            effective_php.as_ref(),
+            &[],
+        )?;
+        Executor::reported_version(&output).ok_or_else(|| {
+            Error::Execution(format!(
+                "`{} --version` exited with {} and printed no version: {}",
+                tool_identifier,
+                exit_code,
+                output.lines().next().unwrap_or("").trim()
+            ))
+        })
+    }
+
+    /// 在项目 phpx.lock 中查找工具的锁定信息；lock 文件损坏时告警并忽略
+    fn find_locked_tool(tool_name: &str) -> Option<LockedTool> {
+        let path = LockFile::find()?;
+        match LockFile::load(&path) {
+            Ok(lock) => lock.tools.get(tool_name).cloned(),
+            Err(e) => {
+                tracing::warn!("Ignoring unreadable {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    /// 在项目清单（.phpx.toml / phpx.json）的 [tools] 中查找工具的版本；清单损坏时告警并忽略
+    fn find_manifest_version(tool_name: &str) -> Option<(String, PathBuf)> {
+        let path = ProjectManifest::find()?;
+        match ProjectManifest::load(&path) {
+            Ok(manifest) => manifest.tools.get(tool_name).cloned().map(|v| (v, path)),
+            Err(e) => {
+                tracing::warn!("Ignoring unreadable {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    /// 解析并缓存工具后写入项目 phpx.lock。tools 为空时刷新 lock 中已有的全部工具。
+    /// 返回 lock 文件路径。
+    pub async fn lock_tools(&mut self, tools: &[String], skip_verify: bool) -> Result<PathBuf> {
+        let path = LockFile::default_path();
+        let mut lock = if path.exists() {
+            LockFile::load(&path)?
+        } else {
+            LockFile::default()
+        };
+
+        let specs: Vec<(String, Option<String>)> = if tools.is_empty() {
+            lock.tools
+                .iter()
+                .map(|(name, locked)| (name.clone(), locked.constraint.clone()))
+                .collect()
+        } else {
+            tools
+                .iter()
+                .map(|t| match t.split_once('@') {
+                    Some((name, constraint)) => (name.to_string(), Some(constraint.to_string())),
+                    None => (t.clone(), None),
+                })
+                .collect()
+        };
+
+        for (name, constraint) in specs {
+            let spec = match constraint {
+                Some(ref c) => format!("{}@{}", name, c),
+                None => name.clone(),
+            };
+            let (version, _path) = self.install_tool(&spec, skip_verify, None).await?;
+            let entry = self
+                .cache_manager
+                .get_entry(&name, &version)
+                .filter(|e| !e.is_composer)
+                .cloned();
+            let (download_url, hash) = match entry {
+                Some(e) => (
+                    Some(e.download_url).filter(|u| !u.is_empty()),
+                    e.file_hash.filter(|h| !h.is_empty()),
+                ),
+                None => (None, None),
+            };
+            lock.tools.insert(
+                name,
+                LockedTool {
+                    constraint,
+                    version,
+                    download_url,
+                    hash,
+                },
+            );
+        }
+
+        lock.save(&path)?;
+        Ok(path)
+    }
+
+    fn find_local_tool(&self, tool_name: &str) -> Option<(PathBuf, ToolSource)> {
         // 检查项目 vendor/bin 目录
-        let vendor_path = PathBuf::from("vendor").join("bin").join(tool_name);
-        if vendor_path.exists() {
-            return Some(vendor_path);
+        if let Some(vendor_path) = Self::bin_entry(&PathBuf::from("vendor").join("bin"), tool_name)
+        {
+            return Some((vendor_path, ToolSource::LocalVendor));
         }
 
         // 检查全局 Composer 目录
         if let Some(home_dir) = dirs::home_dir() {
-            let global_path = home_dir
-                .join(".composer")
-                .join("vendor")
-                .join("bin")
-                .join(tool_name);
-            if global_path.exists() {
-                return Some(global_path);
+            let global_bin = home_dir.join(".composer").join("vendor").join("bin");
+            if let Some(global_path) = Self::bin_entry(&global_bin, tool_name) {
+                return Some((global_path, ToolSource::GlobalComposer));
             }
         }
 
         None
     }
 
-    async fn get_tool_version(&self, identifier: &ToolIdentifier) -> Result<Option<String>> {
+    /// vendor/bin 中的入口：通常是 PHP 代理或符号链接，Windows 上可能只有 <tool>.bat 或 <tool>.cmd
+    fn bin_entry(bin_dir: &Path, tool_name: &str) -> Option<PathBuf> {
+        ["", ".bat", ".cmd"]
+            .into_iter()
+            .map(|ext| format!("{}{}", tool_name, ext))
+            .map(|name| bin_dir.join(name))
+            .find(|path| path.exists())
+    }
+
+    /// phpx 缓存中是否已有可用于该请求的副本（具体版本、约束内的版本，或未指定版本时的任一版本）
+    fn has_cached_copy(&mut self, identifier: &ToolIdentifier, with_dev: bool) -> bool {
+        if let Some(constraint) = &identifier.version_constraint {
+            return self
+                .cached_version_matching(&identifier.name, constraint, with_dev)
+                .is_some();
+        }
+        match identifier.version.as_deref() {
+            Some(version) if version != "latest" => self
+                .cached_entry(&identifier.name, version, with_dev)
+                .is_some(),
+            _ => self
+                .cache_manager
+                .list_entries()
+                .iter()
+                .any(|e| e.tool_name == identifier.name),
+        }
+    }
+
+    /// 用户指定了版本或约束时，本地工具的版本必须满足它才可使用；无法确定本地版本时视为不满足
+    fn local_satisfies(
+        identifier: &ToolIdentifier,
+        path: &Path,
+        bin_name: &str,
+        source: &ToolSource,
+    ) -> bool {
+        let wanted_version = identifier.version.as_deref().filter(|v| *v != "latest");
+        if identifier.version_constraint.is_none() && wanted_version.is_none() {
+            return true;
+        }
+        let Some(local_version) = Self::local_tool_version(path, bin_name) else {
+            tracing::debug!(
+                "Ignoring {} {}: its version is unknown and a specific version was requested",
+                source,
+                bin_name
+            );
+            return false;
+        };
+        let parsed = semver::Version::parse(&local_version).ok();
+        let satisfied = match (&identifier.version_constraint, wanted_version) {
+            (Some(constraint), _) => parsed.is_some_and(|v| constraint.matches(&v)),
+            (None, Some(version)) => {
+                let version = version.trim_start_matches('v');
+                local_version == version
+                    || parsed.is_some_and(|v| semver::Version::parse(version).ok() == Some(v))
+            }
+            (None, None) => true,
+        };
+        if !satisfied {
+            tracing::info!(
+                "Ignoring {} {} {}: it does not satisfy the requested version",
+                source,
+                bin_name,
+                local_version
+            );
+        }
+        satisfied
+    }
+
+    /// 本地工具所属 Composer 包的版本：读取 vendor/bin 同级的 vendor/composer/installed.json，
+    /// 找出声明了该 bin 的包
+    fn local_tool_version(bin_path: &Path, bin_name: &str) -> Option<String> {
+        let vendor_dir = bin_path.parent()?.parent()?;
+        let content =
+            std::fs::read_to_string(vendor_dir.join("composer").join("installed.json")).ok()?;
+        let installed: serde_json::Value = serde_json::from_str(&content).ok()?;
+        // Composer 2 为 {"packages": [...]}，Composer 1 直接是数组
+        let packages = installed.get("packages").unwrap_or(&installed).as_array()?;
+        packages
+            .iter()
+            .find(|package| {
+                package
+                    .get("bin")
+                    .and_then(|bins| bins.as_array())
+                    .is_some_and(|bins| {
+                        bins.iter().filter_map(|b| b.as_str()).any(|b| {
+                            Path::new(b).file_name().and_then(|n| n.to_str()) == Some(bin_name)
+                        })
+                    })
+            })
+            .and_then(|package| package.get("version")?.as_str())
+            .map(|version| version.trim_start_matches('v').to_string())
+    }
+
+    /// 解析工具并把耗时计入 timings.resolve
+    async fn resolve_tool_cached(
+        &mut self,
+        identifier: &ToolIdentifier,
+        bypass: bool,
+    ) -> Result<ResolvedTool> {
+        self.progress.on_resolve_start(&identifier.name);
+        let started = Instant::now();
+        let result = self.resolve_with_negative_cache(identifier, bypass).await;
+        self.timings.resolve += started.elapsed();
+        result
+    }
+
+    /// 带负缓存的解析：工具在 NEGATIVE_CACHE_TTL 内解析失败过时直接返回 ToolNotFound，
+    /// 避免脚本重试时反复扫描 Packagist/GitHub；bypass 为 true（--no-cache）时总是重新解析
+    async fn resolve_with_negative_cache(
+        &mut self,
+        identifier: &ToolIdentifier,
+        bypass: bool,
+    ) -> Result<ResolvedTool> {
+        let key = Self::negative_cache_key(identifier);
+        if !bypass
+            && self
+                .cache_manager
+                .is_known_missing(&key, NEGATIVE_CACHE_TTL)
+        {
+            tracing::debug!("{} failed to resolve recently, skipping lookup", key);
+            let mut failure = ResolutionFailure::new(&identifier.name);
+            failure.add_reason(format!(
+                "failed to resolve within the last {} minutes (use --no-cache to retry now)",
+                NEGATIVE_CACHE_TTL / 60
+            ));
+            return Err(Error::ToolNotFound(failure));
+        }
+
+        let result = self.resolver.resolve_tool(identifier).await;
+        if Self::genuinely_missing(&result) {
+            if let Err(e) = self.cache_manager.record_missing(&key, NEGATIVE_CACHE_TTL) {
+                tracing::debug!("Failed to record negative resolution for {}: {}", key, e);
+            }
+        }
+        result
+    }
+
+    /// 各来源都明确表示没有该工具时才记入负缓存；网络错误、镜像不可达、5xx 或限流（transient）不记录
+    fn genuinely_missing(result: &Result<ResolvedTool>) -> bool {
+        matches!(result, Err(Error::ToolNotFound(failure)) if !failure.transient)
+    }
+
+    /// 负缓存的键：工具名加版本或约束
+    fn negative_cache_key(identifier: &ToolIdentifier) -> String {
+        match (&identifier.version_constraint, &identifier.version) {
+            (Some(constraint), _) => format!("{}@{}", identifier.name, constraint),
+            (None, Some(version)) => format!("{}@{}", identifier.name, version),
+            (None, None) => identifier.name.clone(),
+        }
+    }
+
+    /// 查找缓存条目；with_dev 时 Composer 包只接受带开发依赖的安装（<version>+dev），phar 不受影响
+    fn cached_entry(&mut self, name: &str, version: &str, with_dev: bool) -> Option<CacheEntry> {
+        if with_dev {
+            let dev_version = composer::install_version(version, true);
+            if let Some(entry) = self.cache_manager.get_entry(name, &dev_version) {
+                return Some(entry.clone());
+            }
+            return self
+                .cache_manager
+                .get_entry(name, version)
+                .filter(|entry| !entry.is_composer)
+                .cloned();
+        }
+        self.cache_manager.get_entry(name, version).cloned()
+    }
+
+    async fn get_tool_version(
+        &mut self,
+        identifier: &ToolIdentifier,
+        reuse_cached: bool,
+        with_dev: bool,
+    ) -> Result<Option<String>> {
         if let Some(version) = &identifier.version {
             return Ok(Some(version.clone()));
         }
 
+        // reuse_cached 时按约束直接复用缓存中满足约束的最新版本，不访问解析源
+        let mut cached = None;
+        if let Some(constraint) = &identifier.version_constraint {
+            cached = self.cached_version_matching(&identifier.name, constraint, with_dev);
+            if let Some(version) = cached.as_ref().filter(|_| reuse_cached) {
+                tracing::debug!(
+                    "Reusing cached {}@{} for {} (reuse_cached; use --prefer-newer to re-resolve)",
+                    identifier.name,
+                    version,
+                    constraint
+                );
+                return Ok(cached);
+            }
+        }
+
         // 如果没有指定版本，尝试解析得到版本号（Phar 或 Composer 均可）
-        let resolved = self.resolver.resolve_tool(identifier).await.ok();
+        let resolved = self.resolve_tool_cached(identifier, false).await.ok();
         match resolved {
             Some(ResolvedTool::Phar(t)) => Ok(Some(t.version)),
             Some(ResolvedTool::Composer(c)) => Ok(Some(c.version)),
-            None => Ok(None),
+            // 解析失败（如离线）时退回缓存中满足约束的版本
+            None => Ok(cached),
+        }
+    }
+
+    /// 缓存中满足版本约束的最高版本：版本号落在约束内，或条目记录的约束与之相同。
+    /// with_dev 时 Composer 包只接受带开发依赖的安装，反之亦然
+    fn cached_version_matching(
+        &self,
+        name: &str,
+        constraint: &semver::VersionReq,
+        with_dev: bool,
+    ) -> Option<String> {
+        let wanted = constraint.to_string();
+        let dev_suffix = composer::install_version("", true);
+        self.cache_manager
+            .list_entries()
+            .into_iter()
+            .filter(|e| e.tool_name == name && e.version != "latest")
+            .filter(|e| !e.is_composer || e.version.ends_with(&dev_suffix) == with_dev)
+            .filter_map(|e| {
+                let version = e.version.trim_end_matches(&dev_suffix);
+                let parsed = semver::Version::parse(version).ok();
+                let matches = e.constraint.as_deref() == Some(wanted.as_str())
+                    || parsed.as_ref().is_some_and(|v| constraint.matches(v));
+                matches.then(|| (parsed, version.to_string()))
+            })
+            .max()
+            .map(|(_, version)| version)
+    }
+
+    /// 把解析该版本所用的约束记到缓存条目上，下次按同一约束运行时可直接复用
+    fn record_constraint(&mut self, identifier: &ToolIdentifier, cache_version: &str) {
+        let Some(constraint) = &identifier.version_constraint else {
+            return;
+        };
+        if let Err(e) = self.cache_manager.set_constraint(
+            &identifier.name,
+            cache_version,
+            &constraint.to_string(),
+        ) {
+            tracing::debug!("Failed to record constraint for {}: {}", identifier.name, e);
         }
     }
 
+    /// 工具的默认 PHP ini 设置：配置 default_php_ini 优先（空数组表示关闭），其次为内置列表；
+    /// vendor/package 形式的工具名同时按包名最后一段匹配（如 vimeo/psalm -> psalm）
+    fn default_php_ini(&self, tool_name: &str) -> Vec<String> {
+        let short_name = tool_name.rsplit('/').next().unwrap_or(tool_name);
+        for name in [tool_name, short_name] {
+            if let Some(settings) = self.config.default_php_ini.get(name) {
+                return settings.clone();
+            }
+        }
+        BUILTIN_PHP_INI
+            .iter()
+            .find(|(name, _)| *name == short_name)
+            .map(|(_, settings)| settings.iter().map(|s| s.to_string()).collect())
+            .unwrap_or_default()
+    }
+
+    /// --keep-download：将 phar 复制到 dest（为目录时保存为 <tool>.phar），并打印保存位置
+    fn keep_phar(phar_path: &Path, dest: &Path, tool_name: &str) -> Result<()> {
+        let target = if dest.is_dir() {
+            dest.join(format!("{}.phar", sanitize_component(tool_name)))
+        } else {
+            dest.to_path_buf()
+        };
+        std::fs::copy(phar_path, &target)?;
+        eprintln!("Saved phar to {}", target.display());
+        Ok(())
+    }
+
+    fn no_phar_to_keep(tool_name: &str) -> Error {
+        Error::Execution(format!(
+            "{} is installed via Composer; there is no single phar to keep (--keep-download)",
+            tool_name
+        ))
+    }
+
+    /// 标识符指向已存在的文件且以 .phar 结尾或带路径分隔符（如 ./build/tool）时视为本地 phar；
+    /// 仅凭同名文件（如当前目录下恰好有名为 phpstan 的文件）不算，避免误用
+    fn local_phar_path(tool_identifier: &str) -> Option<PathBuf> {
+        let path = Path::new(tool_identifier);
+        let looks_like_path = tool_identifier.ends_with(".phar")
+            || tool_identifier.contains('/')
+            || tool_identifier.contains(std::path::MAIN_SEPARATOR);
+        // vendor/package 形式的工具名也带 /，要求文件确实存在
+        (looks_like_path && path.is_file()).then(|| path.to_path_buf())
+    }
+
+    /// Composer 缓存条目安装时的 PHP 与当前 PHP 不同，且当前 PHP 不满足包自身 require.php 时返回原因
+    fn composer_php_mismatch(
+        &self,
+        entry: &crate::cache::CacheEntry,
+        php_path: Option<&PathBuf>,
+    ) -> Option<String> {
+        if !entry.is_composer {
+            return None;
+        }
+        let installed = entry.php_version.as_deref()?;
+        let php = self.executor.find_php_binary(php_path).ok()?;
+        let current = self.executor.php_version(&php)?;
+        if current == installed {
+            return None;
+        }
+        let manifest = entry
+            .file_path
+            .join("vendor")
+            .join(&entry.tool_name)
+            .join("composer.json");
+        let constraint = Executor::php_constraint_of(&manifest)?;
+        if Executor::php_version_matches_constraint(&current, &constraint) {
+            tracing::debug!(
+                "{} was installed with PHP {}, current PHP {} still satisfies {}",
+                entry.tool_name,
+                installed,
+                current,
+                constraint
+            );
+            return None;
+        }
+        Some(format!(
+            "was installed with PHP {}, but current PHP {} does not satisfy its requirement {}",
+            installed, current, constraint
+        ))
+    }
+
+    /// 解析并下载工具到临时文件，执行签名与哈希校验后删除，不写入缓存、不运行。
+    /// Composer 包由 Composer 自行校验，这里只支持 phar
+    pub async fn verify_tool(&mut self, tool_identifier: &str) -> Result<VerifyReport> {
+        let identifier = self.resolver.parse_identifier(tool_identifier)?;
+        let tool_info = match self.resolve_tool_cached(&identifier, true).await? {
+            ResolvedTool::Phar(tool_info) => tool_info,
+            ResolvedTool::Composer(pkg) => {
+                return Err(Error::Execution(format!(
+                    "{}@{} is installed with Composer, which verifies package checksums itself; \
+                     verify only applies to phar downloads",
+                    pkg.package, pkg.version
+                )))
+            }
+        };
+
+        let download_path = self.download_path(&tool_info).with_extension("verify");
+        self.downloader
+            .download_file_with_progress(&tool_info.download_url, &download_path)
+            .await?;
+        let report = self.verification_report(&tool_info, &download_path);
+        let _ = std::fs::remove_file(&download_path);
+        report
+    }
+
+    fn verification_report(&self, tool_info: &ToolInfo, path: &Path) -> Result<VerifyReport> {
+        if let Some(signature_url) = &tool_info.signature_url {
+            self.security_manager
+                .verify_signature(path, Some(signature_url))?;
+        }
+        let hash = tool_info
+            .hash
+            .as_deref()
+            .map(|expected| self.security_manager.check_hash(path, expected))
+            .transpose()?;
+        Ok(VerifyReport {
+            tool: tool_info.name.clone(),
+            version: tool_info.version.clone(),
+            url: tool_info.download_url.clone(),
+            sha256: SecurityManager::sha256_file(path)?,
+            hash,
+            signature_url: tool_info.signature_url.clone(),
+            signer: None,
+        })
+    }
+
     fn verify_cached_tool(
         &self,
         cache_entry: &crate::cache::CacheEntry,
         skip_verify: bool,
     ) -> Result<()> {
-        if skip_verify || self.security_manager.skip_verification() {
-            return Ok(());
-        }
-
+        // 文件缺失属于缓存损坏而非校验问题，--skip-verify 时也要检查
         if !cache_entry.file_path.exists() {
             return Err(Error::Cache(
                 "Cached file or directory not found".to_string(),
@@ -216,6 +1204,10 @@ impl Runner {
             return Ok(());
         }
 
+        if skip_verify || self.security_manager.skip_verification() {
+            return Ok(());
+        }
+
         let metadata = std::fs::metadata(&cache_entry.file_path)?;
         if metadata.len() != cache_entry.size {
             return Err(Error::Cache("Cached file size mismatch".to_string()));
@@ -236,36 +1228,111 @@ impl Runner {
         tool_info: &crate::resolver::ToolInfo,
         skip_verify: bool,
     ) -> Result<PathBuf> {
-        let file_name = format!("{}-{}.phar", tool_info.name, tool_info.version);
-        let cache_path = self.config.cache_dir.join(&file_name);
+        let download_path = self.download_path(tool_info);
 
         // 下载文件
-        self.downloader
-            .download_file(&tool_info.download_url, &cache_path)
-            .await?;
+        let started = Instant::now();
+        let downloaded = self
+            .downloader
+            .download_file_with_progress(&tool_info.download_url, &download_path)
+            .await;
+        self.timings.download += started.elapsed();
+        downloaded?;
 
-        // 安全验证
-        if !skip_verify && !self.security_manager.skip_verification() {
-            if let Some(signature_url) = &tool_info.signature_url {
-                self.security_manager
-                    .verify_signature(&cache_path, Some(signature_url))?;
-            }
+        let started = Instant::now();
+        let installed = self.install_download(tool_info, &download_path, skip_verify);
+        self.timings.verify += started.elapsed();
+        installed
+    }
 
-            if let Some(expected_hash) = &tool_info.hash {
-                self.security_manager
-                    .verify_hash(&cache_path, expected_hash)?;
-            }
+    /// 下载目标路径：phar 直接下载到缓存位置，压缩包先下载到旁边的临时文件
+    fn download_path(&self, tool_info: &crate::resolver::ToolInfo) -> PathBuf {
+        let cache_path = self.phar_cache_path(tool_info);
+        if ArchiveKind::detect(&tool_info.download_url).is_some() {
+            cache_path.with_extension("download")
         } else {
-            // 即使跳过验证，也要计算哈希值用于缓存记录
-            let _hash = self.calculate_file_hash(&cache_path).ok();
+            cache_path
+        }
+    }
+
+    /// 校验并记录下载结果，返回缓存中的 phar 路径。
+    /// 压缩包：签名与哈希针对下载的压缩包，校验后取出其中的 phar，缓存条目记录 phar 自身的哈希
+    fn install_download(
+        &mut self,
+        tool_info: &crate::resolver::ToolInfo,
+        downloaded: &Path,
+        skip_verify: bool,
+    ) -> Result<PathBuf> {
+        let cache_path = self.phar_cache_path(tool_info);
+        let Some(kind) = ArchiveKind::detect(&tool_info.download_url) else {
+            self.record_downloaded_tool(tool_info, &cache_path, skip_verify)?;
+            return Ok(cache_path);
+        };
+
+        let extracted = self
+            .verify_download(tool_info, downloaded, skip_verify)
+            .and_then(|_| archive::extract_phar(downloaded, kind, &cache_path));
+        let _ = std::fs::remove_file(downloaded);
+        extracted?;
+
+        let phar_info = ToolInfo {
+            signature_url: None,
+            hash: None,
+            ..tool_info.clone()
+        };
+        self.record_downloaded_tool(&phar_info, &cache_path, skip_verify)?;
+        Ok(cache_path)
+    }
+
+    /// phar 在缓存目录中的文件名：vendor/package 中的 / 等字符编码为 -，保证落在 cache_dir 下一层；
+    /// 缓存条目的键仍使用原始工具名
+    fn phar_cache_path(&self, tool_info: &crate::resolver::ToolInfo) -> PathBuf {
+        let file_name =
+            sanitize_component(&format!("{}-{}.phar", tool_info.name, tool_info.version));
+        self.config.cache_dir.join(file_name)
+    }
+
+    /// 按 ToolInfo 中的签名地址与哈希校验下载的文件
+    fn verify_download(
+        &self,
+        tool_info: &crate::resolver::ToolInfo,
+        path: &Path,
+        skip_verify: bool,
+    ) -> Result<()> {
+        if skip_verify || self.security_manager.skip_verification() {
+            return Ok(());
+        }
+        if let Some(signature_url) = &tool_info.signature_url {
+            self.security_manager
+                .verify_signature(path, Some(signature_url))?;
         }
+        if let Some(expected_hash) = &tool_info.hash {
+            self.security_manager.verify_hash(path, expected_hash)?;
+        }
+        Ok(())
+    }
+
+    /// --no-verify-tls（config.no_verify_tls）或 PHPX_NO_VERIFY_TLS 为真值时不校验 HTTPS 证书
+    fn tls_verification_disabled(config: &Config, env_value: Option<&str>) -> bool {
+        config.no_verify_tls || env_value.is_some_and(is_truthy)
+    }
+
+    /// 校验已下载的 phar 并写入缓存记录
+    fn record_downloaded_tool(
+        &mut self,
+        tool_info: &crate::resolver::ToolInfo,
+        cache_path: &PathBuf,
+        skip_verify: bool,
+    ) -> Result<()> {
+        // 安全验证
+        self.verify_download(tool_info, cache_path, skip_verify)?;
 
         // 添加到缓存
-        let metadata = std::fs::metadata(&cache_path)?;
+        let metadata = std::fs::metadata(cache_path)?;
         let file_hash = if skip_verify {
             None
         } else {
-            Some(self.calculate_file_hash(&cache_path)?)
+            Some(self.calculate_file_hash(cache_path)?)
         };
 
         self.cache_manager.add_entry(
@@ -275,20 +1342,210 @@ impl Runner {
             tool_info.download_url.clone(),
             Some(file_hash.unwrap_or_default()),
             metadata.len(),
-        )?;
+        )
+    }
 
-        Ok(cache_path)
+    /// 对每个缓存的工具重新解析最新版本（遵循 phpx.lock 中的约束），只读不下载
+    pub async fn outdated_tools(&mut self, concurrency: usize) -> Result<Vec<OutdatedTool>> {
+        let concurrency = concurrency.max(1);
+        let current = self.current_cached_versions();
+
+        let lock = LockFile::find().and_then(|path| LockFile::load(&path).ok());
+        let mut lookups = Vec::new();
+        for (tool, version) in current {
+            let constraint = lock
+                .as_ref()
+                .and_then(|l| l.tools.get(&tool))
+                .and_then(|t| t.constraint.clone());
+            let spec = match &constraint {
+                Some(c) => format!("{}@{}", tool, c),
+                None => tool.clone(),
+            };
+            let identifier = self.resolver.parse_identifier(&spec)?;
+            // 近期解析失败过的工具直接跳过，不再访问解析源
+            let known_missing = self
+                .cache_manager
+                .is_known_missing(&spec, NEGATIVE_CACHE_TTL);
+            lookups.push((tool, version, constraint, spec, identifier, known_missing));
+        }
+
+        let resolver = &self.resolver;
+        let mut resolved: Vec<_> = stream::iter(lookups)
+            .map(
+                |(tool, version, constraint, spec, identifier, known_missing)| async move {
+                    let (latest, missing) = if known_missing {
+                        (None, false)
+                    } else {
+                        let result = resolver.resolve_tool(&identifier).await;
+                        let missing = Self::genuinely_missing(&result);
+                        let latest = match result {
+                            Ok(ResolvedTool::Phar(info)) => Some(info.version),
+                            Ok(ResolvedTool::Composer(pkg)) => Some(pkg.version),
+                            Err(_) => None,
+                        };
+                        (latest, missing)
+                    };
+                    (tool, version, constraint, spec, latest, missing)
+                },
+            )
+            .buffer_unordered(concurrency)
+            .collect()
+            .await;
+        resolved.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut out = Vec::new();
+        for (tool, current, constraint, spec, latest, missing) in resolved {
+            if missing {
+                if let Err(e) = self.cache_manager.record_missing(&spec, NEGATIVE_CACHE_TTL) {
+                    tracing::debug!("Failed to record negative resolution for {}: {}", spec, e);
+                }
+            }
+            let outdated = latest
+                .as_deref()
+                .is_some_and(|l| Self::version_gt(l, &current));
+            out.push(OutdatedTool {
+                tool,
+                current,
+                latest,
+                constraint,
+                outdated,
+                changelog: Vec::new(),
+            });
+        }
+        Ok(out)
+    }
+
+    /// 缓存中各工具的当前版本；同一工具缓存了多个版本时以最高版本为当前版本
+    pub fn current_cached_versions(&self) -> std::collections::BTreeMap<String, String> {
+        let mut current: std::collections::BTreeMap<String, String> = Default::default();
+        for entry in self.cache_manager.list_entries() {
+            let newer = current
+                .get(&entry.tool_name)
+                .is_none_or(|existing| Self::version_gt(&entry.version, existing));
+            if newer {
+                current.insert(entry.tool_name.clone(), entry.version.clone());
+            }
+        }
+        current
+    }
+
+    /// 工具从 current 升级到 latest 之间的 GitHub 发布说明，从新到旧
+    pub async fn release_notes(&self, tool: &str, current: &str, latest: &str) -> Vec<ReleaseNote> {
+        self.resolver.release_notes(tool, current, latest).await
     }
 
-    fn calculate_file_hash(&self, file_path: &PathBuf) -> Result<String> {
-        use std::fs::File;
-        use std::io::Read;
+    /// a 是否比 b 新：均为 semver 时按 semver 比较；否则（如缓存版本为 latest）只要不同即视为更新
+    fn version_gt(a: &str, b: &str) -> bool {
+        match (semver::Version::parse(a), semver::Version::parse(b)) {
+            (Ok(a), Ok(b)) => a > b,
+            (Ok(_), Err(_)) => true,
+            (Err(_), Ok(_)) => false,
+            (Err(_), Err(_)) => a != b,
+        }
+    }
+
+    /// 重新解析所有已缓存的工具并缓存新版本；解析与 phar 下载至多 concurrency 个并发，
+    /// 缓存记录与 Composer 安装按顺序执行。返回新缓存的 (工具名, 版本)。
+    pub async fn update_cached_tools(
+        &mut self,
+        concurrency: usize,
+        skip_verify: bool,
+    ) -> Result<Vec<(String, String)>> {
+        let concurrency = concurrency.max(1);
+        let mut names: Vec<String> = self
+            .cache_manager
+            .list_entries()
+            .into_iter()
+            .map(|e| e.tool_name.clone())
+            .collect();
+        names.sort();
+        names.dedup();
+
+        let identifiers = names
+            .iter()
+            .map(|name| self.resolver.parse_identifier(name))
+            .collect::<Result<Vec<_>>>()?;
+
+        let resolver = &self.resolver;
+        let resolved: Vec<_> = stream::iter(identifiers)
+            .map(|identifier| async move {
+                let result = resolver.resolve_tool(&identifier).await;
+                (identifier.name, result)
+            })
+            .buffer_unordered(concurrency)
+            .collect()
+            .await;
+
+        let mut phars = Vec::new();
+        let mut composer_pkgs = Vec::new();
+        for (name, result) in resolved {
+            match result {
+                Ok(ResolvedTool::Phar(info)) => {
+                    if self
+                        .cache_manager
+                        .get_entry(&info.name, &info.version)
+                        .is_none()
+                    {
+                        phars.push(info);
+                    }
+                }
+                Ok(ResolvedTool::Composer(pkg)) => composer_pkgs.push(pkg),
+                Err(e) => tracing::warn!("Failed to resolve {}: {}", name, e),
+            }
+        }
+
+        // 并发下载时不显示单个进度条，避免多个进度条互相覆盖
+        let downloader = &self.downloader;
+        let downloads: Vec<_> = stream::iter(phars)
+            .map(|info| {
+                let path = self.download_path(&info);
+                async move {
+                    let result = downloader.download_file(&info.download_url, &path).await;
+                    (info, path, result)
+                }
+            })
+            .buffer_unordered(concurrency)
+            .collect()
+            .await;
+
+        let mut updated = Vec::new();
+        for (info, path, result) in downloads {
+            let recorded = result.and_then(|_| self.install_download(&info, &path, skip_verify));
+            match recorded {
+                Ok(_) => updated.push((info.name, info.version)),
+                Err(e) => tracing::warn!("Failed to update {}: {}", info.name, e),
+            }
+        }
+
+        for pkg in composer_pkgs {
+            if self
+                .cache_manager
+                .get_entry(&pkg.package, &pkg.version)
+                .is_some()
+            {
+                continue;
+            }
+            match composer::ensure_composer_installed(
+                &pkg,
+                false,
+                &self.config.install_base(),
+                &mut self.cache_manager,
+                &self.config,
+                self.config.default_php_path.as_ref(),
+                &*self.progress,
+            ) {
+                Ok(_) => updated.push((pkg.package, pkg.version)),
+                Err(e) => tracing::warn!("Failed to update {}: {}", pkg.package, e),
+            }
+        }
 
-        let mut file = File::open(file_path)?;
-        let mut buffer = Vec::new();
-        file.read_to_end(&mut buffer)?;
+        updated.sort();
+        Ok(updated)
+    }
 
-        Ok(format!("{:x}", md5::compute(&buffer)))
+    /// 缓存条目记录的文件哈希，使用配置的算法（默认 sha256）并带算法前缀
+    fn calculate_file_hash(&self, file_path: &Path) -> Result<String> {
+        self.security_manager.hash_file(file_path)
     }
 
     pub fn clean_cache(&mut self, tool_name: Option<String>) -> Result<()> {
@@ -312,6 +1569,64 @@ impl Runner {
         }
     }
 
+    /// 固定或取消固定缓存条目（phpx cache pin / unpin）。tool 可带版本（phpstan@1.11.2），
+    /// 不带时作用于该工具已缓存的全部版本；没有匹配的条目时报错
+    pub fn pin_cache(&mut self, tool: &str, pinned: bool) -> Result<usize> {
+        let (name, version) = match tool.split_once('@') {
+            Some((name, version)) => (name, Some(version)),
+            None => (tool, None),
+        };
+        let changed = self.cache_manager.set_pinned(name, version, pinned)?;
+        if changed == 0 {
+            return Err(Error::NotCached(tool.to_string()));
+        }
+        Ok(changed)
+    }
+
+    /// 立即按 TTL 清理过期缓存（phpx cache gc），不受自动清理间隔限制
+    pub fn gc_cache(&mut self) -> Result<()> {
+        self.cache_manager
+            .cleanup_old_entries(self.config.cache_ttl)
+    }
+
+    /// 按用户给出的阈值删除缓存条目，打印删除的条目与回收的空间
+    pub fn prune_cache(
+        &mut self,
+        older_than: Option<u64>,
+        larger_than: Option<u64>,
+        dry_run: bool,
+    ) -> Result<()> {
+        let removed = self.cache_manager.prune(older_than, larger_than, dry_run)?;
+        if removed.is_empty() {
+            println!("No cache entries matched.");
+            return Ok(());
+        }
+
+        let verb = if dry_run { "Would remove" } else { "Removed" };
+        for (entry, size) in &removed {
+            println!(
+                "{} {}@{} ({})",
+                verb,
+                entry.tool_name,
+                entry.version,
+                units::format_size(*size)
+            );
+        }
+        let total: u64 = removed.iter().map(|(_, size)| size).sum();
+        println!(
+            "{} {} entries, {} {}",
+            verb,
+            removed.len(),
+            units::format_size(total),
+            if dry_run {
+                "would be reclaimed"
+            } else {
+                "reclaimed"
+            }
+        );
+        Ok(())
+    }
+
     pub fn list_cache(&self) -> Result<()> {
         let entries = self.cache_manager.list_entries();
 
@@ -333,8 +1648,12 @@ impl Runner {
                 .unwrap_or_else(|| "Unknown".to_string());
 
             println!(
-                "{:<20} {:<15} {:<8.1}MB {:<12}",
-                entry.tool_name, entry.version, size_mb, last_accessed
+                "{:<20} {:<15} {:<8.1}MB {:<12}{}",
+                entry.tool_name,
+                entry.version,
+                size_mb,
+                last_accessed,
+                if entry.pinned { " (pinned)" } else { "" }
             );
         }
 
@@ -348,9 +1667,9 @@ impl Runner {
             .filter(|e| e.tool_name == tool_name)
             .collect();
 
+        // 未命中时返回错误，使脚本可通过退出码判断工具是否已缓存
         if tool_entries.is_empty() {
-            println!("No cache entries found for tool: {}", tool_name);
-            return Ok(());
+            return Err(Error::NotCached(tool_name.to_string()));
         }
 
         println!("Cache information for tool: {}", tool_name);
@@ -358,6 +1677,9 @@ impl Runner {
 
         for entry in tool_entries {
             println!("Version: {}", entry.version);
+            if entry.pinned {
+                println!("Pinned: yes (kept by gc and prune)");
+            }
             println!("File: {}", entry.file_path.display());
             println!("Size: {:.1}MB", entry.size as f64 / 1024.0 / 1024.0);
             println!("Download URL: {}", entry.download_url);
@@ -379,25 +1701,6 @@ impl Runner {
         Ok(())
     }
 
-    pub async fn run_tool_with_options(
-        &mut self,
-        tool_identifier: &str,
-        args: &[String],
-        options: &crate::ToolOptions,
-    ) -> Result<()> {
-        self.run_tool(
-            tool_identifier,
-            args,
-            options.clear_cache,
-            options.no_cache,
-            options.skip_verify,
-            options.php.as_ref(),
-            options.no_local,
-            options.no_interaction,
-        )
-        .await
-    }
-
     /// 为「无缝切版本」在 override 目录安装指定库包（仅 Packagist zip 包），返回安装目录。
     /// 若解析结果为 Phar 则返回错误，提示用 phpx &lt;tool&gt; 运行。
     pub async fn install_override_package(
@@ -406,12 +1709,45 @@ impl Runner {
         php_path: Option<&PathBuf>,
     ) -> Result<PathBuf> {
         let identifier = self.resolver.parse_identifier(package_spec)?;
+        // 安装的是库包：同一版本既有 phar 又有 zip 时取 zip
+        self.resolver.set_prefer_library(true);
         let resolved = self.resolver.resolve_tool(&identifier).await?;
         match resolved {
             ResolvedTool::Composer(pkg) => composer::ensure_override_installed(
                 &pkg.package,
                 &pkg.version,
-                &self.config.cache_dir,
+                &self.config.install_base(),
+                &mut self.cache_manager,
+                &self.config,
+                php_path,
+            ),
+            ResolvedTool::Phar(_) => Err(Error::Execution(
+                "phpx add only supports library packages (Packagist zip). \
+                 For phar-based tools use: phpx <tool>"
+                    .to_string(),
+            )),
+        }
+    }
+
+    /// 将库包加入共享 override 项目（phpx add --shared），返回共享项目目录。
+    pub async fn install_shared_override_package(
+        &mut self,
+        package_spec: &str,
+        php_path: Option<&PathBuf>,
+    ) -> Result<PathBuf> {
+        let identifier = self.resolver.parse_identifier(package_spec)?;
+        self.resolver.set_prefer_library(true);
+        // 共享项目按约束记录成员，之后 remove 其它包时可重新解析
+        let constraint = match (&identifier.version_constraint, &identifier.version) {
+            (Some(c), _) => Some(c.to_string()),
+            (None, Some(v)) if v != "latest" => Some(v.clone()),
+            _ => None,
+        };
+        match self.resolver.resolve_tool(&identifier).await? {
+            ResolvedTool::Composer(pkg) => composer::add_shared_override(
+                &pkg.package,
+                constraint.as_deref().unwrap_or(&pkg.version),
+                &self.config.install_base(),
                 &mut self.cache_manager,
                 &self.config,
                 php_path,
@@ -424,9 +1760,24 @@ impl Runner {
         }
     }
 
-    /// 列出 override 目录下已安装的库包，返回 (package, version, path)。
+    /// 从共享 override 项目移除库包并重新解析其余成员；包不在共享项目中时返回 false。
+    pub fn remove_shared_override_package(
+        &mut self,
+        package: &str,
+        php_path: Option<&PathBuf>,
+    ) -> Result<bool> {
+        composer::remove_shared_override(
+            package,
+            &self.config.install_base(),
+            &mut self.cache_manager,
+            &self.config,
+            php_path,
+        )
+    }
+
+    /// 列出 override 目录下已安装的库包，返回 (package, version, path)；共享项目的成员版本为其约束。
     pub fn list_override_packages(&self) -> Result<Vec<(String, String, PathBuf)>> {
-        let override_dir = self.config.cache_dir.join("override");
+        let override_dir = self.config.install_base().join("override");
         if !override_dir.exists() {
             return Ok(vec![]);
         }
@@ -438,15 +1789,24 @@ impl Runner {
                 continue;
             }
             let name = entry.file_name().to_string_lossy().into_owned();
+            if path == composer::shared_override_dir(&self.config.install_base()) {
+                continue;
+            }
+            // 优先读取安装时写入的 composer.json，目录名中的版本可能含 '-'（如 2.0.0-beta1）
+            if let Some((package, version)) = composer::override_requirement(&path) {
+                out.push((package, version, path));
+                continue;
+            }
             // 目录名格式: vendor-package-version，如 guzzlehttp-guzzle-7.10.0
             let parts: Vec<&str> = name.split('-').collect();
             if parts.len() < 2 {
                 out.push((name.clone(), String::new(), path));
                 continue;
             }
-            let (package, version) = if parts.last().map_or(false, |s| {
-                s.chars().next().map_or(false, |c| c.is_ascii_digit())
-            }) {
+            let (package, version) = if parts
+                .last()
+                .is_some_and(|s| s.chars().next().is_some_and(|c| c.is_ascii_digit()))
+            {
                 let version = parts.last().unwrap().to_string();
                 let slug = parts[..parts.len() - 1].join("-");
                 let package = slug.replacen('-', "/", 1);
@@ -456,6 +1816,11 @@ impl Runner {
             };
             out.push((package, version, path));
         }
+        let shared_dir = composer::shared_override_dir(&self.config.install_base());
+        for (package, constraint) in composer::shared_override_members(&self.config.install_base())?
+        {
+            out.push((package, constraint, shared_dir.clone()));
+        }
         out.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.cmp(&b.1)));
         Ok(out)
     }
@@ -466,8 +1831,8 @@ impl Runner {
         package: &str,
         version: Option<&str>,
     ) -> Result<Vec<PathBuf>> {
-        let slug = package.replace('/', "-");
-        let override_dir = self.config.cache_dir.join("override");
+        let slug = sanitize_component(package);
+        let override_dir = self.config.install_base().join("override");
         if !override_dir.exists() {
             return Ok(vec![]);
         }
@@ -490,6 +1855,7 @@ impl Runner {
             let path = entry.path();
             if path.is_dir() {
                 std::fs::remove_dir_all(&path)?;
+                composer::remove_override_pin(&self.config.install_base(), &name_str)?;
                 removed.push(path);
             }
         }
@@ -498,16 +1864,15 @@ impl Runner {
 
     /// 在指定路径生成 override_autoload.php：先加载 override 目录的 autoload，再加载项目 vendor。
     pub fn write_override_bootstrap(
-        override_install_dir: &PathBuf,
-        bootstrap_path: &PathBuf,
+        override_install_dir: &Path,
+        bootstrap_path: &Path,
     ) -> Result<()> {
         let override_autoload = override_install_dir
             .canonicalize()
-            .unwrap_or_else(|_| override_install_dir.clone())
+            .unwrap_or_else(|_| override_install_dir.to_path_buf())
             .join("vendor")
             .join("autoload.php");
-        let path_str = override_autoload.display().to_string();
-        let escaped = path_str.replace('\\', "\\\\").replace('\'', "\\'");
+        let escaped = php_quoted_path(&override_autoload.to_string_lossy(), cfg!(windows));
         let content = format!(
             r#"<?php
 // Generated by phpx add --bootstrap. Load override vendor first, then project vendor.
@@ -523,3 +1888,302 @@ require __DIR__ . '/vendor/autoload.php';
         Ok(())
     }
 }
+
+/// 把路径转为 PHP 单引号字符串的内容。Windows 下去掉 canonicalize 产生的 \\?\ 前缀
+/// （require 不认这种扩展长度路径），并统一为 PHP 同样接受的 /
+fn php_quoted_path(path: &str, windows: bool) -> String {
+    let path = if windows {
+        let path = match path.strip_prefix(r"\\?\UNC\") {
+            Some(unc) => format!(r"\\{}", unc),
+            None => path.strip_prefix(r"\\?\").unwrap_or(path).to_string(),
+        };
+        path.replace('\\', "/")
+    } else {
+        path.to_string()
+    };
+    path.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+/// 向工具参数加上 --no-interaction：显式 --no-interaction 时总是加上，stdin 非终端的自动检测只对
+/// NO_INTERACTION_TOOLS 中的工具生效。插在用户的 `--` 之前，避免被当作位置参数；已有时不重复添加
+fn push_no_interaction(args: &mut Vec<String>, tool: &str, explicit: bool, auto: bool) {
+    let tool = tool.rsplit('/').next().unwrap_or(tool);
+    let wanted = explicit || (auto && NO_INTERACTION_TOOLS.contains(&tool));
+    if !wanted {
+        return;
+    }
+    let end = args.iter().position(|a| a == "--").unwrap_or(args.len());
+    if args[..end].iter().any(|a| a == "--no-interaction") {
+        return;
+    }
+    args.insert(end, "--no-interaction".to_string());
+}
+
+/// 在终端中询问是否重新安装不满足当前 PHP 的 Composer 工具；非交互环境直接返回 false
+fn confirm_reinstall(tool: &str, version: &str, reason: &str) -> bool {
+    if !std::io::stdin().is_terminal() || !std::io::stderr().is_terminal() {
+        return false;
+    }
+    eprint!(
+        "Cached {}@{} {}. Re-install it now? [y/N] ",
+        tool, version, reason
+    );
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_interaction_is_auto_added_only_for_known_tools_and_before_double_dash() {
+        let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        let mut phpunit = args(&["--filter", "Foo"]);
+        push_no_interaction(&mut phpunit, "phpunit/phpunit", false, true);
+        assert_eq!(phpunit, args(&["--filter", "Foo"]));
+
+        let mut rector = args(&["process", "--", "--dry-run"]);
+        push_no_interaction(&mut rector, "rector/rector", false, true);
+        assert_eq!(
+            rector,
+            args(&["process", "--no-interaction", "--", "--dry-run"])
+        );
+
+        // 显式 --no-interaction 对任何工具都生效
+        let mut phpcs = args(&["src"]);
+        push_no_interaction(&mut phpcs, "phpcs", true, false);
+        assert_eq!(phpcs, args(&["src", "--no-interaction"]));
+        push_no_interaction(&mut phpcs, "phpcs", true, false);
+        assert_eq!(phpcs, args(&["src", "--no-interaction"]));
+    }
+
+    #[tokio::test]
+    async fn unreachable_sources_are_not_negative_cached() {
+        let dir = tempfile::tempdir().unwrap();
+        // 绑定后立即释放的端口：连接被拒绝，相当于镜像不可达
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let base = format!("http://127.0.0.1:{}", port);
+        let config = Config {
+            cache_dir: dir.path().join("cache"),
+            packagist_base: base.clone(),
+            github_api_base: base.clone(),
+            github_download_base: base,
+            mirror_health_check: true,
+            ..Config::default()
+        };
+        let mut runner = Runner::from_config(config).unwrap();
+        let identifier = runner.resolver.parse_identifier("acme/tool").unwrap();
+
+        match runner.resolve_with_negative_cache(&identifier, false).await {
+            Err(Error::ToolNotFound(failure)) => assert!(failure.transient),
+            other => panic!("expected a transient ToolNotFound, got {:?}", other.is_ok()),
+        }
+        let key = Runner::negative_cache_key(&identifier);
+        assert!(!runner
+            .cache_manager
+            .is_known_missing(&key, NEGATIVE_CACHE_TTL));
+    }
+
+    #[test]
+    fn bin_entry_finds_windows_cmd_proxies() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("phpstan.cmd"), "").unwrap();
+        assert_eq!(
+            Runner::bin_entry(dir.path(), "phpstan"),
+            Some(dir.path().join("phpstan.cmd"))
+        );
+        assert_eq!(Runner::bin_entry(dir.path(), "psalm"), None);
+    }
+
+    #[test]
+    fn no_verify_tls_flag_and_env_var_accept_invalid_certs() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            cache_dir: dir.path().to_path_buf(),
+            no_verify_tls: true,
+            ..Config::default()
+        };
+        assert!(Runner::tls_verification_disabled(&config, None));
+        let runner = Runner::from_config(config).unwrap();
+        assert!(runner.downloader.accepts_invalid_certs());
+        assert!(runner.resolver.accepts_invalid_certs());
+
+        let config = Config::default();
+        assert!(Runner::tls_verification_disabled(&config, Some("1")));
+        assert!(Runner::tls_verification_disabled(&config, Some("TRUE")));
+        assert!(!Runner::tls_verification_disabled(&config, Some("0")));
+        assert!(!Runner::tls_verification_disabled(&config, None));
+    }
+
+    #[test]
+    fn unwritable_cache_dir_falls_back_to_a_removed_temp_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        // 父路径是普通文件，任何用户（包括 root）都无法在其下创建目录
+        let blocker = dir.path().join("not-a-dir");
+        std::fs::write(&blocker, b"").unwrap();
+        let config = Config {
+            cache_dir: blocker.join("cache"),
+            ..Config::default()
+        };
+
+        let runner = Runner::from_config(config).unwrap();
+        assert!(runner.uses_temporary_cache());
+        let temp = runner.config.cache_dir.clone();
+        assert!(temp.is_dir());
+        assert!(!temp.starts_with(dir.path()));
+        drop(runner);
+        assert!(!temp.exists());
+    }
+
+    #[test]
+    fn local_tool_version_from_installed_json_gates_requested_versions() {
+        let dir = tempfile::tempdir().unwrap();
+        let vendor = dir.path().join("vendor");
+        std::fs::create_dir_all(vendor.join("bin")).unwrap();
+        std::fs::create_dir_all(vendor.join("composer")).unwrap();
+        std::fs::write(
+            vendor.join("composer").join("installed.json"),
+            r#"{"packages":[
+                {"name":"psr/log","version":"3.0.0"},
+                {"name":"phpstan/phpstan","version":"1.11.2","bin":["phpstan","phpstan.phar"]}
+            ]}"#,
+        )
+        .unwrap();
+
+        let bin = vendor.join("bin").join("phpstan");
+        assert_eq!(
+            Runner::local_tool_version(&bin, "phpstan").as_deref(),
+            Some("1.11.2")
+        );
+        assert_eq!(Runner::local_tool_version(&bin, "psalm"), None);
+
+        let resolver = ToolResolver::new(ResolverConfig::default());
+        let satisfies = |spec: &str| {
+            let identifier = resolver.parse_identifier(spec).unwrap();
+            Runner::local_satisfies(&identifier, &bin, "phpstan", &ToolSource::LocalVendor)
+        };
+        assert!(satisfies("phpstan"));
+        assert!(satisfies("phpstan@^1.10"));
+        assert!(satisfies("phpstan@=1.11.2"));
+        assert!(!satisfies("phpstan@^2"));
+        assert!(!satisfies("phpstan@=1.10.0"));
+    }
+
+    #[test]
+    fn bootstrap_path_strips_windows_extended_prefix() {
+        assert_eq!(
+            php_quoted_path(
+                r"\\?\C:\Users\dev\AppData\Local\phpx\override\vendor\autoload.php",
+                true
+            ),
+            "C:/Users/dev/AppData/Local/phpx/override/vendor/autoload.php"
+        );
+        assert_eq!(
+            php_quoted_path(r"\\?\UNC\server\share\phpx\autoload.php", true),
+            "//server/share/phpx/autoload.php"
+        );
+        assert_eq!(
+            php_quoted_path("/home/o'brien/phpx/autoload.php", false),
+            r"/home/o\'brien/phpx/autoload.php"
+        );
+    }
+
+    #[test]
+    fn vendor_package_phar_is_cached_flat_under_original_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            cache_dir: dir.path().to_path_buf(),
+            ..Config::default()
+        };
+        let mut runner = Runner::from_config(config).unwrap();
+        let tool_info = ToolInfo {
+            name: "friendsofphp/php-cs-fixer".to_string(),
+            version: "3.64.0".to_string(),
+            download_url: "https://example.test/php-cs-fixer.phar".to_string(),
+            signature_url: None,
+            hash: None,
+        };
+
+        let path = runner.phar_cache_path(&tool_info);
+        assert_eq!(
+            path,
+            dir.path().join("friendsofphp-php-cs-fixer-3.64.0.phar")
+        );
+
+        std::fs::write(&path, b"phar").unwrap();
+        runner
+            .record_downloaded_tool(&tool_info, &path, true)
+            .unwrap();
+        let entry = runner
+            .cache_manager
+            .get_entry("friendsofphp/php-cs-fixer", "3.64.0")
+            .unwrap();
+        assert_eq!(entry.file_path, path);
+    }
+
+    #[test]
+    fn constraint_reuses_highest_matching_cached_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            cache_dir: dir.path().to_path_buf(),
+            ..Config::default()
+        };
+        let mut runner = Runner::from_config(config).unwrap();
+        for version in ["3.1.0", "3.2.0", "4.0.0"] {
+            let path = dir.path().join(format!("tool-{}.phar", version));
+            std::fs::write(&path, b"phar").unwrap();
+            runner
+                .cache_manager
+                .add_entry(
+                    "tool".to_string(),
+                    version.to_string(),
+                    path,
+                    String::new(),
+                    None,
+                    4,
+                )
+                .unwrap();
+        }
+        // 非 semver 版本只能靠记录下来的约束匹配
+        let path = dir.path().join("tool-nightly.phar");
+        std::fs::write(&path, b"phar").unwrap();
+        runner
+            .cache_manager
+            .add_entry(
+                "tool".to_string(),
+                "nightly".to_string(),
+                path,
+                String::new(),
+                None,
+                4,
+            )
+            .unwrap();
+        runner
+            .cache_manager
+            .set_constraint("tool", "nightly", ">=5")
+            .unwrap();
+
+        let req = |c: &str| semver::VersionReq::parse(c).unwrap();
+        assert_eq!(
+            runner.cached_version_matching("tool", &req("^3"), false),
+            Some("3.2.0".to_string())
+        );
+        assert_eq!(
+            runner.cached_version_matching("tool", &req(">=5"), false),
+            Some("nightly".to_string())
+        );
+        assert_eq!(
+            runner.cached_version_matching("tool", &req("^2"), false),
+            None
+        );
+    }
+}
@@ -1,8 +1,129 @@
 use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// 文件哈希算法：Packagist 给出 SHA-1，GitHub 与新缓存条目使用 SHA-256，旧缓存条目与 .md5 校验和为 MD5
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    Md5,
+    Sha1,
+    #[default]
+    Sha256,
+}
+
+impl HashAlgorithm {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Md5 => "md5",
+            Self::Sha1 => "sha1",
+            Self::Sha256 => "sha256",
+        }
+    }
+
+    /// 按十六进制摘要的长度推断算法，用于没有前缀的旧哈希
+    fn from_hex_len(len: usize) -> Option<Self> {
+        match len {
+            32 => Some(Self::Md5),
+            40 => Some(Self::Sha1),
+            64 => Some(Self::Sha256),
+            _ => None,
+        }
+    }
+
+    /// 文件内容的十六进制摘要
+    pub fn digest_file(self, file_path: &Path) -> Result<String> {
+        fn hex<D: sha2::Digest + std::io::Write>(
+            mut hasher: D,
+            file_path: &Path,
+        ) -> Result<String> {
+            std::io::copy(&mut std::fs::File::open(file_path)?, &mut hasher)?;
+            Ok(hasher
+                .finalize()
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect())
+        }
+        match self {
+            Self::Md5 => {
+                let mut context = md5::Context::new();
+                std::io::copy(&mut std::fs::File::open(file_path)?, &mut context)?;
+                Ok(format!("{:x}", context.compute()))
+            }
+            Self::Sha1 => hex(sha1::Sha1::default(), file_path),
+            Self::Sha256 => hex(sha2::Sha256::default(), file_path),
+        }
+    }
+
+    /// 带算法前缀的哈希（如 sha256:ab12…），缓存条目以这种形式记录
+    pub fn tag(self, digest: &str) -> String {
+        format!("{}:{}", self.name(), digest)
+    }
+}
+
+/// 拆分期望的哈希：`sha256:…` 等带前缀的形式，或不带前缀时按长度推断（32 位 md5、40 位 sha1、64 位 sha256）
+pub fn parse_hash(expected: &str) -> Result<(HashAlgorithm, &str)> {
+    let expected = expected.trim();
+    let parsed = match expected.split_once(':') {
+        Some((prefix, digest)) => {
+            let algorithm = match prefix.to_ascii_lowercase().as_str() {
+                "md5" => Some(HashAlgorithm::Md5),
+                "sha1" => Some(HashAlgorithm::Sha1),
+                "sha256" => Some(HashAlgorithm::Sha256),
+                _ => None,
+            };
+            algorithm.map(|algorithm| (algorithm, digest))
+        }
+        None => HashAlgorithm::from_hex_len(expected.len()).map(|algorithm| (algorithm, expected)),
+    };
+    parsed.ok_or_else(|| Error::Security(format!("Unsupported hash format: {}", expected)))
+}
+
+/// 一次哈希校验的结果
+#[derive(Debug, Clone, Serialize)]
+pub struct HashCheck {
+    pub algorithm: &'static str,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl HashCheck {
+    /// 按 expected 标明（或推断）的算法计算文件哈希
+    pub fn compute(file_path: &Path, expected: &str) -> Result<Self> {
+        let (algorithm, digest) = parse_hash(expected)?;
+        Ok(Self {
+            algorithm: algorithm.name(),
+            expected: digest.to_string(),
+            actual: algorithm.digest_file(file_path)?,
+        })
+    }
+
+    pub fn matches(&self) -> bool {
+        self.actual.eq_ignore_ascii_case(&self.expected)
+    }
+}
+
+/// 设为 1 / true / yes 时全局关闭签名与哈希校验，便于排查问题时不改配置、不改命令
+pub const SKIP_VERIFY_ENV: &str = "PHPX_SKIP_VERIFY";
 
 pub struct SecurityManager {
-    /// 是否默认跳过签名/哈希验证（来自配置）
+    /// 是否默认跳过签名/哈希验证（来自配置或 PHPX_SKIP_VERIFY）
     skip_verify: bool,
+    /// 记录新缓存条目哈希所用的算法
+    hash_algorithm: HashAlgorithm,
+}
+
+/// 环境变量是否为真值（1、true、yes，不区分大小写）
+pub(crate) fn env_flag(name: &str) -> bool {
+    std::env::var(name).is_ok_and(|value| is_truthy(&value))
+}
+
+/// 取值是否为真值，见 env_flag
+pub(crate) fn is_truthy(value: &str) -> bool {
+    matches!(
+        value.trim().to_ascii_lowercase().as_str(),
+        "1" | "true" | "yes"
+    )
 }
 
 impl Default for SecurityManager {
@@ -13,41 +134,94 @@ impl Default for SecurityManager {
 
 impl SecurityManager {
     pub fn new(skip_verify: bool) -> Self {
-        Self { skip_verify }
+        let forced = env_flag(SKIP_VERIFY_ENV);
+        if forced {
+            tracing::warn!(
+                "{} is set: signature and hash verification is DISABLED for all tools",
+                SKIP_VERIFY_ENV
+            );
+        }
+        Self {
+            skip_verify: skip_verify || forced,
+            hash_algorithm: HashAlgorithm::default(),
+        }
+    }
+
+    pub fn with_hash_algorithm(mut self, algorithm: HashAlgorithm) -> Self {
+        self.hash_algorithm = algorithm;
+        self
     }
 
-    pub fn verify_signature(
-        &self,
-        _file_path: &std::path::Path,
-        _signature_url: Option<&str>,
-    ) -> Result<()> {
+    pub fn verify_signature(&self, _file_path: &Path, _signature_url: Option<&str>) -> Result<()> {
         // TODO: 实现 GPG 签名验证
         tracing::warn!("GPG signature verification not implemented yet");
         Ok(())
     }
 
-    pub fn verify_hash(&self, file_path: &std::path::Path, expected_hash: &str) -> Result<()> {
-        use std::fs::File;
-        use std::io::Read;
-
-        let mut file = File::open(file_path)?;
-        let mut buffer = Vec::new();
-        file.read_to_end(&mut buffer)?;
-
-        let actual_hash = format!("{:x}", md5::compute(&buffer));
-
-        if actual_hash == expected_hash {
-            tracing::info!("File hash verification successful");
+    /// 校验文件哈希；expected_hash 可带算法前缀（sha256:…），见 parse_hash
+    pub fn verify_hash(&self, file_path: &Path, expected_hash: &str) -> Result<()> {
+        let check = self.check_hash(file_path, expected_hash)?;
+        if check.matches() {
+            tracing::info!("File {} verification successful", check.algorithm);
             Ok(())
         } else {
             Err(Error::Security(format!(
-                "Hash mismatch: expected {}, got {}",
-                expected_hash, actual_hash
+                "Hash mismatch ({}): expected {}, got {}",
+                check.algorithm, check.expected, check.actual
             )))
         }
     }
 
+    /// 计算文件哈希并与 expected_hash 比较，不论是否匹配都返回两边的值
+    pub fn check_hash(&self, file_path: &Path, expected_hash: &str) -> Result<HashCheck> {
+        HashCheck::compute(file_path, expected_hash)
+    }
+
+    /// 用配置的算法计算文件哈希，返回带算法前缀的形式
+    pub fn hash_file(&self, file_path: &Path) -> Result<String> {
+        let digest = self.hash_algorithm.digest_file(file_path)?;
+        Ok(self.hash_algorithm.tag(&digest))
+    }
+
+    /// 文件内容的 sha256（十六进制）
+    pub fn sha256_file(file_path: &Path) -> Result<String> {
+        HashAlgorithm::Sha256.digest_file(file_path)
+    }
+
     pub fn skip_verification(&self) -> bool {
         self.skip_verify
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_hash_picks_the_algorithm_from_the_prefix_or_length() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tool.phar");
+        std::fs::write(&path, b"abc").unwrap();
+        let manager = SecurityManager::new(false);
+
+        let md5 = "900150983cd24fb0d6963f7d28e17f72";
+        let sha1 = "a9993e364706816aba3e25717850c26c9cd0d89d";
+        let sha256 = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        for expected in [
+            md5.to_string(),
+            sha1.to_string(),
+            sha256.to_string(),
+            format!("SHA256:{}", sha256.to_uppercase()),
+        ] {
+            manager.verify_hash(&path, &expected).unwrap();
+        }
+        assert!(manager
+            .verify_hash(&path, &format!("sha1:{}", md5))
+            .is_err());
+        assert!(manager.verify_hash(&path, "crc32:352441c2").is_err());
+        assert_eq!(
+            manager.hash_file(&path).unwrap(),
+            format!("sha256:{}", sha256)
+        );
+    }
+}
@@ -0,0 +1,165 @@
+//! `phpx global`：在 bin_dir 中写入固定版本的包装脚本（shim），
+//! 使 PATH 上的 `phpstan` 等命令透明地执行 `phpx phpstan@=<版本>`。
+//! 脚本第二行带有 SHIM_MARKER 标记，list / remove 只处理带标记的文件，不会碰 bin_dir 中的其它程序。
+
+use crate::error::{Error, Result};
+use std::path::{Path, PathBuf};
+
+/// 写在 shim 中的标记，其后为固定的工具标识符
+const SHIM_MARKER: &str = "phpx shim: ";
+
+/// bin_dir 中的一个 shim
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct Shim {
+    /// 命令名（文件名去掉 .cmd）
+    pub name: String,
+    /// shim 运行的工具标识符，如 phpstan@=1.11.2
+    pub spec: String,
+    pub path: PathBuf,
+}
+
+/// 命令名对应的 shim 文件路径：Windows 下为 <name>.cmd
+pub fn shim_path(bin_dir: &Path, name: &str) -> PathBuf {
+    if cfg!(windows) {
+        bin_dir.join(format!("{}.cmd", name))
+    } else {
+        bin_dir.join(name)
+    }
+}
+
+/// 写入 shim；已有同名文件且不是 phpx 的 shim 时拒绝覆盖
+pub fn install(bin_dir: &Path, name: &str, spec: &str, phpx: &Path) -> Result<PathBuf> {
+    if name.is_empty() || name.contains(['/', '\\']) {
+        return Err(Error::InvalidToolIdentifier(format!(
+            "'{}' is not a valid command name",
+            name
+        )));
+    }
+    let path = shim_path(bin_dir, name);
+    if path.exists() && read_spec(&path).is_none() {
+        return Err(Error::Execution(format!(
+            "{} already exists and was not created by phpx",
+            path.display()
+        )));
+    }
+    std::fs::create_dir_all(bin_dir)?;
+    std::fs::write(&path, script(spec, phpx, cfg!(windows)))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(path)
+}
+
+/// 删除 shim，返回是否存在；同名文件不是 phpx 的 shim 时报错
+pub fn remove(bin_dir: &Path, name: &str) -> Result<bool> {
+    let path = shim_path(bin_dir, name);
+    if !path.exists() {
+        return Ok(false);
+    }
+    if read_spec(&path).is_none() {
+        return Err(Error::Execution(format!(
+            "{} was not created by phpx, leaving it alone",
+            path.display()
+        )));
+    }
+    std::fs::remove_file(&path)?;
+    Ok(true)
+}
+
+/// 列出 bin_dir 中 phpx 写入的 shim，按名称排序
+pub fn list(bin_dir: &Path) -> Result<Vec<Shim>> {
+    let Ok(entries) = std::fs::read_dir(bin_dir) else {
+        return Ok(Vec::new());
+    };
+    let mut shims: Vec<Shim> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter_map(|path| {
+            let spec = read_spec(&path)?;
+            let file_name = path.file_name()?.to_str()?;
+            let name = file_name
+                .strip_suffix(".cmd")
+                .unwrap_or(file_name)
+                .to_string();
+            Some(Shim { name, spec, path })
+        })
+        .collect();
+    shims.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(shims)
+}
+
+/// dir 是否在 PATH 中，用于提示用户把 bin_dir 加入 PATH
+pub fn on_path(dir: &Path) -> bool {
+    std::env::var_os("PATH").is_some_and(|paths| std::env::split_paths(&paths).any(|p| p == dir))
+}
+
+/// 从 shim 的标记行取出工具标识符；不是 phpx 的 shim 时为 None
+fn read_spec(path: &Path) -> Option<String> {
+    let content = std::fs::read_to_string(path).ok()?;
+    content.lines().take(3).find_map(|line| {
+        let (_, spec) = line.split_once(SHIM_MARKER)?;
+        Some(spec.trim().to_string())
+    })
+}
+
+/// shim 内容：Unix 为 sh 脚本，Windows 为 .cmd；`--` 之后的参数原样交给工具，不被 phpx 解析
+fn script(spec: &str, phpx: &Path, windows: bool) -> String {
+    if windows {
+        format!(
+            "@echo off\r\nrem {}{}\r\n\"{}\" \"{}\" -- %*\r\nexit /b %ERRORLEVEL%\r\n",
+            SHIM_MARKER,
+            spec,
+            phpx.display(),
+            spec
+        )
+    } else {
+        format!(
+            "#!/bin/sh\n# {}{}\nexec '{}' '{}' -- \"$@\"\n",
+            SHIM_MARKER,
+            spec,
+            phpx.display().to_string().replace('\'', r"'\''"),
+            spec.replace('\'', r"'\''")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn install_list_and_remove_only_touch_phpx_shims() {
+        let dir = tempfile::tempdir().unwrap();
+        let phpx = Path::new("/opt/phpx/bin/phpx");
+        let path = install(dir.path(), "phpstan", "phpstan/phpstan@=1.11.2", phpx).unwrap();
+        assert!(std::fs::read_to_string(&path)
+            .unwrap()
+            .contains("'phpstan/phpstan@=1.11.2' --"));
+
+        let other = shim_path(dir.path(), "composer");
+        std::fs::write(&other, "#!/bin/sh\necho not ours\n").unwrap();
+        assert!(install(dir.path(), "composer", "composer", phpx).is_err());
+
+        assert_eq!(
+            list(dir.path()).unwrap(),
+            vec![Shim {
+                name: "phpstan".to_string(),
+                spec: "phpstan/phpstan@=1.11.2".to_string(),
+                path: path.clone(),
+            }]
+        );
+        assert!(remove(dir.path(), "composer").is_err());
+        assert!(remove(dir.path(), "phpstan").unwrap());
+        assert!(!remove(dir.path(), "phpstan").unwrap());
+        assert!(other.exists());
+    }
+
+    #[test]
+    fn windows_shim_is_a_cmd_script() {
+        let content = script("psalm@=5.0.0", Path::new(r"C:\phpx\phpx.exe"), true);
+        assert!(content.starts_with("@echo off\r\nrem phpx shim: psalm@=5.0.0\r\n"));
+        assert!(content.contains("\"C:\\phpx\\phpx.exe\" \"psalm@=5.0.0\" -- %*"));
+    }
+}
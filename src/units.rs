@@ -0,0 +1,83 @@
+//! 命令行参数中的人类可读时长（30d、12h）与大小（50MB、1.5GiB）的解析与格式化
+
+/// 解析时长，返回秒数；支持 s / m / h / d / w 后缀，不带后缀按秒计
+pub fn parse_duration(raw: &str) -> Result<u64, String> {
+    let s = raw.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let value: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration '{}', expected e.g. 30d or 12h", raw))?;
+    let multiplier = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        other => return Err(format!("unknown duration unit '{}' in '{}'", other, raw)),
+    };
+    value
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("duration '{}' is too large", raw))
+}
+
+/// 解析大小，返回字节数；K / M / G 均按 1024 进制（KB 与 KiB 等价），不带单位按字节计
+pub fn parse_size(raw: &str) -> Result<u64, String> {
+    let s = raw.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let value: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid size '{}', expected e.g. 50MB or 1GiB", raw))?;
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1 << 10,
+        "m" | "mb" | "mib" => 1 << 20,
+        "g" | "gb" | "gib" => 1 << 30,
+        other => return Err(format!("unknown size unit '{}' in '{}'", other, raw)),
+    };
+    let bytes = value * multiplier as f64;
+    if !bytes.is_finite() || bytes > u64::MAX as f64 {
+        return Err(format!("size '{}' is too large", raw));
+    }
+    Ok(bytes as u64)
+}
+
+/// 字节数格式化为 1024 进制的可读形式
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations_and_sizes() {
+        assert_eq!(parse_duration("30d"), Ok(30 * 24 * 60 * 60));
+        assert_eq!(parse_duration("12h"), Ok(12 * 60 * 60));
+        assert_eq!(parse_duration("90"), Ok(90));
+        assert!(parse_duration("3y").is_err());
+
+        assert_eq!(parse_size("50MB"), Ok(50 * 1024 * 1024));
+        assert_eq!(parse_size("1.5 GiB"), Ok(3 * 512 * 1024 * 1024));
+        assert_eq!(parse_size("512"), Ok(512));
+        assert!(parse_size("10 parsecs").is_err());
+
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(50 * 1024 * 1024), "50.0 MB");
+    }
+}
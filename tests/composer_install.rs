@@ -0,0 +1,94 @@
+//! 通过公开的 phpx::composer 接口验证隔离安装约定；用一个假的 composer 脚本代替真实 Composer，不访问网络
+#![cfg(unix)]
+
+use phpx::cache::CacheManager;
+use phpx::composer::{ensure_composer_installed, install_version};
+use phpx::config::Config;
+use phpx::progress::NoProgress;
+use phpx::resolver::ComposerPackage;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+fn write_script(path: &Path, content: &str) {
+    std::fs::write(path, content).unwrap();
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+/// 假的 composer：记录调用次数与收到的 COMPOSER_HOME，并在当前目录生成 vendor/bin/tool
+fn fake_composer(dir: &Path) -> PathBuf {
+    let script = dir.join("composer");
+    write_script(
+        &script,
+        &format!(
+            "#!/bin/sh\necho run >> '{log}'\necho \"$COMPOSER_HOME\" > '{home}'\n\
+             mkdir -p vendor/bin && touch vendor/bin/tool\n",
+            log = dir.join("calls.log").display(),
+            home = dir.join("home.txt").display()
+        ),
+    );
+    script
+}
+
+/// 假的 PHP：只回答安装后记录的 PHP 版本
+fn fake_php(dir: &Path) -> PathBuf {
+    let php = dir.join("php");
+    write_script(&php, "#!/bin/sh\necho 8.2.0\n");
+    php
+}
+
+#[test]
+fn isolated_install_follows_the_documented_contract() {
+    let dir = tempfile::tempdir().unwrap();
+    let composer = fake_composer(dir.path());
+    let php = fake_php(dir.path());
+    let config = Config {
+        cache_dir: dir.path().join("cache"),
+        composer_path: Some(composer.clone()),
+        ..Config::default()
+    };
+    let mut cache_manager = CacheManager::new(config.cache_dir.clone()).unwrap();
+    let pkg = ComposerPackage {
+        package: "acme/tool".to_string(),
+        version: "1.2.0".to_string(),
+        bin_names: vec!["tool".to_string()],
+        require_dev: Default::default(),
+    };
+    let install = |cache_manager: &mut CacheManager| {
+        ensure_composer_installed(
+            &pkg,
+            false,
+            &config.install_base(),
+            cache_manager,
+            &config,
+            Some(&php),
+            &NoProgress,
+        )
+        .unwrap()
+    };
+
+    let (install_dir, vendor_bin) = install(&mut cache_manager);
+    // 每个 包@版本 独占安装根目录下的一个目录，只含生成的 composer.json 与 composer 的产物
+    assert!(install_dir.starts_with(config.install_base().join("composer")));
+    assert!(vendor_bin.starts_with(&install_dir) && vendor_bin.is_file());
+    let manifest: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(install_dir.join("composer.json")).unwrap())
+            .unwrap();
+    assert_eq!(manifest["require"]["acme/tool"], "1.2.0");
+    // COMPOSER_HOME 指向缓存目录而不是用户的全局 Composer
+    let home = std::fs::read_to_string(dir.path().join("home.txt")).unwrap();
+    assert_eq!(
+        Path::new(home.trim()),
+        config.cache_dir.join("composer_home")
+    );
+    let entry = cache_manager
+        .get_entry("acme/tool", &install_version("1.2.0", false))
+        .unwrap();
+    assert!(entry.is_composer && entry.file_path == install_dir);
+    assert_eq!(entry.php_version.as_deref(), Some("8.2.0"));
+
+    // 已有完整安装时直接返回，不再调用 Composer
+    let (again, _) = install(&mut cache_manager);
+    assert_eq!(again, install_dir);
+    let calls = std::fs::read_to_string(dir.path().join("calls.log")).unwrap();
+    assert_eq!(calls.lines().count(), 1);
+}
@@ -0,0 +1,590 @@
+//! 使用 mock HTTP 服务器验证 Packagist / GitHub 解析逻辑，不访问真实网络
+
+use mockito::{Server, ServerGuard};
+use phpx::resolver::{ResolvedTool, ResolverConfig, SourcePreference, Stability, ToolResolver};
+
+fn resolver_for(server: &ServerGuard) -> ToolResolver {
+    ToolResolver::new(ResolverConfig {
+        packagist_base: server.url(),
+        github_api_base: server.url(),
+        github_download_base: server.url(),
+    })
+}
+
+/// 刚绑定又释放的本机端口：没有进程监听，连接会立即被拒绝
+fn closed_port_url() -> String {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    drop(listener);
+    format!("http://127.0.0.1:{}", port)
+}
+
+async fn resolve(server: &ServerGuard, identifier: &str) -> phpx::Result<ResolvedTool> {
+    let resolver = resolver_for(server);
+    let identifier = resolver.parse_identifier(identifier)?;
+    resolver.resolve_tool(&identifier).await
+}
+
+#[tokio::test]
+async fn packagist_path_dist_resolves_to_phar() {
+    let mut server = Server::new_async().await;
+    let phar_url = format!("{}/dist/phpstan.phar", server.url());
+    server
+        .mock("GET", "/packages/phpstan/phpstan.json")
+        .with_header("content-type", "application/json")
+        .with_body(format!(
+            r#"{{"package":{{"versions":{{
+                "1.10.0":{{"dist":{{"type":"path","url":"{url}"}}}},
+                "1.11.0":{{"dist":{{"type":"path","url":"{url}"}}}},
+                "2.0.0":{{"dist":{{"type":"path","url":"{url}"}}}}
+            }}}}}}"#,
+            url = phar_url
+        ))
+        .create_async()
+        .await;
+
+    match resolve(&server, "phpstan/phpstan@^1.10").await.unwrap() {
+        ResolvedTool::Phar(info) => {
+            assert_eq!(info.version, "1.11.0");
+            assert_eq!(info.download_url, phar_url);
+        }
+        other => panic!("expected phar, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn single_segment_name_falls_back_to_vendor_package_with_zip_dist() {
+    let mut server = Server::new_async().await;
+    server
+        .mock("GET", "/packages/rector/rector.json")
+        .with_header("content-type", "application/json")
+        .with_body(
+            r#"{"package":{"versions":{
+                "1.2.0":{"bin":["bin/rector"],"dist":{"type":"zip","url":"https://example.test/rector.zip"}}
+            }}}"#,
+        )
+        .create_async()
+        .await;
+
+    match resolve(&server, "rector").await.unwrap() {
+        ResolvedTool::Composer(pkg) => {
+            assert_eq!(pkg.package, "rector/rector");
+            assert_eq!(pkg.version, "1.2.0");
+            assert_eq!(pkg.bin_names, vec!["rector"]);
+        }
+        other => panic!("expected composer package, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn github_release_is_found_under_uppercase_repo_name() {
+    let mut server = Server::new_async().await;
+    let phar_url = format!("{}/downloads/php-cs-fixer.phar", server.url());
+    server
+        .mock("GET", "/repos/PHP-CS-Fixer/PHP-CS-Fixer/releases")
+        .with_header("content-type", "application/json")
+        .with_body(format!(
+            r#"[{{"tag_name":"v3.64.0","assets":[
+                {{"name":"php-cs-fixer.phar","browser_download_url":"{url}","url":"{url}"}},
+                {{"name":"php-cs-fixer.phar.asc","browser_download_url":"{url}.asc"}}
+            ]}}]"#,
+            url = phar_url
+        ))
+        .create_async()
+        .await;
+
+    // Packagist 与其它大小写变体均未配置，mock 服务器返回 501，解析应继续尝试下一个来源
+    match resolve(&server, "php-cs-fixer").await.unwrap() {
+        ResolvedTool::Phar(info) => {
+            assert_eq!(info.version, "3.64.0");
+            assert_eq!(info.download_url, phar_url);
+            assert_eq!(info.signature_url, Some(format!("{}.asc", phar_url)));
+        }
+        other => panic!("expected phar, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn unknown_tool_is_not_found() {
+    let server = Server::new_async().await;
+    assert!(matches!(
+        resolve(&server, "does-not-exist").await,
+        Err(phpx::Error::ToolNotFound(_))
+    ));
+}
+
+#[tokio::test]
+async fn unreachable_packagist_mirror_is_skipped_when_health_check_is_enabled() {
+    let mut server = Server::new_async().await;
+    let phar_url = format!("{}/downloads/box.phar", server.url());
+    server
+        .mock("HEAD", "/")
+        .with_status(404)
+        .create_async()
+        .await;
+    server
+        .mock("GET", "/repos/box-project/box/releases")
+        .with_header("content-type", "application/json")
+        .with_body(format!(
+            r#"[{{"tag_name":"4.6.0","assets":[{{"name":"box.phar","browser_download_url":"{}"}}]}}]"#,
+            phar_url
+        ))
+        .create_async()
+        .await;
+
+    let resolver = ToolResolver::new(ResolverConfig {
+        packagist_base: closed_port_url(),
+        github_api_base: server.url(),
+        github_download_base: server.url(),
+    })
+    .with_health_check(true);
+    let identifier = resolver.parse_identifier("box-project/box").unwrap();
+    match resolver.resolve_tool(&identifier).await.unwrap() {
+        ResolvedTool::Phar(info) => assert_eq!(info.download_url, phar_url),
+        other => panic!("expected phar, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn unreachable_github_download_base_is_reported_without_suggestions() {
+    let mut server = Server::new_async().await;
+    server
+        .mock("HEAD", "/")
+        .with_status(404)
+        .create_async()
+        .await;
+    server
+        .mock("GET", mockito::Matcher::Regex("^/packages/".to_string()))
+        .with_status(404)
+        .create_async()
+        .await;
+    server
+        .mock("GET", mockito::Matcher::Regex("^/repos/".to_string()))
+        .with_header("content-type", "application/json")
+        .with_body("[]")
+        .create_async()
+        .await;
+    let search = server
+        .mock("GET", "/search.json")
+        .match_query(mockito::Matcher::Any)
+        .expect(0)
+        .create_async()
+        .await;
+
+    let download_base = closed_port_url();
+    let resolver = ToolResolver::new(ResolverConfig {
+        packagist_base: server.url(),
+        github_api_base: server.url(),
+        github_download_base: download_base.clone(),
+    })
+    .with_health_check(true);
+    let identifier = resolver.parse_identifier("acme/tool").unwrap();
+    match resolver.resolve_tool(&identifier).await {
+        Err(phpx::Error::ToolNotFound(failure)) => {
+            assert!(failure
+                .reasons
+                .contains(&format!("GitHub: {} is unreachable", download_base)));
+            assert!(failure.suggestions.is_empty());
+        }
+        other => panic!("expected ToolNotFound, got {:?}", other),
+    }
+    search.assert_async().await;
+}
+
+#[tokio::test]
+async fn failure_explains_library_packages_and_suggests_near_matches() {
+    let mut server = Server::new_async().await;
+    server
+        .mock("GET", "/packages/lint/lint.json")
+        .with_header("content-type", "application/json")
+        .with_body(
+            r#"{"package":{"versions":{
+                "1.0.0":{"dist":{"type":"zip","url":"https://example.test/lint.zip"}}
+            }}}"#,
+        )
+        .create_async()
+        .await;
+    server
+        .mock("GET", "/search.json")
+        .match_query(mockito::Matcher::Any)
+        .with_header("content-type", "application/json")
+        .with_body(r#"{"results":[{"name":"acme/linter"},{"name":"acme/lint"}]}"#)
+        .create_async()
+        .await;
+    // 其余包名与仓库都像真实主机一样回答 404，而不是 mockito 默认的 501
+    server
+        .mock("GET", "/packages/lint.json")
+        .with_status(404)
+        .create_async()
+        .await;
+    server
+        .mock("GET", mockito::Matcher::Regex("^/repos/".to_string()))
+        .with_status(404)
+        .create_async()
+        .await;
+
+    match resolve(&server, "lint").await {
+        Err(phpx::Error::ToolNotFound(failure)) => {
+            assert_eq!(
+                failure.reasons,
+                vec!["Packagist: lint/lint exists but is a library with no phar or bin to run"]
+            );
+            assert_eq!(failure.suggestions, vec!["acme/lint", "acme/linter"]);
+        }
+        other => panic!("expected ToolNotFound, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn rate_limited_github_suppresses_name_suggestions() {
+    let mut server = Server::new_async().await;
+    server
+        .mock("GET", mockito::Matcher::Regex("^/packages/".to_string()))
+        .with_status(404)
+        .create_async()
+        .await;
+    server
+        .mock("GET", mockito::Matcher::Regex("^/repos/".to_string()))
+        .with_status(403)
+        .with_header("x-ratelimit-remaining", "0")
+        .create_async()
+        .await;
+    let search = server
+        .mock("GET", "/search.json")
+        .match_query(mockito::Matcher::Any)
+        .with_header("content-type", "application/json")
+        .with_body(r#"{"results":[{"name":"acme/lint"}]}"#)
+        .expect(0)
+        .create_async()
+        .await;
+
+    match resolve(&server, "acme/lint").await {
+        Err(phpx::Error::ToolNotFound(failure)) => {
+            assert!(failure.transient);
+            assert!(failure.suggestions.is_empty());
+            assert!(failure.reasons.contains(
+                &"GitHub: API rate limit exceeded (set GITHUB_TOKEN to raise the limit)"
+                    .to_string()
+            ));
+        }
+        other => panic!("expected ToolNotFound, got {:?}", other),
+    }
+    search.assert_async().await;
+}
+
+#[tokio::test]
+async fn empty_github_release_list_falls_through_to_next_repo_variant() {
+    let mut server = Server::new_async().await;
+    let phar_url = format!("{}/downloads/box.phar", server.url());
+    server
+        .mock("GET", "/repos/box-project/box/releases")
+        .with_header("content-type", "application/json")
+        .with_body("[]")
+        .create_async()
+        .await;
+    server
+        .mock("GET", "/repos/box-project/php-box/releases")
+        .with_header("content-type", "application/json")
+        .with_body(format!(
+            r#"[{{"tag_name":"4.6.0","assets":[{{"name":"box.phar","browser_download_url":"{}"}}]}}]"#,
+            phar_url
+        ))
+        .create_async()
+        .await;
+
+    match resolve(&server, "box-project/box").await.unwrap() {
+        ResolvedTool::Phar(info) => assert_eq!(info.download_url, phar_url),
+        other => panic!("expected phar, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn github_error_object_is_reported_apart_from_missing_releases() {
+    let mut server = Server::new_async().await;
+    server
+        .mock("GET", "/repos/box-project/box/releases")
+        .with_header("content-type", "application/json")
+        .with_body("[]")
+        .create_async()
+        .await;
+    server
+        .mock("GET", "/repos/box-project/php-box/releases")
+        .with_header("content-type", "application/json")
+        .with_body(r#"{"message":"Repository access blocked"}"#)
+        .create_async()
+        .await;
+
+    let resolver = resolver_for(&server).with_suggestions(false);
+    let identifier = resolver.parse_identifier("box-project/box").unwrap();
+    match resolver.resolve_tool(&identifier).await {
+        Err(phpx::Error::ToolNotFound(failure)) => {
+            assert!(failure
+                .reasons
+                .contains(&"GitHub: box-project/box has no releases".to_string()));
+            assert!(failure.reasons.contains(
+                &"GitHub: box-project/php-box returned an error instead of releases: Repository access blocked"
+                    .to_string()
+            ));
+        }
+        other => panic!("expected ToolNotFound, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn github_tags_are_used_when_repo_has_no_releases() {
+    let mut server = Server::new_async().await;
+    server
+        .mock("GET", "/repos/acme/tool/releases")
+        .with_header("content-type", "application/json")
+        .with_body("[]")
+        .create_async()
+        .await;
+    server
+        .mock("GET", "/repos/acme/tool/tags")
+        .match_query(mockito::Matcher::Any)
+        .with_header("content-type", "application/json")
+        .with_body(r#"[{"name":"v2.0.0"},{"name":"v1.2.0"},{"name":"v1.10.0"}]"#)
+        .create_async()
+        .await;
+    server
+        .mock("HEAD", "/acme/tool/raw/v1.10.0/build/tool.phar")
+        .create_async()
+        .await;
+
+    match resolve(&server, "acme/tool@^1.0").await.unwrap() {
+        ResolvedTool::Phar(info) => {
+            assert_eq!(info.version, "1.10.0");
+            assert_eq!(
+                info.download_url,
+                format!("{}/acme/tool/raw/v1.10.0/build/tool.phar", server.url())
+            );
+        }
+        other => panic!("expected phar, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn release_notes_cover_versions_after_current_up_to_latest() {
+    let mut server = Server::new_async().await;
+    server
+        .mock("GET", "/repos/acme/tool/releases")
+        .match_query(mockito::Matcher::Any)
+        .with_header("content-type", "application/json")
+        .with_body(
+            r#"[
+                {"tag_name":"v1.3.0","assets":[],"body":"Not yet","published_at":"2024-03-01T00:00:00Z"},
+                {"tag_name":"v1.2.0","assets":[],"body":"Faster\r\n","published_at":"2024-02-01T10:00:00Z"},
+                {"tag_name":"v1.1.0","assets":[],"body":"Fixes","published_at":null},
+                {"tag_name":"v1.0.0","assets":[],"body":"Initial"}
+            ]"#,
+        )
+        .create_async()
+        .await;
+
+    let notes = resolver_for(&server)
+        .release_notes("acme/tool", "1.0.0", "1.2.0")
+        .await;
+    let summary: Vec<(&str, Option<&str>, &str)> = notes
+        .iter()
+        .map(|n| {
+            (
+                n.version.as_str(),
+                n.published_at.as_deref(),
+                n.body.as_str(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            ("1.2.0", Some("2024-02-01"), "Faster"),
+            ("1.1.0", None, "Fixes")
+        ]
+    );
+}
+
+#[tokio::test]
+async fn github_drafts_are_skipped_and_prereleases_need_pre() {
+    let mut server = Server::new_async().await;
+    let base = server.url();
+    let asset = |tag: &str| {
+        format!(
+            r#"[{{"name":"tool.phar","browser_download_url":"{}/{}/tool.phar"}}]"#,
+            base, tag
+        )
+    };
+    server
+        .mock("GET", "/repos/acme/tool/releases")
+        .with_header("content-type", "application/json")
+        .with_body(format!(
+            r#"[
+                {{"tag_name":"v3.0.0","draft":true,"assets":{}}},
+                {{"tag_name":"v2.0.0","prerelease":true,"published_at":"2024-05-01T00:00:00Z","assets":{}}},
+                {{"tag_name":"v1.5.0","published_at":"2024-04-01T00:00:00Z","assets":{}}}
+            ]"#,
+            asset("v3.0.0"),
+            asset("v2.0.0"),
+            asset("v1.5.0")
+        ))
+        .create_async()
+        .await;
+
+    let resolver = resolver_for(&server);
+    let identifier = resolver.parse_identifier("acme/tool").unwrap();
+    match resolver.resolve_tool(&identifier).await.unwrap() {
+        ResolvedTool::Phar(info) => assert_eq!(info.version, "1.5.0"),
+        other => panic!("expected phar, got {:?}", other),
+    }
+
+    let resolver = resolver_for(&server).with_min_stability(Stability::Dev);
+    match resolver.resolve_tool(&identifier).await.unwrap() {
+        ResolvedTool::Phar(info) => assert_eq!(info.version, "2.0.0"),
+        other => panic!("expected phar, got {:?}", other),
+    }
+}
+
+/// 同一版本同时在 Packagist 与 GitHub 上时，auto 固定使用 Packagist；--source github 只查 GitHub
+#[tokio::test]
+async fn packagist_wins_ties_with_github_unless_source_is_github() {
+    let mut server = Server::new_async().await;
+    let base = server.url();
+    server
+        .mock("GET", "/packages/acme/tool.json")
+        .with_header("content-type", "application/json")
+        .with_body(format!(
+            r#"{{"package":{{"versions":{{"1.0.0":{{"dist":{{"type":"path","url":"{}/packagist/tool.phar"}}}}}}}}}}"#,
+            base
+        ))
+        .create_async()
+        .await;
+    server
+        .mock("GET", "/repos/acme/tool/releases")
+        .with_header("content-type", "application/json")
+        .with_body(format!(
+            r#"[{{"tag_name":"v1.0.0","assets":[{{"name":"tool.phar","browser_download_url":"{}/github/tool.phar"}}]}}]"#,
+            base
+        ))
+        .create_async()
+        .await;
+
+    let download_url = |resolved: ResolvedTool| match resolved {
+        ResolvedTool::Phar(info) => info.download_url,
+        other => panic!("expected phar, got {:?}", other),
+    };
+    let resolver = resolver_for(&server);
+    let identifier = resolver.parse_identifier("acme/tool@=1.0.0").unwrap();
+    assert_eq!(
+        download_url(resolver.resolve_tool(&identifier).await.unwrap()),
+        format!("{}/packagist/tool.phar", base)
+    );
+    let resolver = resolver_for(&server).with_source(SourcePreference::Github);
+    assert_eq!(
+        download_url(resolver.resolve_tool(&identifier).await.unwrap()),
+        format!("{}/github/tool.phar", base)
+    );
+}
+
+/// Packagist 同一版本既有 path 又有 zip dist：运行工具取 phar，安装库包（prefer_library）取 zip
+#[tokio::test]
+async fn packagist_path_and_zip_for_one_version_follow_intent() {
+    let mut server = Server::new_async().await;
+    server
+        .mock("GET", "/packages/acme/tool.json")
+        .with_header("content-type", "application/json")
+        .with_body(format!(
+            r#"{{"package":{{"versions":{{
+                "1.0.0":{{"dist":{{"type":"path","url":"{}/tool.phar"}}}},
+                "v1.0.0":{{"dist":{{"type":"zip","url":"{}/tool.zip"}},"bin":["bin/tool"]}}
+            }}}}}}"#,
+            server.url(),
+            server.url()
+        ))
+        .create_async()
+        .await;
+
+    let resolver = resolver_for(&server).with_source(SourcePreference::Packagist);
+    let identifier = resolver.parse_identifier("acme/tool").unwrap();
+    match resolver.resolve_tool(&identifier).await.unwrap() {
+        ResolvedTool::Phar(info) => assert_eq!(info.version, "1.0.0"),
+        other => panic!("expected phar, got {:?}", other),
+    }
+    let resolver = resolver.with_prefer_library(true);
+    match resolver.resolve_tool(&identifier).await.unwrap() {
+        ResolvedTool::Composer(pkg) => {
+            assert_eq!(pkg.version, "1.0.0");
+            assert_eq!(pkg.bin_names, ["tool"]);
+        }
+        other => panic!("expected composer package, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn packagist_metadata_and_phar_are_read_from_a_file_mirror() {
+    let mirror = tempfile::tempdir().unwrap();
+    let packages = mirror.path().join("packages").join("phpstan");
+    std::fs::create_dir_all(&packages).unwrap();
+    let phar = mirror.path().join("phpstan-1.11.0.phar");
+    std::fs::write(&phar, b"<?php echo 'phpstan';").unwrap();
+    let phar_url = reqwest::Url::from_file_path(&phar).unwrap().to_string();
+    std::fs::write(
+        packages.join("phpstan.json"),
+        format!(
+            r#"{{"package":{{"versions":{{
+                "1.11.0":{{"dist":{{"type":"path","url":"{url}"}}}}
+            }}}}}}"#,
+            url = phar_url
+        ),
+    )
+    .unwrap();
+
+    let base = reqwest::Url::from_directory_path(mirror.path())
+        .unwrap()
+        .to_string();
+    let resolver = ToolResolver::new(ResolverConfig {
+        packagist_base: base.trim_end_matches('/').to_string(),
+        github_api_base: "http://127.0.0.1:9".to_string(),
+        github_download_base: base.trim_end_matches('/').to_string(),
+    })
+    .with_source(SourcePreference::Packagist);
+    let identifier = resolver.parse_identifier("phpstan/phpstan").unwrap();
+    let info = match resolver.resolve_tool(&identifier).await.unwrap() {
+        ResolvedTool::Phar(info) => info,
+        other => panic!("expected phar, got {:?}", other),
+    };
+    assert_eq!(info.version, "1.11.0");
+    assert_eq!(info.download_url, phar_url);
+
+    let dest = mirror.path().join("downloaded.phar");
+    phpx::download::Downloader::new()
+        .download_file(&info.download_url, &dest)
+        .await
+        .unwrap();
+    assert_eq!(std::fs::read(dest).unwrap(), std::fs::read(phar).unwrap());
+}
+
+#[tokio::test]
+async fn explicit_packagist_prerelease_is_found_under_stable_minimum() {
+    let mut server = Server::new_async().await;
+    let phar_url = format!("{}/dist/tool.phar", server.url());
+    server
+        .mock("GET", "/packages/acme/tool.json")
+        .with_header("content-type", "application/json")
+        .with_body(format!(
+            r#"{{"package":{{"versions":{{
+                "1.0.0":{{"dist":{{"type":"path","url":"{url}"}}}},
+                "2.0.0-beta1":{{"dist":{{"type":"path","url":"{url}"}}}}
+            }}}}}}"#,
+            url = phar_url
+        ))
+        .create_async()
+        .await;
+
+    match resolve(&server, "acme/tool@2.0.0-beta1").await.unwrap() {
+        ResolvedTool::Phar(info) => assert_eq!(info.version, "2.0.0-beta1"),
+        other => panic!("expected phar, got {:?}", other),
+    }
+    match resolve(&server, "acme/tool").await.unwrap() {
+        ResolvedTool::Phar(info) => assert_eq!(info.version, "1.0.0"),
+        other => panic!("expected phar, got {:?}", other),
+    }
+}